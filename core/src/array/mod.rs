@@ -1,12 +1,29 @@
 #![allow(clippy::len_without_is_empty)]
 
+mod slot_map;
+
+pub use slot_map::*;
+
 use core::{
     alloc::{self, Allocator, Layout},
-    mem, ops,
+    fmt, hash, iter,
+    marker::PhantomData,
+    mem::{self, ManuallyDrop},
+    ops,
     ptr::{self, slice_from_raw_parts_mut, NonNull},
     slice,
 };
 
+/// Returns the number of elements of type `T` which fit in an allocation of
+/// `size` bytes.
+#[inline(always)]
+const fn capacity_from_size<T>(size: usize) -> usize {
+    match mem::size_of::<T>() {
+        0 => usize::MAX,
+        n => size / n,
+    }
+}
+
 /// # Safety
 ///
 /// This method is safe to use as long as you use the same allocator is used
@@ -19,59 +36,67 @@ unsafe fn reserve<T>(
     additional: usize,
     alloc: &impl Allocator,
 ) -> Result<(), alloc::AllocError> {
+    let required = match length.checked_add(additional) {
+        Some(x) => x,
+        None => return Err(alloc::AllocError),
+    };
+    if required <= *capacity {
+        return Ok(());
+    }
+
+    if mem::size_of::<T>() == 0 {
+        // Zero sized types never need any memory.
+        *capacity = usize::MAX;
+        return Ok(());
+    }
+
+    // Grow geometrically so that repeatedly pushing is amortized `O(1)`.
+    let new_capacity = required.max(capacity.saturating_mul(2));
+    let new_layout = match Layout::array::<T>(new_capacity) {
+        Ok(l) => l,
+        Err(_) => return Err(alloc::AllocError),
+    };
+
     if *capacity == 0 {
         // Allocate for the first time.
-        let size = match mem::size_of::<T>().checked_mul(additional) {
-            Some(sz) => sz,
-            None => return Err(alloc::AllocError),
-        };
-        let layout = match Layout::from_size_align(size, mem::align_of::<T>())
-        {
-            Ok(layout) => layout,
-            Err(_) => return Err(alloc::AllocError),
-        };
-        let result = alloc.allocate(layout)?;
-        let size = result.len();
+        let result = alloc.allocate(new_layout)?;
         *data = result.cast();
-        *capacity = size;
-        *length = 0;
+        *capacity = capacity_from_size::<T>(result.len());
         return Ok(());
     }
-    let old_size = match mem::size_of::<T>().checked_mul(*capacity) {
-        Some(sz) => sz,
-        None => return Err(alloc::AllocError),
-    };
-    let new_size = {
-        let tmp = match capacity.checked_add(additional) {
-            Some(x) => x,
-            None => return Err(alloc::AllocError),
-        };
-        match mem::size_of::<T>().checked_mul(tmp) {
-            Some(x) => x,
-            None => return Err(alloc::AllocError),
-        }
-    };
 
-    let old_layout =
-        match Layout::from_size_align(old_size, mem::align_of::<T>()) {
-            Ok(l) => l,
-            Err(_) => return Err(alloc::AllocError),
-        };
-    let new_layout =
-        match Layout::from_size_align(new_size, mem::align_of::<T>()) {
-            Ok(l) => l,
-            Err(_) => return Err(alloc::AllocError),
-        };
+    // SAFETY: this layout was already successfully created when the memory
+    //         was allocated.
+    let old_layout = Layout::array::<T>(*capacity).unwrap_unchecked();
     // SAFETY: we know ptr is currently allocated, we know old_layout is the
     //         layout of ptr, and we know
     //         new_layout.size() >= old_layout.size().
     let result = unsafe { alloc.grow(data.cast(), old_layout, new_layout)? };
-    let size = result.len();
     *data = result.cast();
-    *capacity = size;
+    *capacity = capacity_from_size::<T>(result.len());
     Ok(())
 }
 
+/// # Safety
+///
+/// This method is safe to use as long as you use the same allocator is used
+/// each time the same data referenced is used.
+#[inline(always)]
+unsafe fn deallocate<T>(
+    data: NonNull<T>,
+    capacity: usize,
+    alloc: &impl Allocator,
+) {
+    if capacity == 0 || mem::size_of::<T>() == 0 {
+        return;
+    }
+
+    // SAFETY: this layout was already successfully created when the memory
+    //         was allocated.
+    let layout = Layout::array::<T>(capacity).unwrap_unchecked();
+    alloc.deallocate(data.cast(), layout);
+}
+
 /// # Safety
 ///
 /// This method is safe to use as long as you use the same allocator is used
@@ -90,16 +115,9 @@ unsafe fn push<T>(
         reserve(data, length, capacity, 1, alloc)?;
     }
 
-    let offset = match mem::size_of::<T>().checked_mul(*capacity) {
-        Some(x) => {
-            if x > isize::MAX as usize {
-                return Err(alloc::AllocError);
-            }
-            x as isize
-        }
-        None => return Err(alloc::AllocError),
-    };
-    *data.as_ptr().byte_offset(offset) = value;
+    // SAFETY: reserve made sure there's room for one more element.
+    ptr::write(data.as_ptr().add(*length), value);
+    *length += 1;
     Ok(())
 }
 
@@ -108,15 +126,15 @@ fn with_capacity<T>(
     capacity: usize,
     alloc: &impl Allocator,
 ) -> Result<(NonNull<T>, usize), alloc::AllocError> {
-    let layout = match Layout::from_size_align(
-        mem::size_of::<T>() * capacity,
-        mem::align_of::<T>(),
-    ) {
+    let layout = match Layout::array::<T>(capacity) {
         Ok(l) => l,
         Err(_) => return Err(alloc::AllocError),
     };
+    if layout.size() == 0 {
+        return Ok((NonNull::dangling(), capacity_from_size::<T>(0)));
+    }
     let mem = alloc.allocate(layout)?;
-    let capacity = mem.len();
+    let capacity = capacity_from_size::<T>(mem.len());
     Ok((mem.cast(), capacity))
 }
 
@@ -126,8 +144,7 @@ fn with_capacity<T>(
 #[inline(always)]
 const unsafe fn pop<T>(data: &mut NonNull<T>, length: &mut usize) -> T {
     *length -= 1;
-    let offset = *length * mem::size_of::<T>();
-    ptr::read(data.as_ptr().byte_add(offset))
+    ptr::read(data.as_ptr().add(*length))
 }
 
 #[inline(always)]
@@ -153,31 +170,22 @@ unsafe fn append<T>(
     capacity: &mut usize,
     alloc: &impl Allocator,
     other_data: NonNull<T>,
-    other_length: usize,
+    other_length: &mut usize,
 ) -> Result<(), alloc::AllocError> {
-    reserve(data, length, capacity, other_length, alloc)?;
-    for i in 0..other_length {
-        // SAFETY: we can use unchecked arithmetic here because these things
-        //         are already located at the computed offsets, meaning they
-        //         can't overflow here.
-        //
-        //         For the write, we know it can't overflow since we already
-        //         computed the size in reserve().
-        unsafe {
-            let value = ptr::read(
-                other_data
-                    .as_ptr()
-                    .byte_add(i.unchecked_mul(mem::size_of::<T>())),
-            );
-            ptr::write(
-                data.as_ptr().byte_add(
-                    (i.unchecked_add(*length))
-                        .unchecked_mul(mem::size_of::<T>()),
-                ),
-                value,
-            );
-        }
+    reserve(data, length, capacity, *other_length, alloc)?;
+    // SAFETY: reserve made sure there's room for every element in other, and
+    //         other can't overlap with data since we have unique access to
+    //         both.
+    unsafe {
+        ptr::copy_nonoverlapping(
+            other_data.as_ptr(),
+            data.as_ptr().add(*length),
+            *other_length,
+        );
     }
+    *length += *other_length;
+    // The elements have been moved out of other.
+    *other_length = 0;
     Ok(())
 }
 
@@ -194,9 +202,7 @@ const fn push_within_capacity<T>(
 
     // SAFETY: we already checked that the length is within bounds.
     unsafe {
-        let offset = mem::size_of::<T>().unchecked_mul(*length);
-        let dst = data.byte_add(offset);
-        ptr::write(dst.as_ptr(), value);
+        ptr::write(data.as_ptr().add(*length), value);
     }
 
     *length += 1;
@@ -228,16 +234,11 @@ unsafe fn insert_unchecked<T>(
     //            already assuming index < self.len().
     //         2. Since reserve allocated successfully, we know the offset
     //            won't overflow.
-    let offset = mem::size_of::<T>().unchecked_mul(index);
-    let base = data.byte_add(offset);
-    let count = mem::size_of::<T>().unchecked_mul(length.unchecked_sub(index));
-    ptr::copy(
-        base.as_ptr(),
-        base.byte_add(mem::size_of::<T>()).as_ptr(),
-        count,
-    );
-
-    ptr::write(base.as_ptr(), value);
+    let base = data.as_ptr().add(index);
+    ptr::copy(base, base.add(1), length.unchecked_sub(index));
+
+    ptr::write(base, value);
+    *length += 1;
     Ok(())
 }
 
@@ -253,13 +254,12 @@ const unsafe fn remove_unchecked<T>(
     // SAFETY: we know this is safe because the index is in bounds. We are
     //         just manually moving the object out.
     let result = {
-        let offset = mem::size_of::<T>().unchecked_mul(index);
-        let addr = data.byte_add(offset);
-        let result = ptr::read(addr.as_ptr());
+        let addr = data.as_ptr().add(index);
+        let result = ptr::read(addr);
         ptr::copy(
-            addr.byte_add(mem::size_of::<T>()).as_ptr(),
-            addr.as_ptr(),
-            length.unchecked_sub(index),
+            addr.add(1),
+            addr,
+            length.unchecked_sub(index).unchecked_sub(1),
         );
         result
     };
@@ -281,13 +281,10 @@ const unsafe fn swap_remove_unchecked<T>(
     // SAFETY: we know this is safe because the index is in bounds. We are
     //         just manually moving the object out.
     let result = unsafe {
-        let offset = mem::size_of::<T>().unchecked_mul(index);
-        let end_offset =
-            mem::size_of::<T>().unchecked_mul(length.unchecked_sub(1));
-        let addr = data.byte_add(offset);
-        let end_addr = data.byte_add(end_offset);
-        let result = ptr::read(addr.as_ptr());
-        ptr::write(addr.as_ptr(), ptr::read(end_addr.as_ptr()));
+        let addr = data.as_ptr().add(index);
+        let end_addr = data.as_ptr().add(length.unchecked_sub(1));
+        let result = ptr::read(addr);
+        ptr::copy(end_addr, addr, 1);
         result
     };
 
@@ -302,16 +299,15 @@ fn truncate<T>(data: &mut NonNull<T>, length: &mut usize, len: usize) {
         return;
     }
 
-    for i in (len - 1)..*length {
-        // SAFETY: we know this is safe because we've already checked that
-        //         the index is in bounds.
-        unsafe {
-            let offset = mem::size_of::<T>().unchecked_mul(i);
-            let addr = data.as_ptr().map_addr(|a| a.unchecked_add(offset));
-            ptr::drop_in_place(addr);
-        }
+    // SAFETY: we know this is safe because we've already checked that
+    //         the elements are in bounds. Like in clear(), the length is set
+    //         first so that a panicking drop doesn't cause a double drop.
+    unsafe {
+        let tail =
+            slice_from_raw_parts_mut(data.as_ptr().add(len), *length - len);
+        *length = len;
+        ptr::drop_in_place(tail);
     }
-    *length = len;
 }
 
 /// A dynamic array type whose elements are placed in contiguous memory.
@@ -411,6 +407,25 @@ impl<'a, T> Array<'a, T> {
         self.capacity
     }
 
+    /// Returns a pointer to the start of the [Array]'s buffer, which has room
+    /// for [Array::capacity] elements.
+    #[inline(always)]
+    pub const fn as_mut_ptr(&mut self) -> *mut T {
+        self.data.as_ptr()
+    }
+
+    /// Sets the length of the [Array] without dropping or initializing any
+    /// elements.
+    ///
+    /// # Safety
+    ///
+    /// This function is safe to use if `len <= self.capacity()` and every
+    /// element up to `len` is initialized.
+    #[inline(always)]
+    pub const unsafe fn set_len(&mut self, len: usize) {
+        self.length = len;
+    }
+
     /// Tries to reserve enough memory for at least `additional` extra elements
     /// to be appended to the end of the [Array]. That is, after calling
     /// this you can be sure that the next `additional` calls to
@@ -497,7 +512,7 @@ impl<'a, T> Array<'a, T> {
                 &mut self.capacity,
                 &self.alloc,
                 other.data,
-                other.length,
+                &mut other.length,
             )
         }
     }
@@ -569,15 +584,15 @@ impl<'a, T> Array<'a, T> {
     ///
     /// If the given `index` is out of bounds, returns [`Option::None`].
     pub const fn remove(&mut self, index: usize) -> Option<T> {
+        if index >= self.length {
+            return None;
+        }
+
         if index == self.length - 1 {
             // SAFETY: we already checked that there's something to pop
             return Some(unsafe { pop(&mut self.data, &mut self.length) });
         }
 
-        if index >= self.length {
-            return None;
-        }
-
         // SAFETY: this is safe because we already checked that the index is in
         //         bounds.
         Some(unsafe {
@@ -600,14 +615,14 @@ impl<'a, T> Array<'a, T> {
     ///
     /// If the given `index` is out of bounds, returns [`Option::None`].
     pub const fn swap_remove(&mut self, index: usize) -> Option<T> {
-        if index == self.length - 1 {
-            return self.pop();
-        }
-
         if index >= self.length {
             return None;
         }
 
+        if index == self.length - 1 {
+            return self.pop();
+        }
+
         // SAFETY: we know this is safe because we've checked that the index is
         //         in bounds.
         Some(unsafe {
@@ -633,6 +648,248 @@ impl<'a, T> Array<'a, T> {
     pub fn truncate(&mut self, len: usize) {
         truncate(&mut self.data, &mut self.length, len)
     }
+
+    /// Appends every element of `iter` to the end of the [Array].
+    ///
+    /// Returns an error if an allocation failed. The elements which were
+    /// appended before the failure are kept.
+    pub fn try_extend<I: IntoIterator<Item = T>>(
+        &mut self,
+        iter: I,
+    ) -> Result<(), alloc::AllocError> {
+        let iter = iter.into_iter();
+        let (lower, _) = iter.size_hint();
+        self.reserve(lower)?;
+        for value in iter {
+            self.push(value)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a, T: Clone> Array<'a, T> {
+    /// Returns a copy of the [Array] using the same allocator.
+    ///
+    /// Returns an error if an allocation failed.
+    pub fn try_clone(&self) -> Result<Array<'a, T>, alloc::AllocError> {
+        let (data, capacity) = with_capacity(self.length, &self.alloc)?;
+        let mut result: Array<'a, T> = Array {
+            data,
+            length: 0,
+            capacity,
+            alloc: self.alloc,
+        };
+        for value in self.iter() {
+            // SAFETY: we reserved enough capacity for every element.
+            unsafe {
+                ptr::write(
+                    result.data.as_ptr().add(result.length),
+                    value.clone(),
+                )
+            };
+            result.length += 1;
+        }
+        Ok(result)
+    }
+}
+
+impl<T> Drop for Array<'_, T> {
+    fn drop(&mut self) {
+        clear(&mut self.data, &mut self.length);
+        // SAFETY: we use the same allocator every time
+        unsafe { deallocate(self.data, self.capacity, &self.alloc) }
+    }
+}
+
+impl<T: Clone> Clone for Array<'_, T> {
+    /// Returns a copy of the [Array] using the same allocator.
+    ///
+    /// Calls [handle_alloc_error](::alloc::alloc::handle_alloc_error) if an
+    /// allocation fails. Use [Array::try_clone] to handle the error instead.
+    fn clone(&self) -> Self {
+        match self.try_clone() {
+            Ok(x) => x,
+            Err(_) => ::alloc::alloc::handle_alloc_error(
+                Layout::array::<T>(self.length).unwrap_or(Layout::new::<T>()),
+            ),
+        }
+    }
+}
+
+impl<T> Extend<T> for Array<'_, T> {
+    /// Appends every element of `iter` to the end of the [Array].
+    ///
+    /// Calls [handle_alloc_error](::alloc::alloc::handle_alloc_error) if an
+    /// allocation fails. Use [Array::try_extend] to handle the error instead.
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        if self.try_extend(iter).is_err() {
+            ::alloc::alloc::handle_alloc_error(Layout::new::<T>())
+        }
+    }
+}
+
+impl<'a, T: Copy + 'a> Extend<&'a T> for Array<'_, T> {
+    /// Appends a copy of every element of `iter` to the end of the [Array].
+    ///
+    /// Calls [handle_alloc_error](::alloc::alloc::handle_alloc_error) if an
+    /// allocation fails. Use [Array::try_extend] to handle the error instead.
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Array<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: PartialEq<U>, U> PartialEq<Array<'_, U>> for Array<'_, T> {
+    fn eq(&self, other: &Array<'_, U>) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: PartialEq<U>, U> PartialEq<[U]> for Array<'_, T> {
+    fn eq(&self, other: &[U]) -> bool {
+        **self == *other
+    }
+}
+
+impl<T: PartialEq<U>, U, const N: usize> PartialEq<[U; N]> for Array<'_, T> {
+    fn eq(&self, other: &[U; N]) -> bool {
+        **self == *other
+    }
+}
+
+impl<T: Eq> Eq for Array<'_, T> {}
+
+impl<T: hash::Hash> hash::Hash for Array<'_, T> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+impl<'a, T> IntoIterator for Array<'a, T> {
+    type Item = T;
+    type IntoIter = IntoIter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        // The iterator takes over dropping the elements and freeing the
+        // memory.
+        let array = ManuallyDrop::new(self);
+        IntoIter {
+            data: array.data,
+            capacity: array.capacity,
+            start: 0,
+            end: array.length,
+            alloc: array.alloc,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'b, T> IntoIterator for &'b Array<'_, T> {
+    type Item = &'b T;
+    type IntoIter = slice::Iter<'b, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'b, T> IntoIterator for &'b mut Array<'_, T> {
+    type Item = &'b mut T;
+    type IntoIter = slice::IterMut<'b, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// An iterator which moves the elements out of an [Array], returned by
+/// [Array::into_iter]. The [Array]'s memory is freed once the iterator is
+/// dropped.
+pub struct IntoIter<'a, T> {
+    data: NonNull<T>,
+    capacity: usize,
+    /// The elements which haven't been yielded yet are in `start..end`.
+    start: usize,
+    end: usize,
+    alloc: &'a dyn Allocator,
+    _marker: PhantomData<T>,
+}
+
+impl<T> IntoIter<'_, T> {
+    /// Returns the elements which haven't been yielded yet as a slice.
+    pub fn as_slice(&self) -> &[T] {
+        // SAFETY: the elements in start..end are still initialized.
+        unsafe {
+            slice::from_raw_parts(
+                self.data.as_ptr().add(self.start),
+                self.end - self.start,
+            )
+        }
+    }
+}
+
+impl<T> Iterator for IntoIter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.start == self.end {
+            return None;
+        }
+
+        // SAFETY: the element at start hasn't been moved out yet.
+        let value = unsafe { ptr::read(self.data.as_ptr().add(self.start)) };
+        self.start += 1;
+        Some(value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end - self.start;
+        (len, Some(len))
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<'_, T> {
+    fn next_back(&mut self) -> Option<T> {
+        if self.start == self.end {
+            return None;
+        }
+
+        self.end -= 1;
+        // SAFETY: the element at end hasn't been moved out yet.
+        Some(unsafe { ptr::read(self.data.as_ptr().add(self.end)) })
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<'_, T> {}
+
+impl<T> iter::FusedIterator for IntoIter<'_, T> {}
+
+impl<T: fmt::Debug> fmt::Debug for IntoIter<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("IntoIter").field(&self.as_slice()).finish()
+    }
+}
+
+impl<T> Drop for IntoIter<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: start is always within the allocation.
+        let remaining = slice_from_raw_parts_mut(
+            unsafe { self.data.as_ptr().add(self.start) },
+            self.end - self.start,
+        );
+        // SAFETY: same as clear(), we mark the elements as moved before
+        //         dropping them.
+        self.start = self.end;
+        unsafe {
+            ptr::drop_in_place(remaining);
+            deallocate(self.data, self.capacity, &self.alloc);
+        }
+    }
 }
 
 /// An [Array] without the allocator stored inline. Useful for embedding in
@@ -816,7 +1073,7 @@ impl<T> RawArray<T> {
             &mut self.capacity,
             &alloc,
             other.data,
-            other.length,
+            &mut other.length,
         )
     }
 
@@ -890,15 +1147,15 @@ impl<T> RawArray<T> {
     ///
     /// If the given `index` is out of bounds, returns [`Option::None`].
     pub const fn remove(&mut self, index: usize) -> Option<T> {
+        if index >= self.length {
+            return None;
+        }
+
         if index == self.length - 1 {
             // SAFETY: we already checked that there is something to pop.
             return Some(unsafe { pop(&mut self.data, &mut self.length) });
         }
 
-        if index >= self.length {
-            return None;
-        }
-
         // SAFETY: this is safe because we already checked that the index is in
         //         bounds.
         Some(unsafe {
@@ -921,14 +1178,14 @@ impl<T> RawArray<T> {
     ///
     /// If the given `index` is out of bounds, returns [`Option::None`].
     pub const fn swap_remove(&mut self, index: usize) -> Option<T> {
-        if index == self.length - 1 {
-            return self.pop();
-        }
-
         if index >= self.length {
             return None;
         }
 
+        if index == self.length - 1 {
+            return self.pop();
+        }
+
         // SAFETY: we know this is safe because we've checked that the index is
         //         in bounds.
         Some(unsafe {
@@ -954,4 +1211,139 @@ impl<T> RawArray<T> {
     pub fn truncate(&mut self, len: usize) {
         truncate(&mut self.data, &mut self.length, len)
     }
+
+    /// Drops all the elements in the [RawArray] and frees its memory, leaving
+    /// it empty. Since a [RawArray] doesn't know its allocator, this has to be
+    /// called before it's dropped, or its memory is leaked.
+    ///
+    /// # Safety
+    ///
+    /// This method is safe to use as long as you use the same allocator for
+    /// all methods on this object.
+    pub unsafe fn free(&mut self, alloc: impl Allocator) {
+        clear(&mut self.data, &mut self.length);
+        deallocate(self.data, self.capacity, &alloc);
+        *self = RawArray::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::alloc::{alloc::Global, format, rc::Rc, vec::Vec};
+
+    #[test]
+    fn array_push_pop_works() {
+        let mut array = Array::new(&Global);
+        for i in 0..100 {
+            assert!(array.push(i).is_ok());
+        }
+        assert_eq!(array.len(), 100);
+        assert!(array.capacity() >= 100);
+        assert_eq!(array[42], 42);
+        assert_eq!(array.pop(), Some(99));
+        assert_eq!(array.len(), 99);
+    }
+
+    #[test]
+    fn array_insert_remove_works() {
+        let mut array = Array::new(&Global);
+        assert!(array.try_extend([1, 2, 4, 5]).is_ok());
+        assert_eq!(array.insert(2, 3), Ok(Some(())));
+        assert_eq!(array.insert(6, 0), Ok(None));
+        assert_eq!(array, [1, 2, 3, 4, 5]);
+        assert_eq!(array.remove(0), Some(1));
+        assert_eq!(array.swap_remove(0), Some(2));
+        assert_eq!(array, [5, 3, 4]);
+        assert_eq!(array.remove(3), None);
+        array.truncate(1);
+        assert_eq!(array, [5]);
+    }
+
+    #[test]
+    fn array_remove_from_empty_is_none() {
+        let mut array = Array::<i32>::new(&Global);
+        assert_eq!(array.remove(0), None);
+        assert_eq!(array.swap_remove(0), None);
+    }
+
+    #[test]
+    fn array_append_moves_elements() {
+        let mut a = Array::new(&Global);
+        let mut b = Array::new(&Global);
+        assert!(a.try_extend([1, 2]).is_ok());
+        assert!(b.try_extend([3, 4]).is_ok());
+        assert!(a.append(&mut b).is_ok());
+        assert_eq!(a, [1, 2, 3, 4]);
+        assert_eq!(b.len(), 0);
+    }
+
+    #[test]
+    fn array_drop_drops_elements() {
+        let rc = Rc::new(());
+        {
+            let mut array = Array::new(&Global);
+            for _ in 0..10 {
+                assert!(array.push(rc.clone()).is_ok());
+            }
+            assert_eq!(Rc::strong_count(&rc), 11);
+            array.truncate(5);
+            assert_eq!(Rc::strong_count(&rc), 6);
+        }
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+
+    #[test]
+    fn array_into_iter_works() {
+        let mut array = Array::new(&Global);
+        assert!(array.try_extend(0..5).is_ok());
+        let mut iter = array.into_iter();
+        assert_eq!(iter.len(), 5);
+        assert_eq!(iter.next(), Some(0));
+        assert_eq!(iter.next_back(), Some(4));
+        assert_eq!(iter.collect::<Vec<_>>(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn array_into_iter_drops_remaining() {
+        let rc = Rc::new(());
+        let mut array = Array::new(&Global);
+        for _ in 0..4 {
+            assert!(array.push(rc.clone()).is_ok());
+        }
+        let mut iter = array.into_iter();
+        assert!(iter.next().is_some());
+        assert_eq!(Rc::strong_count(&rc), 4);
+        drop(iter);
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+
+    #[test]
+    fn array_borrowing_iterators_work() {
+        let mut array = Array::new(&Global);
+        assert!(array.try_extend([1, 2, 3]).is_ok());
+        for x in &mut array {
+            *x *= 2;
+        }
+        assert_eq!((&array).into_iter().sum::<i32>(), 12);
+    }
+
+    #[test]
+    fn array_try_clone_works() {
+        let mut array = Array::new(&Global);
+        assert!(array.try_extend([1, 2, 3]).is_ok());
+        let clone = array.try_clone().unwrap();
+        assert_eq!(array, clone);
+        assert_eq!(format!("{clone:?}"), "[1, 2, 3]");
+    }
+
+    #[test]
+    fn array_zero_sized_types_work() {
+        let mut array = Array::new(&Global);
+        for _ in 0..1000 {
+            assert!(array.push(()).is_ok());
+        }
+        assert_eq!(array.len(), 1000);
+        assert_eq!(array.into_iter().count(), 1000);
+    }
 }
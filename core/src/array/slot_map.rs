@@ -0,0 +1,474 @@
+use core::{
+    alloc::{self, Allocator},
+    fmt, iter, slice,
+};
+
+use super::RawArray;
+
+/// Marks the end of the free list in a [SlotMap].
+const NO_SLOT: u32 = u32::MAX;
+
+/// A key into a [SlotMap], returned by [SlotMap::insert].
+///
+/// Keys stay valid until the element they refer to is removed. After that,
+/// the slot they point to gets a new generation, so looking up a stale key
+/// returns [None] rather than whatever was inserted in its place.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Key {
+    index: u32,
+    generation: u32,
+}
+
+/// A slot which a [Key] can refer to.
+#[derive(Clone, Copy)]
+struct Slot {
+    /// Odd while the slot is occupied, and even while it's free, so a key,
+    /// whose generation is always odd, never matches a free slot.
+    generation: u32,
+    /// The index of the slot's element in the dense arrays while it's
+    /// occupied, or the next free slot while it's free.
+    next: u32,
+}
+
+/// A container whose elements are looked up with [Key]s, which unlike array
+/// indices can't accidentally refer to a different element once the one they
+/// referred to is removed.
+///
+/// Inserting, removing and looking up elements are all `O(1)`. The elements
+/// are kept next to each other in memory, so iterating over them is as fast as
+/// iterating over an [Array](super::Array), but removing an element moves the
+/// last one into its place, so the order isn't kept.
+///
+/// Each slot can be reused about two billion times. After that its generation
+/// would wrap around and old keys to it would start matching again, so the
+/// slot is retired instead, leaving 8 bytes which are never used until the
+/// [SlotMap] is dropped.
+pub struct SlotMap<'a, T> {
+    slots: RawArray<Slot>,
+    /// The elements, in no particular order.
+    values: RawArray<T>,
+    /// The index of the slot each element in `values` belongs to.
+    owners: RawArray<u32>,
+    /// The first free slot, or [NO_SLOT] if there isn't one.
+    free: u32,
+    alloc: &'a dyn Allocator,
+}
+
+impl<'a, T> SlotMap<'a, T> {
+    /// Returns a new empty [SlotMap] using the allocator `alloc`.
+    #[inline(always)]
+    pub const fn new(alloc: &'a impl Allocator) -> SlotMap<'a, T> {
+        SlotMap {
+            slots: RawArray::new(),
+            values: RawArray::new(),
+            owners: RawArray::new(),
+            free: NO_SLOT,
+            alloc,
+        }
+    }
+
+    /// Returns a new [SlotMap] with room for `capacity` elements.
+    pub fn with_capacity(
+        capacity: usize,
+        alloc: &'a impl Allocator,
+    ) -> Result<SlotMap<'a, T>, alloc::AllocError> {
+        let mut map = SlotMap::new(alloc);
+        map.reserve(capacity)?;
+        Ok(map)
+    }
+
+    /// Returns the number of elements in the [SlotMap].
+    #[inline(always)]
+    pub const fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns true if the [SlotMap] has no elements.
+    #[inline(always)]
+    pub const fn is_empty(&self) -> bool {
+        self.values.len() == 0
+    }
+
+    /// Tries to reserve enough memory for at least `additional` more elements
+    /// to be inserted without allocating.
+    pub fn reserve(
+        &mut self,
+        additional: usize,
+    ) -> Result<(), alloc::AllocError> {
+        // SAFETY: we use the same allocator every time
+        unsafe {
+            self.values.reserve(additional, self.alloc)?;
+            self.owners.reserve(additional, self.alloc)?;
+            // Free slots get reused before new ones are added, so this may
+            // reserve more slots than are actually needed.
+            self.slots.reserve(additional, self.alloc)
+        }
+    }
+
+    /// Inserts `value` into the [SlotMap], returning a [Key] which refers to
+    /// it.
+    ///
+    /// Returns an error if an allocation failed, or if the [SlotMap] already
+    /// has `u32::MAX - 1` slots.
+    pub fn insert(&mut self, value: T) -> Result<Key, alloc::AllocError> {
+        // Everything which could fail happens up front, so a failure leaves
+        // the map as it was.
+        self.reserve(1)?;
+        let dense = self.values.len() as u32;
+        let index = if self.free != NO_SLOT {
+            let index = self.free;
+            let slot = &mut self.slots[index as usize];
+            self.free = slot.next;
+            // Free slots are even, so this can't overflow.
+            slot.generation += 1;
+            slot.next = dense;
+            index
+        } else {
+            let index = self.slots.len();
+            if index >= NO_SLOT as usize {
+                return Err(alloc::AllocError);
+            }
+            let slot = Slot {
+                generation: 1,
+                next: dense,
+            };
+            // The reserve above made room for this.
+            let _ = self.slots.push_within_capacity(slot);
+            index as u32
+        };
+
+        let _ = self.owners.push_within_capacity(index);
+        let _ = self.values.push_within_capacity(value);
+        Ok(Key {
+            index,
+            generation: self.slots[index as usize].generation,
+        })
+    }
+
+    /// Returns the slot `key` refers to, if its element hasn't been removed.
+    #[inline(always)]
+    fn slot(&self, key: Key) -> Option<&Slot> {
+        self.slots
+            .get(key.index as usize)
+            .filter(|slot| slot.generation == key.generation)
+    }
+
+    /// Removes the element `key` refers to and returns it, or returns [None]
+    /// if it was already removed.
+    pub fn remove(&mut self, key: Key) -> Option<T> {
+        let dense = self.slot(key)?.next as usize;
+        let value = self.values.swap_remove(dense)?;
+        self.owners.swap_remove(dense);
+        if let Some(&moved) = self.owners.get(dense) {
+            // The last element took the removed one's place.
+            self.slots[moved as usize].next = dense as u32;
+        }
+
+        vacate(&mut self.slots, &mut self.free, key.index);
+        Some(value)
+    }
+
+    /// Returns true if the element `key` refers to hasn't been removed.
+    #[inline(always)]
+    pub fn contains_key(&self, key: Key) -> bool {
+        self.slot(key).is_some()
+    }
+
+    /// Returns a reference to the element `key` refers to, or [None] if it
+    /// was removed.
+    #[inline(always)]
+    pub fn get(&self, key: Key) -> Option<&T> {
+        let dense = self.slot(key)?.next as usize;
+        self.values.get(dense)
+    }
+
+    /// Returns a mutable reference to the element `key` refers to, or [None]
+    /// if it was removed.
+    #[inline(always)]
+    pub fn get_mut(&mut self, key: Key) -> Option<&mut T> {
+        let dense = self.slot(key)?.next as usize;
+        self.values.get_mut(dense)
+    }
+
+    /// Removes every element from the [SlotMap], without freeing any memory.
+    /// Keys to the removed elements stay invalid even once their slots are
+    /// reused.
+    pub fn clear(&mut self) {
+        for &index in self.owners.iter() {
+            vacate(&mut self.slots, &mut self.free, index);
+        }
+        self.owners.clear();
+        self.values.clear();
+    }
+
+    /// Returns the elements as a slice, in no particular order.
+    #[inline(always)]
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    /// Returns the elements as a mutable slice, in no particular order.
+    #[inline(always)]
+    pub fn values_mut(&mut self) -> &mut [T] {
+        &mut self.values
+    }
+
+    /// Returns an iterator over the keys and elements in the [SlotMap], in no
+    /// particular order.
+    #[inline(always)]
+    pub fn iter(&self) -> SlotMapIter<'_, T> {
+        SlotMapIter {
+            slots: &self.slots,
+            inner: self.owners.iter().zip(self.values.iter()),
+        }
+    }
+
+    /// Returns an iterator over the keys and mutable references to the
+    /// elements in the [SlotMap], in no particular order.
+    #[inline(always)]
+    pub fn iter_mut(&mut self) -> SlotMapIterMut<'_, T> {
+        SlotMapIterMut {
+            slots: &self.slots,
+            inner: self.owners.iter().zip(self.values.iter_mut()),
+        }
+    }
+
+    /// Returns an iterator over the keys in the [SlotMap], in no particular
+    /// order.
+    #[inline(always)]
+    pub fn keys(&self) -> impl Iterator<Item = Key> + '_ {
+        self.iter().map(|(key, _)| key)
+    }
+}
+
+impl<T> Drop for SlotMap<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: we use the same allocator every time
+        unsafe {
+            self.values.free(self.alloc);
+            self.owners.free(self.alloc);
+            self.slots.free(self.alloc);
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SlotMap<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Marks the occupied slot at `index` as free, and pushes it on the free list
+/// starting at `free` unless its generation has run out.
+#[inline(always)]
+fn vacate(slots: &mut [Slot], free: &mut u32, index: u32) {
+    let slot = &mut slots[index as usize];
+    match slot.generation.checked_add(1) {
+        Some(generation) => {
+            slot.generation = generation;
+            slot.next = *free;
+            *free = index;
+        }
+        // Wrapping back to the first generation would make old keys valid
+        // again, so the slot is retired. It's even, so no key matches it, and
+        // it isn't on the free list, so it's never reused.
+        None => slot.generation = 0,
+    }
+}
+
+/// Returns the [Key] for the slot at `index`, which must be occupied.
+#[inline(always)]
+fn key(slots: &[Slot], index: u32) -> Key {
+    Key {
+        index,
+        generation: slots[index as usize].generation,
+    }
+}
+
+/// An iterator over the keys and elements of a [SlotMap], returned by
+/// [SlotMap::iter].
+pub struct SlotMapIter<'b, T> {
+    slots: &'b [Slot],
+    inner: iter::Zip<slice::Iter<'b, u32>, slice::Iter<'b, T>>,
+}
+
+impl<'b, T> Iterator for SlotMapIter<'b, T> {
+    type Item = (Key, &'b T);
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&index, value) = self.inner.next()?;
+        Some((key(self.slots, index), value))
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for SlotMapIter<'_, T> {}
+
+/// An iterator over the keys and mutable references to the elements of a
+/// [SlotMap], returned by [SlotMap::iter_mut].
+pub struct SlotMapIterMut<'b, T> {
+    slots: &'b [Slot],
+    inner: iter::Zip<slice::Iter<'b, u32>, slice::IterMut<'b, T>>,
+}
+
+impl<'b, T> Iterator for SlotMapIterMut<'b, T> {
+    type Item = (Key, &'b mut T);
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&index, value) = self.inner.next()?;
+        Some((key(self.slots, index), value))
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for SlotMapIterMut<'_, T> {}
+
+impl<'b, T> IntoIterator for &'b SlotMap<'_, T> {
+    type Item = (Key, &'b T);
+    type IntoIter = SlotMapIter<'b, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'b, T> IntoIterator for &'b mut SlotMap<'_, T> {
+    type Item = (Key, &'b mut T);
+    type IntoIter = SlotMapIterMut<'b, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::alloc::{alloc::Global, rc::Rc, vec::Vec};
+
+    #[test]
+    fn slot_map_insert_get_remove() {
+        let mut map = SlotMap::new(&Global);
+        let a = map.insert(1).unwrap();
+        let b = map.insert(2).unwrap();
+        let c = map.insert(3).unwrap();
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.get(b), Some(&2));
+
+        assert_eq!(map.remove(a), Some(1));
+        assert_eq!(map.remove(a), None);
+        assert_eq!(map.get(a), None);
+        assert!(!map.contains_key(a));
+        // c was moved into a's place, but its key still works.
+        assert_eq!(map.get(c), Some(&3));
+        *map.get_mut(c).unwrap() = 30;
+        assert_eq!(map.values(), [30, 2]);
+    }
+
+    #[test]
+    fn slot_map_stale_keys_stay_invalid() {
+        let mut map = SlotMap::new(&Global);
+        let a = map.insert("a").unwrap();
+        map.remove(a);
+        // The slot is reused, but with a new generation.
+        let b = map.insert("b").unwrap();
+        assert_eq!(a.index, b.index);
+        assert_ne!(a, b);
+        assert_eq!(map.get(a), None);
+        assert_eq!(map.remove(a), None);
+        assert_eq!(map.get(b), Some(&"b"));
+
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.get(b), None);
+        let c = map.insert("c").unwrap();
+        assert_eq!(map.get(b), None);
+        assert_eq!(map.get(c), Some(&"c"));
+    }
+
+    #[test]
+    fn slot_map_retires_slots_whose_generation_runs_out() {
+        let mut map = SlotMap::new(&Global);
+        let a = map.insert("a").unwrap();
+        let b = map.insert("b").unwrap();
+        // Pretend both slots have been reused as often as they can be.
+        map.slots[a.index as usize].generation = u32::MAX;
+        map.slots[b.index as usize].generation = u32::MAX;
+        let a = Key {
+            index: a.index,
+            generation: u32::MAX,
+        };
+        let b = Key {
+            index: b.index,
+            generation: u32::MAX,
+        };
+        assert_eq!(map.get(a), Some(&"a"));
+
+        assert_eq!(map.remove(a), Some("a"));
+        assert_eq!(map.free, NO_SLOT);
+        let c = map.insert("c").unwrap();
+        assert_ne!(c.index, a.index);
+        assert_eq!(map.get(a), None);
+        // The generation a key would have had if it had wrapped around.
+        let wrapped = Key {
+            index: a.index,
+            generation: 1,
+        };
+        assert_eq!(map.get(wrapped), None);
+
+        map.clear();
+        assert_eq!(map.get(b), None);
+        // Only c's slot is left to reuse, so e needs a new one.
+        let d = map.insert("d").unwrap();
+        assert_eq!(d.index, c.index);
+        let e = map.insert("e").unwrap();
+        assert_eq!(e.index, 3);
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn slot_map_iterates_densely() {
+        let mut map = SlotMap::new(&Global);
+        let keys = (0..10).map(|i| map.insert(i).unwrap()).collect::<Vec<_>>();
+        for &key in keys.iter().step_by(2) {
+            map.remove(key);
+        }
+        for (_, value) in &mut map {
+            *value *= 10;
+        }
+
+        let mut seen = map.iter().collect::<Vec<_>>();
+        seen.sort_by_key(|(_, &v)| v);
+        assert_eq!(seen.len(), 5);
+        for (i, (key, &value)) in seen.into_iter().enumerate() {
+            assert_eq!(key, keys[i * 2 + 1]);
+            assert_eq!(value, (i as i32 * 2 + 1) * 10);
+        }
+        assert_eq!(map.keys().count(), 5);
+    }
+
+    #[test]
+    fn slot_map_drops_elements() {
+        let rc = Rc::new(());
+        {
+            let mut map = SlotMap::with_capacity(4, &Global).unwrap();
+            let a = map.insert(rc.clone()).unwrap();
+            map.insert(rc.clone()).unwrap();
+            map.insert(rc.clone()).unwrap();
+            assert_eq!(Rc::strong_count(&rc), 4);
+            drop(map.remove(a));
+            assert_eq!(Rc::strong_count(&rc), 3);
+        }
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+}
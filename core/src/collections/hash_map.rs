@@ -0,0 +1,858 @@
+use core::{
+    alloc::{self, Allocator},
+    borrow::Borrow,
+    fmt,
+    hash::{BuildHasher, Hash},
+    iter,
+    marker::PhantomData,
+    mem,
+};
+
+use super::{
+    raw::{Indices, RawTable},
+    BuildFastHasher,
+};
+
+/// A hash map which allocates its memory from the allocator it's given.
+///
+/// Lookups use a SwissTable, which looks at a whole group of buckets at once
+/// and stores part of each key's hash next to it, so only keys which are very
+/// likely to be equal are ever compared.
+///
+/// Keys are hashed with `S`, which by default is a [BuildFastHasher]. That's
+/// fast, but it isn't safe against someone picking keys which collide, so
+/// maps holding untrusted keys should be created with
+/// [HashMap::with_hasher] and a keyed hasher.
+pub struct HashMap<'a, K, V, S = BuildFastHasher> {
+    table: RawTable<(K, V)>,
+    hasher: S,
+    alloc: &'a dyn Allocator,
+}
+
+impl<'a, K, V> HashMap<'a, K, V> {
+    /// Returns a new empty [HashMap] using the allocator `alloc`.
+    #[inline(always)]
+    pub const fn new(alloc: &'a impl Allocator) -> HashMap<'a, K, V> {
+        HashMap::with_hasher(BuildFastHasher::new(), alloc)
+    }
+
+    /// Returns a new [HashMap] with room for at least `capacity` entries.
+    #[inline(always)]
+    pub fn with_capacity(
+        capacity: usize,
+        alloc: &'a impl Allocator,
+    ) -> Result<HashMap<'a, K, V>, alloc::AllocError> {
+        HashMap::with_capacity_and_hasher(
+            capacity,
+            BuildFastHasher::new(),
+            alloc,
+        )
+    }
+}
+
+impl<'a, K, V, S> HashMap<'a, K, V, S> {
+    /// Returns a new empty [HashMap] which hashes its keys with `hasher`,
+    /// using the allocator `alloc`.
+    #[inline(always)]
+    pub const fn with_hasher(
+        hasher: S,
+        alloc: &'a impl Allocator,
+    ) -> HashMap<'a, K, V, S> {
+        HashMap {
+            table: RawTable::new(),
+            hasher,
+            alloc,
+        }
+    }
+
+    /// Returns a new [HashMap] with room for at least `capacity` entries,
+    /// which hashes its keys with `hasher`.
+    pub fn with_capacity_and_hasher(
+        capacity: usize,
+        hasher: S,
+        alloc: &'a impl Allocator,
+    ) -> Result<HashMap<'a, K, V, S>, alloc::AllocError> {
+        let table = match capacity {
+            0 => RawTable::new(),
+            _ => RawTable::with_capacity(capacity, alloc)?,
+        };
+        Ok(HashMap {
+            table,
+            hasher,
+            alloc,
+        })
+    }
+
+    /// Returns the number of entries in the [HashMap].
+    #[inline(always)]
+    pub const fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Returns true if the [HashMap] has no entries.
+    #[inline(always)]
+    pub const fn is_empty(&self) -> bool {
+        self.table.len() == 0
+    }
+
+    /// Returns the number of entries the [HashMap] can hold without
+    /// allocating.
+    #[inline(always)]
+    pub const fn capacity(&self) -> usize {
+        self.table.capacity()
+    }
+
+    /// Returns the [BuildHasher] used to hash keys.
+    #[inline(always)]
+    pub const fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    /// Removes every entry, keeping the memory they used.
+    #[inline(always)]
+    pub fn clear(&mut self) {
+        self.table.clear();
+    }
+
+    /// Removes every entry for which `f` returns false.
+    pub fn retain(&mut self, mut f: impl FnMut(&K, &mut V) -> bool) {
+        for index in self.table.indices() {
+            // SAFETY: the index comes from the table, and the iterator isn't
+            //         affected by removing the entry it just returned.
+            unsafe {
+                let (key, value) = &mut *self.table.bucket(index);
+                if !f(key, value) {
+                    drop(self.table.remove(index));
+                }
+            }
+        }
+    }
+
+    /// Returns an iterator over the entries, in no particular order.
+    #[inline(always)]
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            indices: self.table.indices(),
+            table: &self.table,
+        }
+    }
+
+    /// Returns an iterator over the entries, in no particular order, which
+    /// allows changing their values.
+    #[inline(always)]
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            indices: self.table.indices(),
+            table: &self.table,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns an iterator over the keys, in no particular order.
+    #[inline(always)]
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { iter: self.iter() }
+    }
+
+    /// Returns an iterator over the values, in no particular order.
+    #[inline(always)]
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { iter: self.iter() }
+    }
+
+    /// Returns an iterator over the values, in no particular order, which
+    /// allows changing them.
+    #[inline(always)]
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut {
+            iter: self.iter_mut(),
+        }
+    }
+}
+
+impl<'a, K: Eq + Hash, V, S: BuildHasher> HashMap<'a, K, V, S> {
+    /// Returns the index of the bucket holding `key`.
+    #[inline(always)]
+    fn find<Q: Eq + Hash + ?Sized>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+    {
+        let hash = self.hasher.hash_one(key);
+        self.table.find(hash, |(k, _)| k.borrow() == key)
+    }
+
+    /// Tries to reserve enough memory for at least `additional` more entries
+    /// to be inserted without allocating.
+    pub fn reserve(
+        &mut self,
+        additional: usize,
+    ) -> Result<(), alloc::AllocError> {
+        let hasher = &self.hasher;
+        // SAFETY: we use the same allocator every time
+        unsafe {
+            self.table.reserve(
+                additional,
+                |(k, _)| hasher.hash_one(k),
+                self.alloc,
+            )
+        }
+    }
+
+    /// Inserts `value` under `key`, returning the value which was there
+    /// before, if there was one.
+    ///
+    /// If the key was already in the [HashMap], the key itself isn't
+    /// replaced. Returns an error if an allocation failed, in which case the
+    /// [HashMap] is left as it was.
+    pub fn insert(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> Result<Option<V>, alloc::AllocError> {
+        match self.entry(key) {
+            Entry::Occupied(mut entry) => Ok(Some(entry.insert(value))),
+            Entry::Vacant(entry) => entry.insert(value).map(|_| None),
+        }
+    }
+
+    /// Returns a reference to the value under `key`.
+    #[inline(always)]
+    pub fn get<Q: Eq + Hash + ?Sized>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+    {
+        self.get_key_value(key).map(|(_, v)| v)
+    }
+
+    /// Returns a reference to the key equal to `key` and its value.
+    #[inline(always)]
+    pub fn get_key_value<Q: Eq + Hash + ?Sized>(
+        &self,
+        key: &Q,
+    ) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+    {
+        let index = self.find(key)?;
+        // SAFETY: the index comes from the table.
+        let (k, v) = unsafe { &*self.table.bucket(index) };
+        Some((k, v))
+    }
+
+    /// Returns a mutable reference to the value under `key`.
+    #[inline(always)]
+    pub fn get_mut<Q: Eq + Hash + ?Sized>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+    {
+        let index = self.find(key)?;
+        // SAFETY: the index comes from the table.
+        Some(unsafe { &mut (*self.table.bucket(index)).1 })
+    }
+
+    /// Returns true if there's a value under `key`.
+    #[inline(always)]
+    pub fn contains_key<Q: Eq + Hash + ?Sized>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+    {
+        self.find(key).is_some()
+    }
+
+    /// Removes the value under `key` and returns it.
+    #[inline(always)]
+    pub fn remove<Q: Eq + Hash + ?Sized>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    /// Removes the value under `key`, returning it along with the key which
+    /// was stored.
+    pub fn remove_entry<Q: Eq + Hash + ?Sized>(
+        &mut self,
+        key: &Q,
+    ) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+    {
+        let index = self.find(key)?;
+        // SAFETY: the index comes from the table.
+        Some(unsafe { self.table.remove(index) })
+    }
+
+    /// Returns the [Entry] for `key`, which can be used to look at, insert
+    /// or remove its value with a single lookup.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S> {
+        let hash = self.hasher.hash_one(&key);
+        match self.table.find(hash, |(k, _)| *k == key) {
+            Some(index) => Entry::Occupied(OccupiedEntry {
+                table: &mut self.table,
+                index,
+            }),
+            None => Entry::Vacant(VacantEntry {
+                table: &mut self.table,
+                hasher: &self.hasher,
+                alloc: self.alloc,
+                hash,
+                key,
+            }),
+        }
+    }
+}
+
+impl<K, V, S> Drop for HashMap<'_, K, V, S> {
+    fn drop(&mut self) {
+        // SAFETY: we use the same allocator every time
+        unsafe { self.table.free(self.alloc) };
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, S> fmt::Debug for HashMap<'_, K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Eq + Hash, V: PartialEq, S: BuildHasher> PartialEq
+    for HashMap<'_, K, V, S>
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl<K: Eq + Hash, V: Eq, S: BuildHasher> Eq for HashMap<'_, K, V, S> {}
+
+impl<'b, K, V, S> IntoIterator for &'b HashMap<'_, K, V, S> {
+    type Item = (&'b K, &'b V);
+    type IntoIter = Iter<'b, K, V>;
+
+    #[inline(always)]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'b, K, V, S> IntoIterator for &'b mut HashMap<'_, K, V, S> {
+    type Item = (&'b K, &'b mut V);
+    type IntoIter = IterMut<'b, K, V>;
+
+    #[inline(always)]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// A view into a single key of a [HashMap], returned by [HashMap::entry].
+pub enum Entry<'b, K, V, S> {
+    Occupied(OccupiedEntry<'b, K, V>),
+    Vacant(VacantEntry<'b, K, V, S>),
+}
+
+impl<'b, K: Hash, V, S: BuildHasher> Entry<'b, K, V, S> {
+    /// Returns the key of the [Entry].
+    #[inline(always)]
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Returns the value, inserting `default` first if there isn't one.
+    #[inline(always)]
+    pub fn or_insert(
+        self,
+        default: V,
+    ) -> Result<&'b mut V, alloc::AllocError> {
+        self.or_insert_with(|| default)
+    }
+
+    /// Returns the value, inserting the result of `default` first if there
+    /// isn't one.
+    pub fn or_insert_with(
+        self,
+        default: impl FnOnce() -> V,
+    ) -> Result<&'b mut V, alloc::AllocError> {
+        match self {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Returns the value, inserting [V::default](Default::default) first if
+    /// there isn't one.
+    #[inline(always)]
+    pub fn or_default(self) -> Result<&'b mut V, alloc::AllocError>
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// Calls `f` with the value if there is one.
+    #[inline(always)]
+    pub fn and_modify(mut self, f: impl FnOnce(&mut V)) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+/// An [Entry] for a key which is in the [HashMap].
+pub struct OccupiedEntry<'b, K, V> {
+    table: &'b mut RawTable<(K, V)>,
+    /// Always a full bucket.
+    index: usize,
+}
+
+impl<'b, K, V> OccupiedEntry<'b, K, V> {
+    #[inline(always)]
+    fn pair(&self) -> &(K, V) {
+        // SAFETY: the bucket is full.
+        unsafe { &*self.table.bucket(self.index) }
+    }
+
+    #[inline(always)]
+    fn pair_mut(&mut self) -> &mut (K, V) {
+        // SAFETY: the bucket is full.
+        unsafe { &mut *self.table.bucket(self.index) }
+    }
+
+    /// Returns the key stored in the [HashMap].
+    #[inline(always)]
+    pub fn key(&self) -> &K {
+        &self.pair().0
+    }
+
+    /// Returns a reference to the value.
+    #[inline(always)]
+    pub fn get(&self) -> &V {
+        &self.pair().1
+    }
+
+    /// Returns a mutable reference to the value.
+    #[inline(always)]
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.pair_mut().1
+    }
+
+    /// Returns a mutable reference to the value which lives as long as the
+    /// borrow of the [HashMap].
+    #[inline(always)]
+    pub fn into_mut(self) -> &'b mut V {
+        // SAFETY: the bucket is full.
+        unsafe { &mut (*self.table.bucket(self.index)).1 }
+    }
+
+    /// Replaces the value, returning the old one.
+    #[inline(always)]
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    /// Removes the entry from the [HashMap] and returns its value.
+    #[inline(always)]
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Removes the entry from the [HashMap] and returns its key and value.
+    #[inline(always)]
+    pub fn remove_entry(self) -> (K, V) {
+        // SAFETY: the bucket is full.
+        unsafe { self.table.remove(self.index) }
+    }
+}
+
+/// An [Entry] for a key which isn't in the [HashMap].
+pub struct VacantEntry<'b, K, V, S> {
+    table: &'b mut RawTable<(K, V)>,
+    hasher: &'b S,
+    alloc: &'b dyn Allocator,
+    hash: u64,
+    key: K,
+}
+
+impl<'b, K: Hash, V, S: BuildHasher> VacantEntry<'b, K, V, S> {
+    /// Returns the key which would be inserted.
+    #[inline(always)]
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Takes back the key.
+    #[inline(always)]
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Inserts `value` under the key, returning a mutable reference to it.
+    ///
+    /// Returns an error if an allocation failed, in which case the [HashMap]
+    /// is left as it was.
+    pub fn insert(self, value: V) -> Result<&'b mut V, alloc::AllocError> {
+        let hasher = self.hasher;
+        // SAFETY: we use the same allocator every time, and the key was
+        //         just looked up and wasn't there.
+        unsafe {
+            let index = self.table.insert(
+                self.hash,
+                (self.key, value),
+                |(k, _)| hasher.hash_one(k),
+                self.alloc,
+            )?;
+            Ok(&mut (*self.table.bucket(index)).1)
+        }
+    }
+}
+
+/// An iterator over the entries of a [HashMap], returned by [HashMap::iter].
+pub struct Iter<'b, K, V> {
+    indices: Indices,
+    table: &'b RawTable<(K, V)>,
+}
+
+impl<'b, K, V> Iterator for Iter<'b, K, V> {
+    type Item = (&'b K, &'b V);
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.indices.next()?;
+        // SAFETY: the index comes from the table.
+        let (k, v) = unsafe { &*self.table.bucket(index) };
+        Some((k, v))
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+impl<K, V> iter::FusedIterator for Iter<'_, K, V> {}
+
+impl<K, V> Clone for Iter<'_, K, V> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        Iter {
+            indices: self.indices.clone(),
+            table: self.table,
+        }
+    }
+}
+
+/// An iterator over the entries of a [HashMap] which allows changing their
+/// values, returned by [HashMap::iter_mut].
+pub struct IterMut<'b, K, V> {
+    indices: Indices,
+    table: &'b RawTable<(K, V)>,
+    _marker: PhantomData<&'b mut V>,
+}
+
+impl<'b, K, V> Iterator for IterMut<'b, K, V> {
+    type Item = (&'b K, &'b mut V);
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.indices.next()?;
+        // SAFETY: the index comes from the table, which was borrowed
+        //         mutably, and each index is only returned once.
+        let (k, v) = unsafe { &mut *self.table.bucket(index) };
+        Some((k, v))
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IterMut<'_, K, V> {}
+
+impl<K, V> iter::FusedIterator for IterMut<'_, K, V> {}
+
+/// An iterator over the keys of a [HashMap], returned by [HashMap::keys].
+pub struct Keys<'b, K, V> {
+    iter: Iter<'b, K, V>,
+}
+
+impl<K, V> Clone for Keys<'_, K, V> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        Keys {
+            iter: self.iter.clone(),
+        }
+    }
+}
+
+impl<'b, K, V> Iterator for Keys<'b, K, V> {
+    type Item = &'b K;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<&'b K> {
+        self.iter.next().map(|(k, _)| k)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Keys<'_, K, V> {}
+
+impl<K, V> iter::FusedIterator for Keys<'_, K, V> {}
+
+/// An iterator over the values of a [HashMap], returned by
+/// [HashMap::values].
+pub struct Values<'b, K, V> {
+    iter: Iter<'b, K, V>,
+}
+
+impl<K, V> Clone for Values<'_, K, V> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        Values {
+            iter: self.iter.clone(),
+        }
+    }
+}
+
+impl<'b, K, V> Iterator for Values<'b, K, V> {
+    type Item = &'b V;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<&'b V> {
+        self.iter.next().map(|(_, v)| v)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Values<'_, K, V> {}
+
+impl<K, V> iter::FusedIterator for Values<'_, K, V> {}
+
+/// An iterator over the values of a [HashMap] which allows changing them,
+/// returned by [HashMap::values_mut].
+pub struct ValuesMut<'b, K, V> {
+    iter: IterMut<'b, K, V>,
+}
+
+impl<'b, K, V> Iterator for ValuesMut<'b, K, V> {
+    type Item = &'b mut V;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<&'b mut V> {
+        self.iter.next().map(|(_, v)| v)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for ValuesMut<'_, K, V> {}
+
+impl<K, V> iter::FusedIterator for ValuesMut<'_, K, V> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::alloc::{
+        alloc::Global, collections::BTreeMap, rc::Rc, string::String,
+        string::ToString, vec::Vec,
+    };
+    use core::{alloc::Layout, cell::Cell, ptr::NonNull};
+
+    /// Fails every allocation once `remaining` allocations have been made.
+    struct Limited {
+        remaining: Cell<usize>,
+    }
+
+    unsafe impl Allocator for Limited {
+        fn allocate(
+            &self,
+            layout: Layout,
+        ) -> Result<NonNull<[u8]>, alloc::AllocError> {
+            match self.remaining.get() {
+                0 => Err(alloc::AllocError),
+                n => {
+                    self.remaining.set(n - 1);
+                    Global.allocate(layout)
+                }
+            }
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            Global.deallocate(ptr, layout)
+        }
+    }
+
+    #[test]
+    fn hash_map_insert_get_remove() {
+        let mut map = HashMap::new(&Global);
+        assert_eq!(map.get("a"), None);
+        assert_eq!(map.insert("a".to_string(), 1).unwrap(), None);
+        assert_eq!(map.insert("b".to_string(), 2).unwrap(), None);
+        assert_eq!(map.insert("a".to_string(), 10).unwrap(), Some(1));
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get("a"), Some(&10));
+        assert_eq!(map.get_key_value("b"), Some((&"b".to_string(), &2)));
+
+        *map.get_mut("b").unwrap() += 1;
+        assert_eq!(map.remove("b"), Some(3));
+        assert_eq!(map.remove("b"), None);
+        assert!(!map.contains_key("b"));
+        assert_eq!(map.remove_entry("a"), Some(("a".to_string(), 10)));
+        assert!(map.is_empty());
+    }
+
+    /// Runs a long random mix of inserts and removes against a [BTreeMap],
+    /// over few enough keys that buckets keep being deleted and reused.
+    #[test]
+    fn hash_map_matches_btree_map() {
+        let mut map = HashMap::new(&Global);
+        let mut model = BTreeMap::new();
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        for i in 0..50_000u32 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let key = (state % 512) as u32;
+            if state & (1 << 40) == 0 {
+                assert_eq!(map.insert(key, i).unwrap(), model.insert(key, i));
+            } else {
+                assert_eq!(map.remove(&key), model.remove(&key));
+            }
+            assert_eq!(map.len(), model.len());
+        }
+
+        let mut entries =
+            map.iter().map(|(&k, &v)| (k, v)).collect::<Vec<_>>();
+        entries.sort();
+        assert_eq!(entries, model.into_iter().collect::<Vec<_>>());
+        // Deleted buckets were reused instead of making the table grow.
+        assert!(map.capacity() < 2048);
+    }
+
+    #[test]
+    fn hash_map_entry() {
+        let mut counts = HashMap::new(&Global);
+        for word in ["a", "b", "a", "c", "a"] {
+            *counts.entry(word).or_insert(0).unwrap() += 1;
+        }
+        assert_eq!(counts.get("a"), Some(&3));
+        assert_eq!(counts.get("c"), Some(&1));
+
+        counts
+            .entry("b")
+            .and_modify(|v| *v *= 10)
+            .or_default()
+            .unwrap();
+        counts
+            .entry("d")
+            .and_modify(|v| *v *= 10)
+            .or_default()
+            .unwrap();
+        assert_eq!(counts.get("b"), Some(&10));
+        assert_eq!(counts.get("d"), Some(&0));
+
+        match counts.entry("a") {
+            Entry::Occupied(entry) => {
+                assert_eq!(entry.key(), &"a");
+                assert_eq!(entry.remove(), 3);
+            }
+            Entry::Vacant(_) => unreachable!(),
+        }
+        match counts.entry("a") {
+            Entry::Occupied(_) => unreachable!(),
+            Entry::Vacant(entry) => assert_eq!(entry.into_key(), "a"),
+        }
+        assert_eq!(counts.len(), 3);
+    }
+
+    #[test]
+    fn hash_map_failed_insert_keeps_contents() {
+        let alloc = Limited {
+            remaining: Cell::new(1),
+        };
+        let mut map = HashMap::new(&alloc);
+        // The first allocation has room for 7 entries.
+        for i in 0..7 {
+            map.insert(i, i * 2).unwrap();
+        }
+        assert_eq!(map.insert(7, 14), Err(alloc::AllocError));
+        assert_eq!(map.len(), 7);
+        assert_eq!(map.entry(7).or_insert(14), Err(alloc::AllocError));
+        assert!(map.reserve(1).is_err());
+        for i in 0..7 {
+            assert_eq!(map.get(&i), Some(&(i * 2)));
+        }
+        // Replacing a value doesn't need any more memory.
+        assert_eq!(map.insert(3, 0), Ok(Some(6)));
+
+        let alloc = Limited {
+            remaining: Cell::new(0),
+        };
+        let mut map = HashMap::<u8, u8>::new(&alloc);
+        assert!(map.insert(0, 0).is_err());
+        assert!(HashMap::<u8, u8>::with_capacity(1, &alloc).is_err());
+    }
+
+    #[test]
+    fn hash_map_drops_entries() {
+        let value = Rc::new(());
+        {
+            let mut map = HashMap::new(&Global);
+            for i in 0..100 {
+                map.insert(i, value.clone()).unwrap();
+            }
+            assert_eq!(Rc::strong_count(&value), 101);
+            map.retain(|&k, _| k < 50);
+            assert_eq!(Rc::strong_count(&value), 51);
+            let capacity = map.capacity();
+            map.clear();
+            assert_eq!(Rc::strong_count(&value), 1);
+            assert!(map.capacity() >= capacity);
+
+            for i in 0..10 {
+                map.insert(i, value.clone()).unwrap();
+            }
+            for (_, v) in &mut map {
+                *v = Rc::new(());
+            }
+            assert_eq!(Rc::strong_count(&value), 1);
+            for v in map.values_mut() {
+                *v = value.clone();
+            }
+            assert_eq!(Rc::strong_count(&value), 11);
+        }
+        assert_eq!(Rc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn hash_map_with_capacity_doesnt_grow() {
+        let alloc = Limited {
+            remaining: Cell::new(1),
+        };
+        let mut map = HashMap::with_capacity(100, &alloc).unwrap();
+        assert!(map.capacity() >= 100);
+        for i in 0..100 {
+            map.insert(String::from("key") + &i.to_string(), i).unwrap();
+        }
+        assert_eq!(map.keys().len(), 100);
+        assert_eq!(map.values().sum::<i32>(), (0..100).sum());
+    }
+}
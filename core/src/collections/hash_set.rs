@@ -0,0 +1,288 @@
+use core::{
+    alloc::{self, Allocator},
+    borrow::Borrow,
+    fmt,
+    hash::{BuildHasher, Hash},
+    iter,
+};
+
+use super::{hash_map, BuildFastHasher, HashMap};
+
+/// A hash set which allocates its memory from the allocator it's given.
+///
+/// This is a [HashMap] with no values, so everything said there about
+/// lookups and hashing applies here too.
+pub struct HashSet<'a, T, S = BuildFastHasher> {
+    map: HashMap<'a, T, (), S>,
+}
+
+impl<'a, T> HashSet<'a, T> {
+    /// Returns a new empty [HashSet] using the allocator `alloc`.
+    #[inline(always)]
+    pub const fn new(alloc: &'a impl Allocator) -> HashSet<'a, T> {
+        HashSet {
+            map: HashMap::new(alloc),
+        }
+    }
+
+    /// Returns a new [HashSet] with room for at least `capacity` elements.
+    #[inline(always)]
+    pub fn with_capacity(
+        capacity: usize,
+        alloc: &'a impl Allocator,
+    ) -> Result<HashSet<'a, T>, alloc::AllocError> {
+        Ok(HashSet {
+            map: HashMap::with_capacity(capacity, alloc)?,
+        })
+    }
+}
+
+impl<'a, T, S> HashSet<'a, T, S> {
+    /// Returns a new empty [HashSet] which hashes its elements with `hasher`,
+    /// using the allocator `alloc`.
+    #[inline(always)]
+    pub const fn with_hasher(
+        hasher: S,
+        alloc: &'a impl Allocator,
+    ) -> HashSet<'a, T, S> {
+        HashSet {
+            map: HashMap::with_hasher(hasher, alloc),
+        }
+    }
+
+    /// Returns a new [HashSet] with room for at least `capacity` elements,
+    /// which hashes its elements with `hasher`.
+    #[inline(always)]
+    pub fn with_capacity_and_hasher(
+        capacity: usize,
+        hasher: S,
+        alloc: &'a impl Allocator,
+    ) -> Result<HashSet<'a, T, S>, alloc::AllocError> {
+        Ok(HashSet {
+            map: HashMap::with_capacity_and_hasher(capacity, hasher, alloc)?,
+        })
+    }
+
+    /// Returns the number of elements in the [HashSet].
+    #[inline(always)]
+    pub const fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns true if the [HashSet] has no elements.
+    #[inline(always)]
+    pub const fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns the number of elements the [HashSet] can hold without
+    /// allocating.
+    #[inline(always)]
+    pub const fn capacity(&self) -> usize {
+        self.map.capacity()
+    }
+
+    /// Returns the [BuildHasher] used to hash elements.
+    #[inline(always)]
+    pub const fn hasher(&self) -> &S {
+        self.map.hasher()
+    }
+
+    /// Removes every element, keeping the memory they used.
+    #[inline(always)]
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+
+    /// Removes every element for which `f` returns false.
+    #[inline(always)]
+    pub fn retain(&mut self, mut f: impl FnMut(&T) -> bool) {
+        self.map.retain(|k, _| f(k));
+    }
+
+    /// Returns an iterator over the elements, in no particular order.
+    #[inline(always)]
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            keys: self.map.keys(),
+        }
+    }
+}
+
+impl<'a, T: Eq + Hash, S: BuildHasher> HashSet<'a, T, S> {
+    /// Tries to reserve enough memory for at least `additional` more elements
+    /// to be inserted without allocating.
+    #[inline(always)]
+    pub fn reserve(
+        &mut self,
+        additional: usize,
+    ) -> Result<(), alloc::AllocError> {
+        self.map.reserve(additional)
+    }
+
+    /// Inserts `value`, returning true if it wasn't already in the
+    /// [HashSet].
+    ///
+    /// Returns an error if an allocation failed, in which case the [HashSet]
+    /// is left as it was.
+    #[inline(always)]
+    pub fn insert(&mut self, value: T) -> Result<bool, alloc::AllocError> {
+        match self.map.entry(value) {
+            hash_map::Entry::Occupied(_) => Ok(false),
+            hash_map::Entry::Vacant(entry) => entry.insert(()).map(|_| true),
+        }
+    }
+
+    /// Returns true if `value` is in the [HashSet].
+    #[inline(always)]
+    pub fn contains<Q: Eq + Hash + ?Sized>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.map.contains_key(value)
+    }
+
+    /// Returns a reference to the element equal to `value`.
+    #[inline(always)]
+    pub fn get<Q: Eq + Hash + ?Sized>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+    {
+        self.map.get_key_value(value).map(|(k, _)| k)
+    }
+
+    /// Removes `value`, returning true if it was in the [HashSet].
+    #[inline(always)]
+    pub fn remove<Q: Eq + Hash + ?Sized>(&mut self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.map.remove(value).is_some()
+    }
+
+    /// Removes the element equal to `value` and returns it.
+    #[inline(always)]
+    pub fn take<Q: Eq + Hash + ?Sized>(&mut self, value: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+    {
+        self.map.remove_entry(value).map(|(k, _)| k)
+    }
+
+    /// Returns true if every element of the [HashSet] is also in `other`.
+    pub fn is_subset(&self, other: &HashSet<'_, T, S>) -> bool {
+        self.len() <= other.len() && self.iter().all(|v| other.contains(v))
+    }
+
+    /// Returns true if the [HashSet] and `other` have no elements in common.
+    pub fn is_disjoint(&self, other: &HashSet<'_, T, S>) -> bool {
+        let (small, large) = match self.len() <= other.len() {
+            true => (self, other),
+            false => (other, self),
+        };
+        !small.iter().any(|v| large.contains(v))
+    }
+}
+
+impl<T: fmt::Debug, S> fmt::Debug for HashSet<'_, T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T: Eq + Hash, S: BuildHasher> PartialEq for HashSet<'_, T, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.is_subset(other)
+    }
+}
+
+impl<T: Eq + Hash, S: BuildHasher> Eq for HashSet<'_, T, S> {}
+
+impl<'b, T, S> IntoIterator for &'b HashSet<'_, T, S> {
+    type Item = &'b T;
+    type IntoIter = Iter<'b, T>;
+
+    #[inline(always)]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the elements of a [HashSet], returned by
+/// [HashSet::iter].
+pub struct Iter<'b, T> {
+    keys: hash_map::Keys<'b, T, ()>,
+}
+
+impl<'b, T> Iterator for Iter<'b, T> {
+    type Item = &'b T;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<&'b T> {
+        self.keys.next()
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.keys.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+impl<T> iter::FusedIterator for Iter<'_, T> {}
+
+impl<T> Clone for Iter<'_, T> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        Iter {
+            keys: self.keys.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::alloc::{alloc::Global, vec::Vec};
+
+    #[test]
+    fn hash_set_insert_contains_remove() {
+        let mut set = HashSet::new(&Global);
+        assert!(set.insert("a").unwrap());
+        assert!(set.insert("b").unwrap());
+        assert!(!set.insert("a").unwrap());
+        assert_eq!(set.len(), 2);
+        assert!(set.contains("a"));
+        assert_eq!(set.get("b"), Some(&"b"));
+
+        assert!(set.remove("a"));
+        assert!(!set.remove("a"));
+        assert_eq!(set.take("b"), Some("b"));
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn hash_set_compares_by_contents() {
+        let mut a = HashSet::new(&Global);
+        let mut b = HashSet::new(&Global);
+        for i in 0..100 {
+            a.insert(i).unwrap();
+        }
+        for i in (0..100).rev() {
+            b.insert(i).unwrap();
+        }
+        assert_eq!(a, b);
+
+        b.retain(|&i| i % 2 == 0);
+        assert!(b.is_subset(&a));
+        assert!(!a.is_subset(&b));
+        assert_ne!(a, b);
+
+        a.retain(|&i| i % 2 == 1);
+        assert!(a.is_disjoint(&b));
+        let mut odd = a.iter().copied().collect::<Vec<_>>();
+        odd.sort();
+        assert_eq!(odd, (0..100).filter(|i| i % 2 == 1).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,175 @@
+use core::hash::{BuildHasher, Hasher};
+
+/// An arbitrary odd number with its bits spread out, from PCG.
+const MULTIPLE: u64 = 0x5851_f42d_4c95_7f2d;
+/// The fractional part of pi, so the state doesn't start at zero.
+const SEED: u64 = 0x243f_6a88_85a3_08d3;
+
+/// Multiplies `x` and `y` into 128 bits and folds the halves together, which
+/// spreads every bit of the input across the whole output.
+#[inline(always)]
+const fn folded_multiply(x: u64, y: u64) -> u64 {
+    let full = (x as u128) * (y as u128);
+    (full as u64) ^ ((full >> 64) as u64)
+}
+
+/// A fast, non-cryptographic [Hasher], which is the default for
+/// [HashMap](super::HashMap) and [HashSet](super::HashSet).
+///
+/// It mixes in a word at a time with a single multiplication, which makes it
+/// much faster than SipHash for the small keys hash tables usually have, but
+/// since it isn't keyed with anything random, someone who controls the keys
+/// can pick ones which all collide. Tables holding untrusted keys should use
+/// a keyed hasher instead, like `std`'s `RandomState`.
+#[derive(Clone, Copy, Debug)]
+pub struct FastHasher {
+    state: u64,
+}
+
+impl FastHasher {
+    /// Returns a [FastHasher] starting from `seed`.
+    #[inline(always)]
+    pub const fn with_seed(seed: u64) -> FastHasher {
+        FastHasher { state: seed ^ SEED }
+    }
+
+    #[inline(always)]
+    fn mix(&mut self, word: u64) {
+        self.state = folded_multiply(self.state ^ word, MULTIPLE);
+    }
+}
+
+impl Default for FastHasher {
+    #[inline(always)]
+    fn default() -> FastHasher {
+        FastHasher::with_seed(0)
+    }
+}
+
+impl Hasher for FastHasher {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            // chunks_exact always gives slices of the size asked for.
+            self.mix(u64::from_le_bytes(chunk.try_into().unwrap()));
+        }
+
+        let rest = chunks.remainder();
+        if !rest.is_empty() {
+            let mut word = [0; 8];
+            word[..rest.len()].copy_from_slice(rest);
+            // The length goes in the top byte, which the rest never reaches,
+            // so trailing zeros still change the hash.
+            self.mix(u64::from_le_bytes(word) | (rest.len() as u64) << 56);
+        }
+    }
+
+    #[inline(always)]
+    fn write_u8(&mut self, i: u8) {
+        self.mix(i as u64);
+    }
+
+    #[inline(always)]
+    fn write_u16(&mut self, i: u16) {
+        self.mix(i as u64);
+    }
+
+    #[inline(always)]
+    fn write_u32(&mut self, i: u32) {
+        self.mix(i as u64);
+    }
+
+    #[inline(always)]
+    fn write_u64(&mut self, i: u64) {
+        self.mix(i);
+    }
+
+    #[inline(always)]
+    fn write_u128(&mut self, i: u128) {
+        self.mix(i as u64);
+        self.mix((i >> 64) as u64);
+    }
+
+    #[inline(always)]
+    fn write_usize(&mut self, i: usize) {
+        self.mix(i as u64);
+    }
+
+    #[inline(always)]
+    fn finish(&self) -> u64 {
+        folded_multiply(self.state, SEED)
+    }
+}
+
+/// The [BuildHasher] for [FastHasher], which is the default for
+/// [HashMap](super::HashMap) and [HashSet](super::HashSet).
+///
+/// Every hasher it builds starts from the same seed, so the same key always
+/// hashes the same way, even across runs of the program.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BuildFastHasher {
+    seed: u64,
+}
+
+impl BuildFastHasher {
+    /// Returns a [BuildFastHasher] which builds hashers with the default
+    /// seed.
+    #[inline(always)]
+    pub const fn new() -> BuildFastHasher {
+        BuildFastHasher { seed: 0 }
+    }
+
+    /// Returns a [BuildFastHasher] which builds hashers starting from
+    /// `seed`, so different tables can hash the same keys differently.
+    #[inline(always)]
+    pub const fn with_seed(seed: u64) -> BuildFastHasher {
+        BuildFastHasher { seed }
+    }
+}
+
+impl BuildHasher for BuildFastHasher {
+    type Hasher = FastHasher;
+
+    #[inline(always)]
+    fn build_hasher(&self) -> FastHasher {
+        FastHasher::with_seed(self.seed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::alloc::collections::BTreeSet;
+
+    #[test]
+    fn fast_hasher_spreads_small_integers() {
+        let hasher = BuildFastHasher::new();
+        // The table uses the low bits to pick a bucket and the top 7 bits to
+        // tell items apart, so both have to vary even for tiny keys.
+        let low = (0..1024u32)
+            .map(|i| hasher.hash_one(i) & 1023)
+            .collect::<BTreeSet<_>>();
+        let high = (0..1024u32)
+            .map(|i| hasher.hash_one(i) >> 57)
+            .collect::<BTreeSet<_>>();
+        assert!(low.len() > 512);
+        assert_eq!(high.len(), 128);
+    }
+
+    #[test]
+    fn fast_hasher_distinguishes_trailing_zeros() {
+        let hasher = BuildFastHasher::new();
+        let mut a = hasher.build_hasher();
+        a.write(&[1, 2, 3]);
+        let mut b = hasher.build_hasher();
+        b.write(&[1, 2, 3, 0]);
+        assert_ne!(a.finish(), b.finish());
+
+        assert_ne!(
+            BuildFastHasher::with_seed(1).hash_one("key"),
+            BuildFastHasher::with_seed(2).hash_one("key"),
+        );
+        assert_eq!(hasher.hash_one("key"), hasher.hash_one("key"));
+    }
+}
@@ -0,0 +1,12 @@
+#![allow(clippy::len_without_is_empty)]
+
+/// A hash map and the types used to work with its entries.
+pub mod hash_map;
+/// A hash set and the types used to work with it.
+pub mod hash_set;
+mod hasher;
+mod raw;
+
+pub use hash_map::HashMap;
+pub use hash_set::HashSet;
+pub use hasher::*;
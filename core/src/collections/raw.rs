@@ -0,0 +1,524 @@
+//! The table behind [HashMap](super::HashMap) and [HashSet](super::HashSet),
+//! which is a SwissTable.
+//!
+//! Every bucket has a control byte saying whether it's empty, deleted, or
+//! full, and if it's full, the top 7 bits of the hash of what's in it. Lookups
+//! compare a whole group of control bytes against those 7 bits at once, so
+//! only buckets which are very likely to match are ever looked at.
+//!
+//! The control bytes are followed by another group's worth which mirror the
+//! first group, so a group can be loaded starting at any bucket without
+//! wrapping around.
+
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    mem,
+    ptr::{self, NonNull},
+};
+
+/// The number of control bytes compared at once.
+const GROUP: usize = mem::size_of::<u64>();
+
+const EMPTY: u8 = 0xff;
+const DELETED: u8 = 0x80;
+
+const LO: u64 = 0x0101_0101_0101_0101;
+const HI: u64 = 0x8080_8080_8080_8080;
+
+/// The top 7 bits of a hash, which are stored in a full bucket's control
+/// byte.
+#[inline(always)]
+fn h2(hash: u64) -> u8 {
+    (hash >> (u64::BITS - 7)) as u8
+}
+
+/// A group of control bytes, loaded into an integer so they can all be
+/// compared at once.
+#[derive(Clone, Copy)]
+struct Group(u64);
+
+impl Group {
+    /// # Safety
+    ///
+    /// `ctrl` must point to at least [GROUP] readable bytes.
+    #[inline(always)]
+    unsafe fn load(ctrl: *const u8) -> Group {
+        Group(u64::from_le(ptr::read_unaligned(ctrl.cast::<u64>())))
+    }
+
+    /// Returns the buckets whose control byte might be `byte`. There can be
+    /// false positives, but only for full buckets.
+    #[inline(always)]
+    fn match_byte(self, byte: u8) -> BitMask {
+        let x = self.0 ^ (LO * byte as u64);
+        BitMask(x.wrapping_sub(LO) & !x & HI)
+    }
+
+    #[inline(always)]
+    fn match_empty(self) -> BitMask {
+        // Only EMPTY has both of its top two bits set.
+        BitMask(self.0 & (self.0 << 1) & HI)
+    }
+
+    #[inline(always)]
+    fn match_empty_or_deleted(self) -> BitMask {
+        BitMask(self.0 & HI)
+    }
+
+    #[inline(always)]
+    fn match_full(self) -> BitMask {
+        BitMask(!self.0 & HI)
+    }
+}
+
+/// A set of buckets in a [Group], with the top bit of each bucket's byte set
+/// if it's in the set.
+#[derive(Clone, Copy)]
+struct BitMask(u64);
+
+impl BitMask {
+    #[inline(always)]
+    fn lowest(self) -> Option<usize> {
+        if self.0 == 0 {
+            None
+        } else {
+            Some(self.0.trailing_zeros() as usize / GROUP)
+        }
+    }
+
+    /// The number of buckets at the start of the group before the first one
+    /// in the set.
+    #[inline(always)]
+    fn leading_buckets(self) -> usize {
+        self.0.trailing_zeros() as usize / GROUP
+    }
+
+    /// The number of buckets at the end of the group after the last one in
+    /// the set.
+    #[inline(always)]
+    fn trailing_buckets(self) -> usize {
+        self.0.leading_zeros() as usize / GROUP
+    }
+}
+
+impl Iterator for BitMask {
+    type Item = usize;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<usize> {
+        let bit = self.lowest()?;
+        self.0 &= self.0 - 1;
+        Some(bit)
+    }
+}
+
+/// Returns the number of items a table with `buckets` buckets can hold, which
+/// keeps at least an eighth of them empty so lookups stay short.
+#[inline(always)]
+const fn bucket_capacity(buckets: usize) -> usize {
+    buckets / 8 * 7
+}
+
+/// Returns the number of buckets needed to hold `capacity` items, or [None]
+/// if that's too many.
+#[inline(always)]
+fn capacity_to_buckets(capacity: usize) -> Option<usize> {
+    if capacity < GROUP {
+        // Tables are never smaller than a group, so every group loaded from
+        // them is made of distinct buckets.
+        return Some(GROUP);
+    }
+    (capacity.checked_mul(8)? / 7).checked_next_power_of_two()
+}
+
+/// Returns the layout of a table with `buckets` buckets, and the offset of
+/// its control bytes.
+#[inline(always)]
+fn table_layout<T>(buckets: usize) -> Option<(Layout, usize)> {
+    let data = Layout::array::<T>(buckets).ok()?;
+    let ctrl = Layout::array::<u8>(buckets + GROUP).ok()?;
+    data.extend(ctrl).ok()
+}
+
+/// A hash table which doesn't know how to hash its items, or which allocator
+/// its memory came from, so those have to be passed in when they're needed.
+pub(crate) struct RawTable<T> {
+    data: NonNull<T>,
+    ctrl: NonNull<u8>,
+    /// Always zero or a power of two, which is at least [GROUP].
+    buckets: usize,
+    items: usize,
+    /// The number of items which can be inserted into empty buckets before
+    /// the table has to grow. Buckets which were deleted can be reused
+    /// without counting against this.
+    growth_left: usize,
+}
+
+impl<T> RawTable<T> {
+    /// Returns a table which hasn't allocated any memory.
+    #[inline(always)]
+    pub(crate) const fn new() -> RawTable<T> {
+        RawTable {
+            data: NonNull::dangling(),
+            ctrl: NonNull::dangling(),
+            buckets: 0,
+            items: 0,
+            growth_left: 0,
+        }
+    }
+
+    /// Allocates a table with room for at least `capacity` items.
+    pub(crate) fn with_capacity(
+        capacity: usize,
+        alloc: &dyn Allocator,
+    ) -> Result<RawTable<T>, AllocError> {
+        let buckets = capacity_to_buckets(capacity).ok_or(AllocError)?;
+        let (layout, ctrl_offset) =
+            table_layout::<T>(buckets).ok_or(AllocError)?;
+        let ptr = alloc.allocate(layout)?.cast::<u8>();
+        // SAFETY: the control bytes are within the allocation.
+        unsafe {
+            let ctrl = ptr.add(ctrl_offset);
+            ctrl.write_bytes(EMPTY, buckets + GROUP);
+            Ok(RawTable {
+                data: ptr.cast(),
+                ctrl,
+                buckets,
+                items: 0,
+                growth_left: bucket_capacity(buckets),
+            })
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) const fn len(&self) -> usize {
+        self.items
+    }
+
+    /// Returns the number of items the table can hold without growing.
+    #[inline(always)]
+    pub(crate) const fn capacity(&self) -> usize {
+        self.items + self.growth_left
+    }
+
+    #[inline(always)]
+    fn mask(&self) -> usize {
+        self.buckets - 1
+    }
+
+    /// # Safety
+    ///
+    /// `index` must be less than the number of buckets.
+    #[inline(always)]
+    unsafe fn ctrl(&self, index: usize) -> *mut u8 {
+        self.ctrl.as_ptr().add(index)
+    }
+
+    /// Sets the control byte of the bucket at `index`, along with its mirror
+    /// if it's in the first group.
+    ///
+    /// # Safety
+    ///
+    /// `index` must be less than the number of buckets.
+    #[inline(always)]
+    unsafe fn set_ctrl(&mut self, index: usize, byte: u8) {
+        let mirror = (index.wrapping_sub(GROUP) & self.mask()) + GROUP;
+        *self.ctrl(index) = byte;
+        *self.ctrl(mirror) = byte;
+    }
+
+    /// Returns a pointer to the item in the bucket at `index`.
+    ///
+    /// # Safety
+    ///
+    /// `index` must be less than the number of buckets.
+    #[inline(always)]
+    pub(crate) unsafe fn bucket(&self, index: usize) -> *mut T {
+        self.data.as_ptr().add(index)
+    }
+
+    /// Calls `f` with the start of each group of buckets which could hold
+    /// something with the hash given, in order, until it returns a value.
+    ///
+    /// # Safety
+    ///
+    /// The table must have allocated its buckets.
+    #[inline(always)]
+    unsafe fn probe<R>(
+        &self,
+        hash: u64,
+        mut f: impl FnMut(usize, Group) -> Option<R>,
+    ) -> R {
+        let mask = self.mask();
+        let mut pos = hash as usize & mask;
+        let mut stride = 0;
+        loop {
+            if let Some(result) = f(pos, Group::load(self.ctrl(pos))) {
+                return result;
+            }
+            // Triangular probing visits every group once, since the number
+            // of buckets is a power of two.
+            stride += GROUP;
+            pos = (pos + stride) & mask;
+        }
+    }
+
+    /// Returns the index of the bucket holding an item with the hash given
+    /// for which `eq` returns true.
+    pub(crate) fn find(
+        &self,
+        hash: u64,
+        mut eq: impl FnMut(&T) -> bool,
+    ) -> Option<usize> {
+        if self.buckets == 0 {
+            return None;
+        }
+
+        let h2 = h2(hash);
+        // SAFETY: the table has buckets, and every index is masked.
+        unsafe {
+            self.probe(hash, |pos, group| {
+                for bit in group.match_byte(h2) {
+                    let index = (pos + bit) & self.mask();
+                    if eq(&*self.bucket(index)) {
+                        return Some(Some(index));
+                    }
+                }
+                // Items are never placed past an empty bucket, so it isn't
+                // in the table.
+                group.match_empty().lowest().map(|_| None)
+            })
+        }
+    }
+
+    /// Returns the index of the first empty or deleted bucket an item with
+    /// the hash given can be placed in.
+    ///
+    /// # Safety
+    ///
+    /// The table must have allocated its buckets.
+    #[inline(always)]
+    unsafe fn find_insert_slot(&self, hash: u64) -> usize {
+        // There's always at least one empty bucket, so this ends.
+        self.probe(hash, |pos, group| {
+            let bit = group.match_empty_or_deleted().lowest()?;
+            Some((pos + bit) & self.mask())
+        })
+    }
+
+    /// Makes sure at least `additional` more items can be inserted without
+    /// growing the table. `hasher` has to return the same hash for each item
+    /// as it had when it was inserted.
+    ///
+    /// # Safety
+    ///
+    /// The same allocator has to be used every time.
+    pub(crate) unsafe fn reserve(
+        &mut self,
+        additional: usize,
+        hasher: impl Fn(&T) -> u64,
+        alloc: &dyn Allocator,
+    ) -> Result<(), AllocError> {
+        if additional <= self.growth_left {
+            return Ok(());
+        }
+
+        let items = self.items.checked_add(additional).ok_or(AllocError)?;
+        let full_capacity = bucket_capacity(self.buckets);
+        // If deleted buckets are taking up most of the room, just getting rid
+        // of them makes enough.
+        let capacity = if items <= full_capacity / 2 {
+            full_capacity
+        } else {
+            items.max(full_capacity + 1)
+        };
+        self.resize(capacity, hasher, alloc)
+    }
+
+    /// Moves every item into a new table with room for `capacity` items.
+    unsafe fn resize(
+        &mut self,
+        capacity: usize,
+        hasher: impl Fn(&T) -> u64,
+        alloc: &dyn Allocator,
+    ) -> Result<(), AllocError> {
+        let mut table = RawTable::with_capacity(capacity, alloc)?;
+        for index in self.indices() {
+            let item = self.bucket(index);
+            let hash = hasher(&*item);
+            let slot = table.find_insert_slot(hash);
+            table.set_ctrl(slot, h2(hash));
+            ptr::copy_nonoverlapping(item, table.bucket(slot), 1);
+        }
+        table.items = self.items;
+        table.growth_left -= self.items;
+
+        // The items were moved, so only the memory is freed.
+        self.deallocate(alloc);
+        *self = table;
+        Ok(())
+    }
+
+    /// Inserts `item`, which has the hash given, and returns the index of its
+    /// bucket. The table must not already hold an equal item.
+    ///
+    /// # Safety
+    ///
+    /// The same allocator has to be used every time.
+    pub(crate) unsafe fn insert(
+        &mut self,
+        hash: u64,
+        item: T,
+        hasher: impl Fn(&T) -> u64,
+        alloc: &dyn Allocator,
+    ) -> Result<usize, AllocError> {
+        if self.buckets == 0 {
+            self.reserve(1, &hasher, alloc)?;
+        }
+        let mut index = self.find_insert_slot(hash);
+        // Deleted buckets can be reused, but taking an empty one needs room
+        // to grow.
+        if *self.ctrl(index) == EMPTY && self.growth_left == 0 {
+            self.reserve(1, &hasher, alloc)?;
+            index = self.find_insert_slot(hash);
+        }
+
+        if *self.ctrl(index) == EMPTY {
+            self.growth_left -= 1;
+        }
+        self.set_ctrl(index, h2(hash));
+        self.bucket(index).write(item);
+        self.items += 1;
+        Ok(index)
+    }
+
+    /// Removes the item in the bucket at `index` and returns it.
+    ///
+    /// # Safety
+    ///
+    /// The bucket at `index` must be full.
+    pub(crate) unsafe fn remove(&mut self, index: usize) -> T {
+        // If there's an empty bucket close enough on either side that no
+        // group could have seen this one full without also seeing the empty
+        // one, lookups never probe past it, so it can be marked empty.
+        // Otherwise, it has to be marked deleted so they keep going.
+        let before = index.wrapping_sub(GROUP) & self.mask();
+        let empty_before = Group::load(self.ctrl(before)).match_empty();
+        let empty_after = Group::load(self.ctrl(index)).match_empty();
+        let byte = if empty_before.trailing_buckets()
+            + empty_after.leading_buckets()
+            >= GROUP
+        {
+            DELETED
+        } else {
+            self.growth_left += 1;
+            EMPTY
+        };
+        self.set_ctrl(index, byte);
+        self.items -= 1;
+        self.bucket(index).read()
+    }
+
+    /// Removes every item, without freeing any memory.
+    pub(crate) fn clear(&mut self) {
+        if self.buckets == 0 {
+            return;
+        }
+
+        let indices = self.indices();
+        self.items = 0;
+        self.growth_left = bucket_capacity(self.buckets);
+        // SAFETY: the table has buckets, and the indices are read before the
+        //         control bytes are overwritten.
+        unsafe {
+            if mem::needs_drop::<T>() {
+                for index in indices {
+                    ptr::drop_in_place(self.bucket(index));
+                }
+            }
+            self.ctrl.write_bytes(EMPTY, self.buckets + GROUP);
+        }
+    }
+
+    /// Frees the table's memory without dropping its items.
+    unsafe fn deallocate(&mut self, alloc: &dyn Allocator) {
+        if self.buckets == 0 {
+            return;
+        }
+        let (layout, _) = table_layout::<T>(self.buckets).unwrap_unchecked();
+        alloc.deallocate(self.data.cast(), layout);
+    }
+
+    /// Drops every item and frees the table's memory, leaving it empty.
+    ///
+    /// # Safety
+    ///
+    /// The same allocator has to be used every time.
+    pub(crate) unsafe fn free(&mut self, alloc: &dyn Allocator) {
+        if mem::needs_drop::<T>() {
+            for index in self.indices() {
+                ptr::drop_in_place(self.bucket(index));
+            }
+        }
+        self.deallocate(alloc);
+        *self = RawTable::new();
+    }
+
+    /// Returns an iterator over the indices of the full buckets.
+    #[inline(always)]
+    pub(crate) fn indices(&self) -> Indices {
+        Indices {
+            ctrl: self.ctrl.as_ptr(),
+            buckets: self.buckets,
+            group: 0,
+            full: BitMask(0),
+            items: self.items,
+        }
+    }
+}
+
+/// An iterator over the indices of the full buckets in a [RawTable].
+///
+/// It reads the control bytes one group at a time, so removing the item at
+/// the index it just returned doesn't affect it.
+#[derive(Clone)]
+pub(crate) struct Indices {
+    ctrl: *const u8,
+    buckets: usize,
+    /// The start of the group after the one `full` came from.
+    group: usize,
+    full: BitMask,
+    items: usize,
+}
+
+impl Iterator for Indices {
+    type Item = usize;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<usize> {
+        if self.items == 0 {
+            return None;
+        }
+        loop {
+            if let Some(bit) = self.full.next() {
+                self.items -= 1;
+                return Some(self.group - GROUP + bit);
+            }
+            if self.group >= self.buckets {
+                return None;
+            }
+            // SAFETY: the number of buckets is a multiple of the group size,
+            //         so the whole group is in bounds.
+            self.full =
+                unsafe { Group::load(self.ctrl.add(self.group)) }.match_full();
+            self.group += GROUP;
+        }
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.items, Some(self.items))
+    }
+}
+
+impl ExactSizeIterator for Indices {}
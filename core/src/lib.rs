@@ -12,5 +12,7 @@ extern crate alloc;
 /// A dynamic array as well as building blocks for creating data structures
 /// containing them.
 pub mod array;
+/// Hash maps and sets which allocate from the allocator they're given.
+pub mod collections;
 /// A rust parser meant for procedural macros.
 pub mod rust;
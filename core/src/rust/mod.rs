@@ -3,7 +3,10 @@
 
 mod token_stream;
 
-pub use token_stream::{Delimiter, TokenStream, TokenTree};
+pub use token_stream::{
+    Delimiter, Group, Ident, LexError, Literal, Punct, Spacing, Span,
+    TokenStream, TokenTree,
+};
 
 use core::iter;
 
@@ -17,10 +20,14 @@ use alloc::{
 #[derive(Debug, Clone, PartialEq)]
 pub enum TypeDecl {
     Struct {
+        vis: Option<String>,
         name: String,
+        /// The generic parameters, including the angle brackets.
+        generics: Option<String>,
         fields: Vec<Field>,
     },
     Enum {
+        vis: Option<String>,
         name: String,
         variants: Vec<EnumVariant>,
     },
@@ -59,9 +66,14 @@ pub struct FunctionSig {
 pub fn parse_type_decls(tokens: TokenStream) -> Result<Vec<TypeDecl>, String> {
     let mut decls = Vec::new();
     let mut iter = tokens.into_iter().peekable();
+    // The visibility of the next declaration, if one was given.
+    let mut vis = None;
 
     while let Some(token) = iter.next() {
         match &token {
+            TokenTree::Ident(ident) if ident.to_string() == "pub" => {
+                vis = Some(parse_visibility(&mut iter));
+            }
             TokenTree::Ident(ident) if ident.to_string() == "struct" => {
                 // Parse struct name
                 let name = if let Some(TokenTree::Ident(name)) = iter.next() {
@@ -71,10 +83,11 @@ pub fn parse_type_decls(tokens: TokenStream) -> Result<Vec<TypeDecl>, String> {
                         "Expected struct name after 'struct'".to_string()
                     );
                 };
+                let generics = parse_generics(&mut iter);
                 // Parse struct fields
                 let mut fields = Vec::new();
                 let mut found_brace = false;
-                while let Some(token) = iter.next() {
+                for token in iter.by_ref() {
                     if let TokenTree::Group(group) = &token {
                         if group.delimiter() == Delimiter::Brace {
                             fields = parse_struct_fields(group.stream())?;
@@ -85,11 +98,15 @@ pub fn parse_type_decls(tokens: TokenStream) -> Result<Vec<TypeDecl>, String> {
                 }
                 if !found_brace {
                     return Err(format!(
-                        "Expected '{{' with fields for struct '{}'",
-                        name
+                        "Expected '{{' with fields for struct '{name}'"
                     ));
                 }
-                decls.push(TypeDecl::Struct { name, fields });
+                decls.push(TypeDecl::Struct {
+                    vis: vis.take(),
+                    name,
+                    generics,
+                    fields,
+                });
             }
             TokenTree::Ident(ident) if ident.to_string() == "enum" => {
                 // Parse enum name
@@ -101,7 +118,7 @@ pub fn parse_type_decls(tokens: TokenStream) -> Result<Vec<TypeDecl>, String> {
                 // Parse enum variants
                 let mut variants = Vec::new();
                 let mut found_brace = false;
-                while let Some(token) = iter.next() {
+                for token in iter.by_ref() {
                     if let TokenTree::Group(group) = &token {
                         if group.delimiter() == Delimiter::Brace {
                             variants = parse_enum_variants(group.stream())?;
@@ -112,11 +129,14 @@ pub fn parse_type_decls(tokens: TokenStream) -> Result<Vec<TypeDecl>, String> {
                 }
                 if !found_brace {
                     return Err(format!(
-                        "Expected '{{' with variants for enum '{}'",
-                        name
+                        "Expected '{{' with variants for enum '{name}'"
                     ));
                 }
-                decls.push(TypeDecl::Enum { name, variants });
+                decls.push(TypeDecl::Enum {
+                    vis: vis.take(),
+                    name,
+                    variants,
+                });
             }
             TokenTree::Ident(ident) if ident.to_string() == "type" => {
                 if let Some(TokenTree::Ident(name)) = iter.next() {
@@ -131,16 +151,19 @@ pub fn parse_type_decls(tokens: TokenStream) -> Result<Vec<TypeDecl>, String> {
             }
             TokenTree::Ident(ident)
                 if ident.to_string() == "fn"
-                    || (ident.to_string() == "pub")
                     || (ident.to_string() == "async")
                     || (ident.to_string() == "const") =>
             {
                 // Parse function signature
-                let sig = parse_function_sig(&token, &mut iter)?;
+                let mut sig = parse_function_sig(&token, &mut iter)?;
+                if let Some(vis) = vis.take() {
+                    sig.vis = Some(vis);
+                }
                 decls.push(TypeDecl::Function { sig });
             }
             _ => {
                 // Skip other tokens for now
+                vis = None;
             }
         }
     }
@@ -151,46 +174,131 @@ fn parse_struct_fields(tokens: TokenStream) -> Result<Vec<Field>, String> {
     let mut fields = Vec::new();
     let mut iter = tokens.into_iter().peekable();
     while let Some(token) = iter.next() {
-        if let TokenTree::Ident(name) = &token {
-            // Expect :
-            if let Some(TokenTree::Punct(p)) = iter.next() {
-                if p.as_char() == ':' {
-                    // Collect type tokens until , or end
-                    let mut ty = String::new();
-                    while let Some(t) = iter.peek() {
-                        match t {
-                            TokenTree::Punct(p) if p.as_char() == ',' => {
-                                iter.next();
-                                break;
-                            }
-                            _ => {
-                                ty.push_str(&token_to_string(
-                                    iter.next().unwrap(),
-                                ));
-                            }
-                        }
+        match &token {
+            // Skip attributes on fields.
+            TokenTree::Punct(p) if p.as_char() == '#' => {
+                iter.next();
+            }
+            TokenTree::Ident(ident) if ident.to_string() == "pub" => {
+                parse_visibility(&mut iter);
+            }
+            TokenTree::Ident(name) => {
+                // Expect :
+                match iter.next() {
+                    Some(TokenTree::Punct(p)) if p.as_char() == ':' => {
+                        fields.push(Field {
+                            name: name.to_string(),
+                            ty: parse_type(&mut iter),
+                        });
+                    }
+                    _ => {
+                        return Err(format!(
+                            "Expected ':' after field name '{name}'"
+                        ))
                     }
-                    fields.push(Field {
-                        name: name.to_string(),
-                        ty: ty.trim().to_string(),
-                    });
-                } else {
-                    return Err(format!(
-                        "Expected ':' after field name '{}'",
-                        name
-                    ));
                 }
-            } else {
-                return Err(format!(
-                    "Expected ':' after field name '{}'",
-                    name
-                ));
             }
+            _ => {}
         }
     }
     Ok(fields)
 }
 
+/// Parses the rest of a visibility after `pub`, such as the `(crate)` in
+/// `pub(crate)`, returning the whole visibility as a string.
+fn parse_visibility(
+    iter: &mut iter::Peekable<impl Iterator<Item = TokenTree>>,
+) -> String {
+    match iter.peek() {
+        Some(TokenTree::Group(group))
+            if group.delimiter() == Delimiter::Parenthesis =>
+        {
+            format!("pub{}", token_to_string(iter.next().unwrap()))
+        }
+        _ => "pub".to_string(),
+    }
+}
+
+/// Collects the generic parameters after a type's name, such as
+/// `<'a, T: Copy>`, if there are any.
+fn parse_generics(
+    iter: &mut iter::Peekable<impl Iterator<Item = TokenTree>>,
+) -> Option<String> {
+    if !matches!(iter.peek(), Some(TokenTree::Punct(p)) if p.as_char() == '<')
+    {
+        return None;
+    }
+
+    let mut generics = String::new();
+    let mut depth = 0usize;
+    // See parse_type.
+    let mut after_minus = false;
+    let mut last_was_word = false;
+    for token in iter.by_ref() {
+        match &token {
+            TokenTree::Punct(p) if p.as_char() == '<' => depth += 1,
+            TokenTree::Punct(p) if p.as_char() == '>' && !after_minus => {
+                depth -= 1
+            }
+            _ => {}
+        }
+
+        after_minus =
+            matches!(&token, TokenTree::Punct(p) if p.as_char() == '-');
+        let is_word =
+            matches!(&token, TokenTree::Ident(_) | TokenTree::Literal(_));
+        if is_word && last_was_word {
+            generics.push(' ');
+        }
+        last_was_word = is_word;
+        generics.push_str(&token_to_string(token));
+        if depth == 0 {
+            break;
+        }
+    }
+    Some(generics)
+}
+
+/// Collects the tokens of a type up until the next `,` which isn't nested in
+/// angle brackets, consuming the `,`.
+fn parse_type(
+    iter: &mut iter::Peekable<impl Iterator<Item = TokenTree>>,
+) -> String {
+    let mut ty = String::new();
+    let mut depth = 0usize;
+    // Whether the last token was a `-`, so that the `>` in `->` isn't
+    // mistaken for a closing angle bracket.
+    let mut after_minus = false;
+    let mut last_was_word = false;
+    while let Some(t) = iter.peek() {
+        match t {
+            TokenTree::Punct(p) if p.as_char() == ',' && depth == 0 => {
+                iter.next();
+                break;
+            }
+            TokenTree::Punct(p) if p.as_char() == '<' => depth += 1,
+            TokenTree::Punct(p) if p.as_char() == '>' && !after_minus => {
+                depth = depth.saturating_sub(1)
+            }
+            _ => {}
+        }
+
+        let token = iter.next().unwrap();
+        after_minus =
+            matches!(&token, TokenTree::Punct(p) if p.as_char() == '-');
+        // Words need to be separated so that `&'a mut T` doesn't turn into
+        // `&'amutT`.
+        let is_word =
+            matches!(&token, TokenTree::Ident(_) | TokenTree::Literal(_));
+        if is_word && last_was_word {
+            ty.push(' ');
+        }
+        last_was_word = is_word;
+        ty.push_str(&token_to_string(token));
+    }
+    ty.trim().to_string()
+}
+
 fn parse_enum_variants(
     tokens: TokenStream,
 ) -> Result<Vec<EnumVariant>, String> {
@@ -262,8 +370,8 @@ fn parse_function_sig(
         }
     }
 
-    let mut tokens_iter = tokens.into_iter().peekable();
-    while let Some(token) = tokens_iter.next() {
+    let tokens_iter = tokens.into_iter().peekable();
+    for token in tokens_iter {
         match &token {
             TokenTree::Ident(ident) if ident.to_string() == "pub" => {
                 vis = Some("pub".to_string())
@@ -364,38 +472,18 @@ fn parse_fn_args(tokens: TokenStream) -> Result<Vec<Field>, String> {
     while let Some(token) = iter.next() {
         if let TokenTree::Ident(name) = &token {
             // Expect :
-            if let Some(TokenTree::Punct(p)) = iter.next() {
-                if p.as_char() == ':' {
-                    // Collect type tokens until , or end
-                    let mut ty = String::new();
-                    while let Some(t) = iter.peek() {
-                        match t {
-                            TokenTree::Punct(p) if p.as_char() == ',' => {
-                                iter.next();
-                                break;
-                            }
-                            _ => {
-                                ty.push_str(&token_to_string(
-                                    iter.next().unwrap(),
-                                ));
-                            }
-                        }
-                    }
+            match iter.next() {
+                Some(TokenTree::Punct(p)) if p.as_char() == ':' => {
                     args.push(Field {
                         name: name.to_string(),
-                        ty: ty.trim().to_string(),
+                        ty: parse_type(&mut iter),
                     });
-                } else {
+                }
+                _ => {
                     return Err(format!(
-                        "Expected ':' after argument name '{}'",
-                        name
-                    ));
+                        "Expected ':' after argument name '{name}'"
+                    ))
                 }
-            } else {
-                return Err(format!(
-                    "Expected ':' after argument name '{}'",
-                    name
-                ));
             }
         }
     }
@@ -407,13 +495,13 @@ fn token_to_string(token: TokenTree) -> String {
         TokenTree::Ident(ident) => ident.to_string(),
         TokenTree::Punct(p) => p.to_string(),
         TokenTree::Literal(lit) => lit.to_string(),
-        TokenTree::Group(_) => String::from("<group>"),
+        TokenTree::Group(group) => group.to_string(),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::token_stream::{Delimiter, Group, TokenStream, TokenTree};
+    use super::token_stream::TokenStream;
     use super::*;
 
     #[test]
@@ -423,7 +511,9 @@ mod tests {
         assert_eq!(
             decls,
             vec![TypeDecl::Struct {
+                vis: None,
                 name: "Foo".into(),
+                generics: None,
                 fields: vec![Field {
                     name: "x".into(),
                     ty: "i32".into()
@@ -434,12 +524,17 @@ mod tests {
 
     #[test]
     fn parses_generic_struct() {
-        let tokens = "struct Foo<T> { x: T }".parse::<TokenStream>().unwrap();
+        let tokens =
+            "struct Foo<'a, T: Fn() -> Vec<u8>> where T: Copy { x: T }"
+                .parse::<TokenStream>()
+                .unwrap();
         let decls = parse_type_decls(tokens).expect("Should parse");
         assert_eq!(
             decls,
             vec![TypeDecl::Struct {
+                vis: None,
                 name: "Foo".into(),
+                generics: Some("<'a,T:Fn()->Vec<u8>>".into()),
                 fields: vec![Field {
                     name: "x".into(),
                     ty: "T".into()
@@ -447,4 +542,66 @@ mod tests {
             }]
         );
     }
+
+    #[test]
+    fn parses_visibility_and_field_types() {
+        let tokens = "
+            /// A doc comment.
+            pub(crate) struct Foo {
+                #[doc = \"x\"]
+                pub x: HashMap<K, Vec<V>>,
+                pub(super) y: &'a mut [u8; 4],
+                z: fn(u8) -> u8,
+            }
+        "
+        .parse::<TokenStream>()
+        .unwrap();
+        let decls = parse_type_decls(tokens).expect("Should parse");
+        assert_eq!(
+            decls,
+            vec![TypeDecl::Struct {
+                vis: Some("pub(crate)".into()),
+                name: "Foo".into(),
+                generics: None,
+                fields: vec![
+                    Field {
+                        name: "x".into(),
+                        ty: "HashMap<K,Vec<V>>".into()
+                    },
+                    Field {
+                        name: "y".into(),
+                        ty: "&'a mut[u8 ; 4]".into()
+                    },
+                    Field {
+                        name: "z".into(),
+                        ty: "fn(u8)->u8".into()
+                    }
+                ]
+            }]
+        );
+    }
+
+    #[test]
+    fn parses_pub_function() {
+        let tokens = "pub(crate) async fn foo(a: Result<u8, E>) -> u8 {}"
+            .parse::<TokenStream>()
+            .unwrap();
+        let decls = parse_type_decls(tokens).expect("Should parse");
+        assert_eq!(
+            decls,
+            vec![TypeDecl::Function {
+                sig: FunctionSig {
+                    vis: Some("pub(crate)".into()),
+                    is_async: true,
+                    is_const: false,
+                    name: "foo".into(),
+                    args: vec![Field {
+                        name: "a".into(),
+                        ty: "Result<u8,E>".into()
+                    }],
+                    ret: Some("u8".into())
+                }
+            }]
+        );
+    }
 }
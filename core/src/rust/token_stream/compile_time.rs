@@ -15,7 +15,7 @@ use proc_macro::{
 
 #[derive(Debug, Clone)]
 #[repr(transparent)]
-pub(crate) struct TokenStream(pub(crate) PMTokenStream);
+pub struct TokenStream(pub(crate) PMTokenStream);
 
 impl TokenStream {
     pub(crate) fn new() -> Self {
@@ -39,7 +39,7 @@ impl fmt::Display for TokenStream {
 }
 
 #[repr(transparent)]
-pub(crate) struct IntoIter(pub(crate) PMIntoIter);
+pub struct IntoIter(pub(crate) PMIntoIter);
 
 impl Iterator for IntoIter {
     type Item = TokenTree;
@@ -53,9 +53,9 @@ impl Iterator for IntoIter {
 #[repr(transparent)]
 pub(crate) struct Delimiter(pub(crate) PMDelimiter);
 
-impl Into<super::Delimiter> for Delimiter {
-    fn into(self) -> super::Delimiter {
-        match self.0 {
+impl From<Delimiter> for super::Delimiter {
+    fn from(val: Delimiter) -> Self {
+        match val.0 {
             PMDelimiter::Parenthesis => super::Delimiter::Parenthesis,
             PMDelimiter::Brace => super::Delimiter::Brace,
             PMDelimiter::Bracket => super::Delimiter::Bracket,
@@ -66,17 +66,11 @@ impl Into<super::Delimiter> for Delimiter {
 
 #[derive(Debug, Clone, Copy)]
 #[repr(transparent)]
-pub(crate) struct Span(pub(crate) PMSpan);
+pub struct Span(pub(crate) PMSpan);
 
 #[derive(Debug, Clone)]
 #[repr(transparent)]
-pub(crate) struct TokenTree(pub(crate) PMTokenTree);
-
-impl TokenTree {
-    pub(crate) fn span(&self) -> Span {
-        Span(self.0.span())
-    }
-}
+pub struct TokenTree(pub(crate) PMTokenTree);
 
 impl fmt::Display for TokenTree {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -86,7 +80,7 @@ impl fmt::Display for TokenTree {
 
 #[derive(Debug, Clone)]
 #[repr(transparent)]
-pub(crate) struct Literal(pub(crate) PMLiteral);
+pub struct Literal(pub(crate) PMLiteral);
 
 impl fmt::Display for Literal {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -102,7 +96,7 @@ impl Literal {
 
 #[derive(Debug, Clone)]
 #[repr(transparent)]
-pub(crate) struct Ident(pub(crate) PMIdent);
+pub struct Ident(pub(crate) PMIdent);
 
 impl fmt::Display for Ident {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -118,7 +112,7 @@ impl Ident {
 
 #[derive(Debug, Clone)]
 #[repr(transparent)]
-pub(crate) struct Punct(pub(crate) PMPunct);
+pub struct Punct(pub(crate) PMPunct);
 
 impl PartialEq<char> for Punct {
     fn eq(&self, other: &char) -> bool {
@@ -151,13 +145,9 @@ impl Punct {
 
 #[derive(Debug, Clone)]
 #[repr(transparent)]
-pub(crate) struct Group(pub(crate) PMGroup);
+pub struct Group(pub(crate) PMGroup);
 
 impl Group {
-    pub(crate) fn new(delimiter: Delimiter, stream: TokenStream) -> Self {
-        Group(PMGroup::new(delimiter.0, stream.0))
-    }
-
     pub(crate) fn delimiter(&self) -> Delimiter {
         Delimiter(self.0.delimiter())
     }
@@ -1,4 +1,5 @@
-use core::{fmt, mem::transmute};
+use alloc::string::{String, ToString};
+use core::{fmt, ops::Range, str::FromStr};
 
 extern crate proc_macro;
 
@@ -11,6 +12,53 @@ pub enum Span {
     Runtime(runtime::Span),
 }
 
+impl Span {
+    /// Returns the range of bytes in the source text this span covers, if
+    /// the tokens were parsed at runtime.
+    pub fn byte_range(&self) -> Option<Range<usize>> {
+        match self {
+            Span::CompileTime(_) => None,
+            Span::Runtime(span) => Some(span.start()..span.end()),
+        }
+    }
+}
+
+/// The error returned when a string couldn't be turned into a [TokenStream].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexError {
+    message: String,
+    offset: Option<u32>,
+}
+
+impl LexError {
+    pub(crate) fn new(message: &str, offset: u32) -> LexError {
+        LexError {
+            message: message.to_string(),
+            offset: Some(offset),
+        }
+    }
+
+    /// Returns a description of the error.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the byte offset in the source text where the error occurred,
+    /// if the tokens were parsed at runtime.
+    pub fn offset(&self) -> Option<usize> {
+        self.offset.map(|x| x as usize)
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.offset {
+            Some(offset) => write!(f, "{} at byte {}", self.message, offset),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum TokenStream {
     CompileTime(compile_time::TokenStream),
@@ -28,6 +76,12 @@ pub enum IntoIter {
     Runtime(alloc::vec::IntoIter<runtime::TokenTree>),
 }
 
+impl Default for TokenStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TokenStream {
     pub fn new() -> TokenStream {
         match proc_macro::is_available() {
@@ -37,14 +91,36 @@ impl TokenStream {
     }
 }
 
+impl FromStr for TokenStream {
+    type Err = LexError;
+
+    /// Parses Rust source code into tokens. Within a procedural macro this
+    /// uses the compiler's lexer, and otherwise the tokens are parsed at
+    /// runtime with byte offsets as their [Span]s.
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        match proc_macro::is_available() {
+            true => match src.parse::<proc_macro::TokenStream>() {
+                Ok(stream) => Ok(TokenStream::CompileTime(
+                    compile_time::TokenStream(stream),
+                )),
+                Err(e) => Err(LexError {
+                    message: e.to_string(),
+                    offset: None,
+                }),
+            },
+            false => src.parse().map(TokenStream::Runtime),
+        }
+    }
+}
+
 impl fmt::Display for TokenStream {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             TokenStream::CompileTime(token_stream) => {
-                write!(f, "{}", token_stream)
+                write!(f, "{token_stream}")
             }
             TokenStream::Runtime(token_stream) => {
-                write!(f, "{}", token_stream)
+                write!(f, "{token_stream}")
             }
         }
     }
@@ -78,10 +154,9 @@ impl Iterator for IntoIter {
     }
 }
 
-impl Into<TokenTree> for compile_time::TokenTree {
-    fn into(self) -> TokenTree {
-        let tt = unsafe { transmute::<_, proc_macro::TokenTree>(self.0) };
-        match tt {
+impl From<compile_time::TokenTree> for TokenTree {
+    fn from(val: compile_time::TokenTree) -> Self {
+        match val.0 {
             proc_macro::TokenTree::Group(group) => TokenTree::Group(
                 Group::CompileTime(compile_time::Group(group)),
             ),
@@ -98,9 +173,9 @@ impl Into<TokenTree> for compile_time::TokenTree {
     }
 }
 
-impl Into<TokenTree> for runtime::TokenTree {
-    fn into(self) -> TokenTree {
-        match self {
+impl From<runtime::TokenTree> for TokenTree {
+    fn from(val: runtime::TokenTree) -> Self {
+        match val {
             runtime::TokenTree::Group(group) => {
                 TokenTree::Group(Group::Runtime(group))
             }
@@ -181,8 +256,8 @@ impl Group {
 impl fmt::Display for Group {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Group::CompileTime(group) => write!(f, "{}", group),
-            Group::Runtime(group) => write!(f, "{}", group),
+            Group::CompileTime(group) => write!(f, "{group}"),
+            Group::Runtime(group) => write!(f, "{group}"),
         }
     }
 }
@@ -213,8 +288,8 @@ impl Ident {
 impl fmt::Display for Ident {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Ident::CompileTime(ident) => write!(f, "{}", ident),
-            Ident::Runtime(ident) => write!(f, "{}", ident),
+            Ident::CompileTime(ident) => write!(f, "{ident}"),
+            Ident::Runtime(ident) => write!(f, "{ident}"),
         }
     }
 }
@@ -257,8 +332,8 @@ impl Punct {
 impl fmt::Display for Punct {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Punct::CompileTime(punct) => write!(f, "{}", punct),
-            Punct::Runtime(punct) => write!(f, "{}", punct),
+            Punct::CompileTime(punct) => write!(f, "{punct}"),
+            Punct::Runtime(punct) => write!(f, "{punct}"),
         }
     }
 }
@@ -281,8 +356,8 @@ impl Literal {
 impl fmt::Display for Literal {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Literal::CompileTime(literal) => write!(f, "{}", literal),
-            Literal::Runtime(literal) => write!(f, "{}", literal),
+            Literal::CompileTime(literal) => write!(f, "{literal}"),
+            Literal::Runtime(literal) => write!(f, "{literal}"),
         }
     }
 }
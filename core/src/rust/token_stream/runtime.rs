@@ -3,31 +3,30 @@
 
 use alloc::{
     string::String,
+    vec,
     vec::{IntoIter, Vec},
 };
-use core::fmt;
+use core::{fmt, str::FromStr};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) enum Delimiter {
     Parenthesis,
     Brace,
     Bracket,
-    None,
 }
 
-impl Into<super::Delimiter> for Delimiter {
-    fn into(self) -> super::Delimiter {
-        match self {
+impl From<Delimiter> for super::Delimiter {
+    fn from(val: Delimiter) -> Self {
+        match val {
             Delimiter::Parenthesis => super::Delimiter::Parenthesis,
             Delimiter::Brace => super::Delimiter::Brace,
             Delimiter::Bracket => super::Delimiter::Bracket,
-            Delimiter::None => super::Delimiter::None,
         }
     }
 }
 
 #[derive(Debug, Clone)]
-pub(crate) struct TokenStream {
+pub struct TokenStream {
     pub tokens: Vec<TokenTree>,
 }
 
@@ -47,7 +46,13 @@ impl fmt::Display for TokenStream {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         for i in 0..self.tokens.len() {
             write!(f, "{}", self.tokens[i])?;
-            if i != self.tokens.len() - 1 {
+            // Joint punctuation like `->` is printed without a space so that
+            // it still means the same thing.
+            let joint = matches!(
+                &self.tokens[i],
+                TokenTree::Punct(p) if matches!(p.spacing, super::Spacing::Joint)
+            );
+            if i != self.tokens.len() - 1 && !joint {
                 write!(f, " ")?;
             }
         }
@@ -55,14 +60,27 @@ impl fmt::Display for TokenStream {
     }
 }
 
+/// The byte range of a token in the source it was parsed from.
 #[derive(Debug, Clone, Copy)]
-pub(crate) struct Span {
+pub struct Span {
     start: u32,
     end: u32,
 }
 
+impl Span {
+    #[inline(always)]
+    pub(crate) fn start(&self) -> usize {
+        self.start as usize
+    }
+
+    #[inline(always)]
+    pub(crate) fn end(&self) -> usize {
+        self.end as usize
+    }
+}
+
 #[derive(Debug, Clone)]
-pub(crate) enum TokenTree {
+pub enum TokenTree {
     Group(Group),
     Ident(Ident),
     Punct(Punct),
@@ -70,6 +88,7 @@ pub(crate) enum TokenTree {
 }
 
 impl TokenTree {
+    #[cfg(test)]
     pub(crate) fn span(&self) -> Span {
         match self {
             TokenTree::Group(x) => x.span,
@@ -83,16 +102,16 @@ impl TokenTree {
 impl fmt::Display for TokenTree {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            TokenTree::Group(group) => write!(f, "{}", group),
-            TokenTree::Ident(ident) => write!(f, "{}", ident),
-            TokenTree::Punct(punct) => write!(f, "{}", punct),
-            TokenTree::Literal(literal) => write!(f, "{}", literal),
+            TokenTree::Group(group) => write!(f, "{group}"),
+            TokenTree::Ident(ident) => write!(f, "{ident}"),
+            TokenTree::Punct(punct) => write!(f, "{punct}"),
+            TokenTree::Literal(literal) => write!(f, "{literal}"),
         }
     }
 }
 
 #[derive(Debug, Clone)]
-pub(crate) struct Literal {
+pub struct Literal {
     text: String,
     span: Span,
 }
@@ -110,14 +129,19 @@ impl Literal {
 }
 
 #[derive(Debug, Clone)]
-pub(crate) struct Ident {
+pub struct Ident {
     string: String,
-    stream: TokenStream,
+    /// Whether this is a raw identifier like `r#type`. The `r#` is not part
+    /// of `string`.
+    is_raw: bool,
     span: Span,
 }
 
 impl fmt::Display for Ident {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_raw {
+            write!(f, "r#")?;
+        }
         write!(f, "{}", self.string)
     }
 }
@@ -129,7 +153,7 @@ impl Ident {
 }
 
 #[derive(Debug, Clone)]
-pub(crate) struct Punct {
+pub struct Punct {
     ch: char,
     spacing: super::Spacing,
     span: Span,
@@ -156,21 +180,13 @@ impl Punct {
 }
 
 #[derive(Debug, Clone)]
-pub(crate) struct Group {
+pub struct Group {
     delimiter: Delimiter,
     stream: TokenStream,
     span: Span,
 }
 
 impl Group {
-    pub(crate) fn new(delimiter: Delimiter, stream: TokenStream) -> Self {
-        Group {
-            delimiter,
-            stream,
-            span: Span { start: 0, end: 0 },
-        }
-    }
-
     pub(crate) fn delimiter(&self) -> Delimiter {
         self.delimiter.clone()
     }
@@ -190,7 +206,669 @@ impl fmt::Display for Group {
             Delimiter::Parenthesis => write!(f, "({})", self.stream),
             Delimiter::Brace => write!(f, "{{{}}}", self.stream),
             Delimiter::Bracket => write!(f, "[{}]", self.stream),
-            Delimiter::None => write!(f, "{}", self.stream),
         }
     }
 }
+
+impl FromStr for TokenStream {
+    type Err = super::LexError;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        if u32::try_from(src.len()).is_err() {
+            return Err(super::LexError::new("input is too large", 0));
+        }
+
+        let mut lexer = Lexer { src, pos: 0 };
+        let tokens = lexer.stream(None)?;
+        Ok(TokenStream { tokens })
+    }
+}
+
+/// Returns true if `c` can start an identifier.
+#[inline(always)]
+fn is_ident_start(c: char) -> bool {
+    c == '_' || c.is_alphabetic()
+}
+
+/// Returns true if `c` can appear in an identifier after the first character.
+#[inline(always)]
+fn is_ident_continue(c: char) -> bool {
+    c == '_' || c.is_alphanumeric()
+}
+
+/// Returns true if `c` is a punctuation character which can be part of a
+/// multi-character operator.
+#[inline(always)]
+fn is_punct(c: char) -> bool {
+    matches!(
+        c,
+        '=' | '<'
+            | '>'
+            | '!'
+            | '~'
+            | '+'
+            | '-'
+            | '*'
+            | '/'
+            | '%'
+            | '^'
+            | '&'
+            | '|'
+            | '@'
+            | '.'
+            | ','
+            | ';'
+            | ':'
+            | '#'
+            | '$'
+            | '?'
+    )
+}
+
+/// Turns Rust source code into [TokenTree]s the same way the compiler does
+/// for procedural macros. Comments are skipped, and doc comments are turned
+/// into `#[doc = "..."]` attributes.
+struct Lexer<'s> {
+    src: &'s str,
+    /// The byte offset of the next character to read.
+    pos: usize,
+}
+
+impl Lexer<'_> {
+    #[inline(always)]
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    #[inline(always)]
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.src[self.pos..].chars().nth(n)
+    }
+
+    #[inline(always)]
+    fn starts_with(&self, s: &str) -> bool {
+        self.src[self.pos..].starts_with(s)
+    }
+
+    #[inline(always)]
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    #[inline(always)]
+    fn eat_while(&mut self, f: impl Fn(char) -> bool) {
+        while let Some(c) = self.peek() {
+            if !f(c) {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+    }
+
+    #[inline(always)]
+    fn span(&self, start: usize) -> Span {
+        // The length of the input was checked to fit in a u32 up front.
+        Span {
+            start: start as u32,
+            end: self.pos as u32,
+        }
+    }
+
+    #[inline(always)]
+    fn error(&self, message: &'static str, at: usize) -> super::LexError {
+        super::LexError::new(message, at as u32)
+    }
+
+    /// Reads tokens until `close` is found, or until the end of the input if
+    /// `close` is [None].
+    fn stream(
+        &mut self,
+        close: Option<(char, usize)>,
+    ) -> Result<Vec<TokenTree>, super::LexError> {
+        let mut tokens = Vec::new();
+        loop {
+            self.eat_while(char::is_whitespace);
+            let start = self.pos;
+            let c = match self.peek() {
+                Some(c) => c,
+                None => match close {
+                    Some((_, open)) => {
+                        return Err(self.error("unclosed delimiter", open))
+                    }
+                    None => return Ok(tokens),
+                },
+            };
+
+            match c {
+                '/' if self.starts_with("//") => {
+                    self.line_comment(&mut tokens)
+                }
+                '/' if self.starts_with("/*") => {
+                    self.block_comment(&mut tokens)?
+                }
+                '(' | '[' | '{' => {
+                    self.bump();
+                    let (delimiter, close) = match c {
+                        '(' => (Delimiter::Parenthesis, ')'),
+                        '[' => (Delimiter::Bracket, ']'),
+                        _ => (Delimiter::Brace, '}'),
+                    };
+                    let stream = self.stream(Some((close, start)))?;
+                    tokens.push(TokenTree::Group(Group {
+                        delimiter,
+                        stream: TokenStream { tokens: stream },
+                        span: self.span(start),
+                    }));
+                }
+                ')' | ']' | '}' => match close {
+                    Some((close, _)) if close == c => {
+                        self.bump();
+                        return Ok(tokens);
+                    }
+                    _ => {
+                        return Err(
+                            self.error("unexpected closing delimiter", start)
+                        )
+                    }
+                },
+                '\'' => self.quote(&mut tokens)?,
+                '"' => {
+                    self.bump();
+                    self.quoted('"', start)?;
+                    tokens.push(self.literal(start));
+                }
+                '0'..='9' => {
+                    self.number();
+                    tokens.push(self.literal(start));
+                }
+                c if is_ident_start(c) => self.word(&mut tokens)?,
+                c if is_punct(c) => {
+                    self.bump();
+                    let spacing = match self.peek() {
+                        Some(next) if is_punct(next) => super::Spacing::Joint,
+                        _ => super::Spacing::Alone,
+                    };
+                    tokens.push(TokenTree::Punct(Punct {
+                        ch: c,
+                        spacing,
+                        span: self.span(start),
+                    }));
+                }
+                _ => return Err(self.error("unexpected character", start)),
+            }
+        }
+    }
+
+    /// Returns a [TokenTree::Literal] containing the source text from
+    /// `start` to the current position.
+    #[inline(always)]
+    fn literal(&self, start: usize) -> TokenTree {
+        TokenTree::Literal(Literal {
+            text: self.src[start..self.pos].into(),
+            span: self.span(start),
+        })
+    }
+
+    /// Reads a `//` comment, adding a `#[doc]` attribute for doc comments.
+    fn line_comment(&mut self, tokens: &mut Vec<TokenTree>) {
+        let start = self.pos;
+        let inner = self.starts_with("//!");
+        let outer = self.starts_with("///") && !self.starts_with("////");
+        self.eat_while(|c| c != '\n');
+        if inner || outer {
+            let text = &self.src[start + 3..self.pos];
+            let text = text.strip_suffix('\r').unwrap_or(text);
+            self.doc(tokens, text, inner, start);
+        }
+    }
+
+    /// Reads a (possibly nested) `/* */` comment, adding a `#[doc]` attribute
+    /// for doc comments.
+    fn block_comment(
+        &mut self,
+        tokens: &mut Vec<TokenTree>,
+    ) -> Result<(), super::LexError> {
+        let start = self.pos;
+        let inner = self.starts_with("/*!");
+        let outer = self.starts_with("/**")
+            && !self.starts_with("/***")
+            && !self.starts_with("/**/");
+        self.pos += 2;
+
+        let mut depth = 1;
+        while depth > 0 {
+            if self.starts_with("/*") {
+                self.pos += 2;
+                depth += 1;
+            } else if self.starts_with("*/") {
+                self.pos += 2;
+                depth -= 1;
+            } else if self.bump().is_none() {
+                return Err(self.error("unterminated block comment", start));
+            }
+        }
+
+        if inner || outer {
+            let text = &self.src[start + 3..self.pos - 2];
+            self.doc(tokens, text, inner, start);
+        }
+        Ok(())
+    }
+
+    /// Adds the `#[doc = "text"]` attribute a doc comment is equivalent to.
+    fn doc(
+        &self,
+        tokens: &mut Vec<TokenTree>,
+        text: &str,
+        inner: bool,
+        start: usize,
+    ) {
+        let span = self.span(start);
+        let punct = |ch| {
+            TokenTree::Punct(Punct {
+                ch,
+                spacing: super::Spacing::Alone,
+                span,
+            })
+        };
+
+        tokens.push(punct('#'));
+        if inner {
+            tokens.push(punct('!'));
+        }
+
+        let mut literal = String::from("\"");
+        for c in text.chars() {
+            literal.extend(c.escape_debug());
+        }
+        literal.push('"');
+
+        tokens.push(TokenTree::Group(Group {
+            delimiter: Delimiter::Bracket,
+            stream: TokenStream {
+                tokens: vec![
+                    TokenTree::Ident(Ident {
+                        string: "doc".into(),
+                        is_raw: false,
+                        span,
+                    }),
+                    punct('='),
+                    TokenTree::Literal(Literal {
+                        text: literal,
+                        span,
+                    }),
+                ],
+            },
+            span,
+        }));
+    }
+
+    /// Reads either a character literal or a lifetime.
+    fn quote(
+        &mut self,
+        tokens: &mut Vec<TokenTree>,
+    ) -> Result<(), super::LexError> {
+        let start = self.pos;
+        self.bump();
+        match (self.peek(), self.peek_nth(1)) {
+            (Some('\\'), _) | (Some(_), Some('\'')) => {
+                self.quoted('\'', start)?;
+                tokens.push(self.literal(start));
+            }
+            (Some(c), _) if is_ident_start(c) => {
+                tokens.push(TokenTree::Punct(Punct {
+                    ch: '\'',
+                    spacing: super::Spacing::Joint,
+                    span: self.span(start),
+                }));
+                let start = self.pos;
+                self.eat_while(is_ident_continue);
+                tokens.push(TokenTree::Ident(Ident {
+                    string: self.src[start..self.pos].into(),
+                    is_raw: false,
+                    span: self.span(start),
+                }));
+            }
+            _ => return Err(self.error("invalid character literal", start)),
+        }
+        Ok(())
+    }
+
+    /// Reads the rest of a string or character literal whose opening `quote`
+    /// has already been read, as well as its suffix.
+    fn quoted(
+        &mut self,
+        quote: char,
+        start: usize,
+    ) -> Result<(), super::LexError> {
+        loop {
+            match self.bump() {
+                Some('\\') => {
+                    if self.bump().is_none() {
+                        break;
+                    }
+                }
+                Some(c) if c == quote => {
+                    self.eat_while(is_ident_continue);
+                    return Ok(());
+                }
+                Some(_) => {}
+                None => break,
+            }
+        }
+
+        match quote {
+            '"' => Err(self.error("unterminated string literal", start)),
+            _ => Err(self.error("unterminated character literal", start)),
+        }
+    }
+
+    /// Reads the rest of a raw string literal, starting from the `#`s or `"`
+    /// after its prefix.
+    fn raw_quoted(&mut self, start: usize) -> Result<(), super::LexError> {
+        let hashes_start = self.pos;
+        self.eat_while(|c| c == '#');
+        let hashes = &self.src[hashes_start..self.pos];
+        if self.bump() != Some('"') {
+            return Err(self.error("invalid raw string literal", start));
+        }
+
+        loop {
+            match self.bump() {
+                Some('"') if self.starts_with(hashes) => {
+                    self.pos += hashes.len();
+                    self.eat_while(is_ident_continue);
+                    return Ok(());
+                }
+                Some(_) => {}
+                None => {
+                    return Err(
+                        self.error("unterminated raw string literal", start)
+                    )
+                }
+            }
+        }
+    }
+
+    /// Reads an integer or floating point literal, including its suffix.
+    fn number(&mut self) {
+        let radix = match (self.peek(), self.peek_nth(1)) {
+            (Some('0'), Some('x')) => 16,
+            (Some('0'), Some('o')) => 8,
+            (Some('0'), Some('b')) => 2,
+            _ => 10,
+        };
+
+        if radix != 10 {
+            self.pos += 2;
+            self.eat_while(|c| c == '_' || c.is_ascii_hexdigit());
+            self.eat_while(is_ident_continue);
+            return;
+        }
+
+        self.eat_while(|c| c == '_' || c.is_ascii_digit());
+        // `1..2` and `1.foo()` are integers followed by punctuation, but `1.`
+        // and `1.5` are floats.
+        if self.peek() == Some('.')
+            && !matches!(self.peek_nth(1), Some(c) if c == '.' || is_ident_start(c))
+        {
+            self.bump();
+            self.eat_while(|c| c == '_' || c.is_ascii_digit());
+        }
+
+        let exponent = match (self.peek(), self.peek_nth(1), self.peek_nth(2))
+        {
+            (Some('e' | 'E'), Some('+' | '-'), Some(c)) => c.is_ascii_digit(),
+            (Some('e' | 'E'), Some(c), _) => c == '_' || c.is_ascii_digit(),
+            _ => false,
+        };
+        if exponent {
+            self.bump();
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.bump();
+            }
+            self.eat_while(|c| c == '_' || c.is_ascii_digit());
+        }
+
+        self.eat_while(is_ident_continue);
+    }
+
+    /// Reads an identifier, raw identifier, or a literal with a prefix such
+    /// as `b"bytes"`, `r#"raw"#` or `c"string"`.
+    fn word(
+        &mut self,
+        tokens: &mut Vec<TokenTree>,
+    ) -> Result<(), super::LexError> {
+        let start = self.pos;
+        let next = (self.peek(), self.peek_nth(1), self.peek_nth(2));
+        match next {
+            (Some('r'), Some('#'), Some(c)) if is_ident_start(c) => {
+                self.pos += 2;
+                let ident_start = self.pos;
+                self.eat_while(is_ident_continue);
+                tokens.push(TokenTree::Ident(Ident {
+                    string: self.src[ident_start..self.pos].into(),
+                    is_raw: true,
+                    span: self.span(start),
+                }));
+                return Ok(());
+            }
+            (Some('r'), Some('"' | '#'), _) => {
+                self.pos += 1;
+                self.raw_quoted(start)?;
+            }
+            (Some('b' | 'c'), Some('r'), Some('"' | '#')) => {
+                self.pos += 2;
+                self.raw_quoted(start)?;
+            }
+            (Some('b'), Some('\''), _) => {
+                self.pos += 2;
+                self.quoted('\'', start)?;
+            }
+            (Some('b' | 'c'), Some('"'), _) => {
+                self.pos += 2;
+                self.quoted('"', start)?;
+            }
+            _ => {
+                self.eat_while(is_ident_continue);
+                tokens.push(TokenTree::Ident(Ident {
+                    string: self.src[start..self.pos].into(),
+                    is_raw: false,
+                    span: self.span(start),
+                }));
+                return Ok(());
+            }
+        }
+
+        tokens.push(self.literal(start));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{format, string::ToString};
+
+    fn lex(src: &str) -> Vec<TokenTree> {
+        src.parse::<TokenStream>().unwrap().tokens
+    }
+
+    /// Returns a short description of each token, with joint punctuation
+    /// followed by a `+`, literals prefixed by `lit:` and groups written out
+    /// with their delimiters.
+    fn describe(tokens: &[TokenTree]) -> Vec<String> {
+        tokens
+            .iter()
+            .map(|t| match t {
+                TokenTree::Ident(ident) => ident.to_string(),
+                TokenTree::Punct(p) => match p.spacing {
+                    super::super::Spacing::Joint => format!("{}+", p.ch),
+                    super::super::Spacing::Alone => format!("{}", p.ch),
+                },
+                TokenTree::Literal(lit) => format!("lit:{}", lit.text),
+                TokenTree::Group(group) => {
+                    let (open, close) = match group.delimiter {
+                        Delimiter::Parenthesis => ("(", ")"),
+                        Delimiter::Brace => ("{", "}"),
+                        Delimiter::Bracket => ("[", "]"),
+                    };
+                    format!(
+                        "{}{}{}",
+                        open,
+                        describe(&group.stream.tokens).join(" "),
+                        close
+                    )
+                }
+            })
+            .collect()
+    }
+
+    fn lex_describe(src: &str) -> Vec<String> {
+        describe(&lex(src))
+    }
+
+    fn lex_err(src: &str) -> super::super::LexError {
+        src.parse::<TokenStream>().unwrap_err()
+    }
+
+    #[test]
+    fn lexes_idents_and_punct() {
+        assert_eq!(
+            lex_describe("pub fn foo(x: i32) -> i32 { x += 1; x }"),
+            [
+                "pub",
+                "fn",
+                "foo",
+                "(x : i32)",
+                "-+",
+                ">",
+                "i32",
+                "{x ++ = lit:1 ; x}"
+            ]
+        );
+    }
+
+    #[test]
+    fn lexes_raw_idents_and_lifetimes() {
+        assert_eq!(
+            lex_describe("r#type &'a str _"),
+            ["r#type", "&", "'+", "a", "str", "_"]
+        );
+    }
+
+    #[test]
+    fn lexes_char_literals() {
+        assert_eq!(
+            lex_describe(r"'a' '\n' '\'' '\u{1F600}' b'x' 'é'"),
+            [
+                "lit:'a'",
+                r"lit:'\n'",
+                r"lit:'\''",
+                r"lit:'\u{1F600}'",
+                "lit:b'x'",
+                "lit:'é'"
+            ]
+        );
+    }
+
+    #[test]
+    fn lexes_string_literals() {
+        assert_eq!(
+            lex_describe(
+                r###""a\"b" b"bytes" r#"raw "quote""# br"x" c"c" cr##"y"##"###
+            ),
+            [
+                r#"lit:"a\"b""#,
+                r#"lit:b"bytes""#,
+                r###"lit:r#"raw "quote""#"###,
+                r#"lit:br"x""#,
+                r#"lit:c"c""#,
+                r###"lit:cr##"y"##"###
+            ]
+        );
+        assert_eq!(lex_describe(r#""suffix"foo"#), [r#"lit:"suffix"foo"#]);
+    }
+
+    #[test]
+    fn lexes_number_literals() {
+        assert_eq!(
+            lex_describe("1 1_000u32 0x1F 0b1010_u8 1.5 1e10 2.5E-3f64 1."),
+            [
+                "lit:1",
+                "lit:1_000u32",
+                "lit:0x1F",
+                "lit:0b1010_u8",
+                "lit:1.5",
+                "lit:1e10",
+                "lit:2.5E-3f64",
+                "lit:1."
+            ]
+        );
+        assert_eq!(
+            lex_describe("1..2 1.foo()"),
+            ["lit:1", ".+", ".", "lit:2", "lit:1", ".", "foo", "()"]
+        );
+    }
+
+    #[test]
+    fn skips_comments() {
+        assert_eq!(
+            lex_describe("a // line\n/* block /* nested */ */ b //// not doc"),
+            ["a", "b"]
+        );
+        assert_eq!(lex_describe("/**/ /*** not doc */ c"), ["c"]);
+    }
+
+    #[test]
+    fn lexes_doc_comments() {
+        assert_eq!(
+            lex_describe("/// outer \"doc\"\n//! inner\n/** block */"),
+            [
+                "#",
+                r#"[doc = lit:" outer \"doc\""]"#,
+                "#",
+                "!",
+                r#"[doc = lit:" inner"]"#,
+                "#",
+                r#"[doc = lit:" block "]"#
+            ]
+        );
+    }
+
+    #[test]
+    fn lexes_spans() {
+        let tokens = lex("foo  (bar)");
+        assert_eq!(tokens[0].span().start(), 0);
+        assert_eq!(tokens[0].span().end(), 3);
+        assert_eq!(tokens[1].span().start(), 5);
+        assert_eq!(tokens[1].span().end(), 10);
+        let TokenTree::Group(group) = &tokens[1] else {
+            panic!("expected a group");
+        };
+        let inner = &group.stream.tokens[0];
+        assert_eq!(inner.span().start(), 6);
+        assert_eq!(inner.span().end(), 9);
+    }
+
+    #[test]
+    fn displays_joint_punct_together() {
+        let stream = "a -> b :: c".parse::<TokenStream>().unwrap();
+        assert_eq!(stream.to_string(), "a -> b :: c");
+        let stream = "a->b".parse::<TokenStream>().unwrap();
+        assert_eq!(stream.to_string(), "a -> b");
+    }
+
+    #[test]
+    fn reports_errors() {
+        assert_eq!(lex_err("\"abc").offset(), Some(0));
+        assert_eq!(lex_err("a ( [ )").offset(), Some(6));
+        assert_eq!(lex_err("a ( b").offset(), Some(2));
+        assert_eq!(lex_err("  /* a").offset(), Some(2));
+        assert_eq!(lex_err("'").offset(), Some(0));
+        assert_eq!(lex_err("r#\"abc\"").offset(), Some(0));
+        assert_eq!(lex_err("\"abc").message(), "unterminated string literal");
+    }
+}
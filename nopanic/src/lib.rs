@@ -0,0 +1,253 @@
+extern crate proc_macro;
+
+use proc_macro::{
+    Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream,
+    TokenTree,
+};
+
+/// Fails the build if the annotated function can panic.
+///
+/// The function body is run inside a guard whose destructor calls an
+/// `extern "C"` function which doesn't exist. The destructor only runs if the
+/// body unwinds, so when the optimizer can prove the body never panics, the
+/// call is removed and the program links. Otherwise linking fails with an
+/// undefined symbol whose name says which function may panic:
+///
+/// ```text
+/// error: linking with `cc` failed: exit status: 1
+///   = note: rust-lld: error: undefined symbol:
+///           error: function `get` annotated with #[nopanic] may panic
+/// ```
+///
+/// This relies on the optimizer, so the check is only done in builds without
+/// `debug_assertions`, where it would otherwise fail for nearly every
+/// function. Use `#[nopanic(always)]` to check in every build, for example if
+/// the dev profile sets an `opt-level`. The check also needs `panic =
+/// "unwind"`, since with `panic = "abort"` there's no unwinding to detect.
+///
+/// The body is moved into a closure which is called immediately, so `return`
+/// and `?` work as usual. `async` and `const` functions aren't supported.
+#[proc_macro_attribute]
+pub fn nopanic(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let always = match attr.into_iter().collect::<Vec<_>>().as_slice() {
+        [] => false,
+        [TokenTree::Ident(x)] if x.to_string() == "always" => true,
+        [x, ..] => {
+            return error(
+                "expected `#[nopanic]` or `#[nopanic(always)]`",
+                x.span(),
+                item,
+            )
+        }
+    };
+
+    let tokens: Vec<TokenTree> = item.clone().into_iter().collect();
+    let Some(fn_index) = tokens.iter().position(|x| is_ident(x, "fn")) else {
+        return error(
+            "#[nopanic] can only be used on functions",
+            Span::call_site(),
+            item,
+        );
+    };
+
+    if let Some(x) = tokens[..fn_index]
+        .iter()
+        .find(|x| is_ident(x, "async") || is_ident(x, "const"))
+    {
+        return error(
+            &format!("#[nopanic] can't be used on {x} functions"),
+            x.span(),
+            item,
+        );
+    }
+
+    let name = match tokens.get(fn_index + 1) {
+        Some(TokenTree::Ident(x)) => x.clone(),
+        _ => {
+            return error(
+                "#[nopanic] can only be used on functions",
+                tokens[fn_index].span(),
+                item,
+            )
+        }
+    };
+
+    let (body, signature) = match tokens.split_last() {
+        Some((TokenTree::Group(x), rest))
+            if x.delimiter() == Delimiter::Brace =>
+        {
+            (x.clone(), rest)
+        }
+        _ => {
+            return error(
+                "#[nopanic] requires a function body",
+                name.span(),
+                item,
+            )
+        }
+    };
+
+    let return_type = return_type(&signature[fn_index + 2..]);
+
+    // `impl Trait` isn't allowed as the return type of a closure, so it's
+    // left to be inferred.
+    let mut closure = "move ||".parse::<TokenStream>().unwrap();
+    if !return_type.iter().any(contains_impl) {
+        closure.extend(" -> ".parse::<TokenStream>());
+        if return_type.is_empty() {
+            closure.extend("()".parse::<TokenStream>());
+        } else {
+            closure.extend(return_type.iter().cloned());
+        }
+    }
+    let span = body.span();
+    closure.extend([TokenTree::Group(body)]);
+
+    let cfg = if always {
+        ""
+    } else {
+        "#[cfg(not(debug_assertions))]"
+    };
+    let message = format!(
+        "\n\nerror: function `{name}` annotated with #[nopanic] may panic\n\n"
+    );
+    let mut new_body: TokenStream = format!(
+        r#"
+        {cfg}
+        struct __StdxNoPanic;
+
+        {cfg}
+        impl ::core::ops::Drop for __StdxNoPanic {{
+            #[inline(always)]
+            fn drop(&mut self) {{
+                extern "C" {{
+                    #[link_name = {message}]
+                    fn __stdx_nopanic_trigger() -> !;
+                }}
+
+                // SAFETY: the symbol doesn't exist, so this either never
+                //         runs or the program doesn't link.
+                unsafe {{ __stdx_nopanic_trigger() }}
+            }}
+        }}
+
+        {cfg}
+        let __stdx_nopanic_guard = __StdxNoPanic;
+        let __stdx_nopanic_result =
+        "#,
+        message = Literal::string(&message),
+    )
+    .parse()
+    .unwrap();
+    new_body.extend([TokenTree::Group(Group::new(
+        Delimiter::Parenthesis,
+        closure,
+    ))]);
+    new_body.extend(
+        format!(
+            "
+            ();
+            {cfg}
+            ::core::mem::forget(__stdx_nopanic_guard);
+            __stdx_nopanic_result
+            "
+        )
+        .parse::<TokenStream>(),
+    );
+
+    let mut body = Group::new(Delimiter::Brace, new_body);
+    body.set_span(span);
+
+    let mut result: TokenStream = signature.iter().cloned().collect();
+    result.extend([TokenTree::Group(body)]);
+    result
+}
+
+/// Returns the tokens after the `->` in a function signature, starting from
+/// the token after the function's name, up to the `where` clause if there is
+/// one.
+fn return_type(signature: &[TokenTree]) -> &[TokenTree] {
+    // The parameters are the first parenthesized group outside of the
+    // generics, which can contain parentheses in bounds like `F: Fn(u8)`.
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < signature.len() {
+        match &signature[i] {
+            TokenTree::Punct(x) if x.as_char() == '<' => depth += 1,
+            TokenTree::Punct(x) if x.as_char() == '>' => {
+                depth = depth.saturating_sub(1)
+            }
+            TokenTree::Punct(x) if is_arrow(x, signature.get(i + 1)) => i += 1,
+            TokenTree::Group(x)
+                if depth == 0 && x.delimiter() == Delimiter::Parenthesis =>
+            {
+                break;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let rest = signature.get(i + 1..).unwrap_or_default();
+    match rest {
+        [TokenTree::Punct(x), next, rest @ ..] if is_arrow(x, Some(next)) => {
+            let end = rest
+                .iter()
+                .position(|x| is_ident(x, "where"))
+                .unwrap_or(rest.len());
+            &rest[..end]
+        }
+        _ => &[],
+    }
+}
+
+fn is_arrow(x: &Punct, next: Option<&TokenTree>) -> bool {
+    x.as_char() == '-'
+        && x.spacing() == Spacing::Joint
+        && matches!(next, Some(TokenTree::Punct(y)) if y.as_char() == '>')
+}
+
+fn is_ident(x: &TokenTree, name: &str) -> bool {
+    matches!(x, TokenTree::Ident(x) if x.to_string() == name)
+}
+
+fn contains_impl(x: &TokenTree) -> bool {
+    match x {
+        TokenTree::Group(x) => {
+            x.stream().into_iter().any(|x| contains_impl(&x))
+        }
+        x => is_ident(x, "impl"),
+    }
+}
+
+/// Returns a `compile_error!` pointing at `span`, followed by the unmodified
+/// `item` so that uses of it don't cause more errors.
+fn error(message: &str, span: Span, item: TokenStream) -> TokenStream {
+    let mut message = Literal::string(message);
+    message.set_span(span);
+    let mut args = Group::new(
+        Delimiter::Parenthesis,
+        [TokenTree::Literal(message)].into_iter().collect(),
+    );
+    args.set_span(span);
+
+    let mut result: TokenStream = [
+        TokenTree::Punct(Punct::new(':', Spacing::Joint)),
+        TokenTree::Punct(Punct::new(':', Spacing::Alone)),
+        TokenTree::Ident(Ident::new("core", span)),
+        TokenTree::Punct(Punct::new(':', Spacing::Joint)),
+        TokenTree::Punct(Punct::new(':', Spacing::Alone)),
+        TokenTree::Ident(Ident::new("compile_error", span)),
+        TokenTree::Punct(Punct::new('!', Spacing::Alone)),
+        TokenTree::Group(args),
+        TokenTree::Punct(Punct::new(';', Spacing::Alone)),
+    ]
+    .into_iter()
+    .map(|mut x| {
+        x.set_span(span);
+        x
+    })
+    .collect();
+    result.extend(item);
+    result
+}
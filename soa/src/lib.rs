@@ -3,14 +3,27 @@ extern crate proc_macro;
 use proc_macro::TokenStream;
 use stdx_core::rust;
 
+/// Generates a struct-of-arrays container for a struct with named fields.
+/// Generic structs aren't supported.
+///
+/// For a struct `Foo`, this generates:
+///
+/// - `SoaFoo<A: Allocator = Global>`, which stores each field of `Foo` in its
+///   own array, all placed in a single allocation from `A`.
+/// - `FooRef<'a>` and `FooRefMut<'a>`, which hold references to each field of
+///   a single element, returned by `SoaFoo::get` and `SoaFoo::get_mut`.
+///
+/// Every field `x` can be accessed as a slice through `SoaFoo::xs` and
+/// `SoaFoo::xs_mut`.
+///
+/// The generated code uses the unstable `allocator_api` feature, so it must
+/// be enabled in the crate using this macro.
 #[proc_macro_derive(Soa)]
 pub fn soa_derive(input: TokenStream) -> TokenStream {
     let decls = match rust::parse_type_decls(rust::TokenStream::from(input)) {
         Ok(x) => x,
         Err(e) => {
-            return format!("::std::compile_error!(\"{}\")", e)
-                .parse()
-                .unwrap();
+            return format!("::std::compile_error!({e:?});").parse().unwrap();
         }
     };
 
@@ -18,19 +31,32 @@ pub fn soa_derive(input: TokenStream) -> TokenStream {
         return format!(
             "{}{}",
             "::std::compile_error!(\"a derive macro can only be ",
-            "used on a single type definition\")"
+            "used on a single type definition\");"
         )
         .parse()
         .unwrap();
     }
 
-    let (name, fields) = match decls[0].clone() {
-        rust::TypeDecl::Struct { name, fields } => (name, fields),
+    let (vis, name, fields) = match decls[0].clone() {
+        rust::TypeDecl::Struct {
+            generics: Some(_), ..
+        } => {
+            return format!(
+                "{}{}",
+                r#"::std::compile_error!("derive(Soa) doesn't support "#,
+                r#"generic structs");"#
+            )
+            .parse()
+            .unwrap()
+        }
+        rust::TypeDecl::Struct {
+            vis, name, fields, ..
+        } if !fields.is_empty() => (vis.unwrap_or_default(), name, fields),
         _ => {
             return format!(
                 "{}{}",
                 r#"::std::compile_error!("derive(Soa) is only valid on "#,
-                r#"structs with named fields")"#
+                r#"structs with named fields");"#
             )
             .parse()
             .unwrap()
@@ -38,41 +64,49 @@ pub fn soa_derive(input: TokenStream) -> TokenStream {
     };
 
     let struct_name = format!("Soa{name}");
-    // Implement the methods returning the slices for each field.
+    let ref_name = format!("{name}Ref");
+    let ref_mut_name = format!("{name}RefMut");
+    let num_fields = fields.len();
+
     let mut field_slice_methods = String::new();
-    let mut offset_calculation = String::from("0");
-    let mut single_element_size = String::from("0");
+    let mut ref_fields = String::new();
+    let mut ref_mut_fields = String::new();
+    let mut get_fields = String::new();
+    let mut get_mut_fields = String::new();
     let mut alignments = String::new();
-    let mut field_types = Vec::<String>::new();
-    let mut resize_body = String::new();
+    let mut offset_calculation = String::new();
+    let mut move_fields = String::new();
     let mut write_fields_from_value = String::new();
-    let num_fields = fields.len();
-    for field in &fields {
-        let field_name = field.name.clone();
-        let field_type_name = field.ty.clone();
+    let mut read_fields = String::new();
+    let mut swap_remove_fields = String::new();
+    let mut drop_fields = String::new();
+    for (i, field) in fields.iter().enumerate() {
+        let field_name = &field.name;
+        let ty = &field.ty;
+
         field_slice_methods.push_str(&format!(
             "
+            /// Returns the `{field_name}` field of every element.
             #[inline]
-            pub fn {field_name}s(&self) {{
-                // SAFETY: the memory layout has each field's slice in
-                //         contiguous memory, one after the other in the order
-                //         they appear in the source code.
-                unsafe {{ 
+            pub fn {field_name}s(&self) -> &[{ty}] {{
+                // SAFETY: the first `len` elements of each array are
+                //         initialized.
+                unsafe {{
                     ::core::slice::from_raw_parts(
-                        self.ptr.byte_offset({offset_calculation}).cast(),
+                        self.field_ptr::<{ty}>({i}),
                         self.len,
                     )
                 }}
             }}
 
+            /// Returns the `{field_name}` field of every element.
             #[inline]
-            pub fn {field_name}s_mut(&mut self) {{
-                // SAFETY: the memory layout has each field's slice in
-                //         contiguous memory, one after the other in the order
-                //         they appear in the source code.
-                unsafe {{ 
-                    ::core::slice::from_raw_parts(
-                        self.ptr.byte_offset({offset_calculation}).cast(),
+            pub fn {field_name}s_mut(&mut self) -> &mut [{ty}] {{
+                // SAFETY: the first `len` elements of each array are
+                //         initialized.
+                unsafe {{
+                    ::core::slice::from_raw_parts_mut(
+                        self.field_ptr::<{ty}>({i}),
                         self.len,
                     )
                 }}
@@ -80,232 +114,473 @@ pub fn soa_derive(input: TokenStream) -> TokenStream {
             "
         ));
 
-        // update the current offset calculation to go past this element.
-        offset_calculation.push_str(&format!(
-            " + ::core::mem::size_of<{field_type_name}>()*self.len"
+        ref_fields.push_str(&format!("pub {field_name}: &'a {ty},\n"));
+        ref_mut_fields.push_str(&format!("pub {field_name}: &'a mut {ty},\n"));
+        get_fields.push_str(&format!(
+            "{field_name}: &*self.field_ptr::<{ty}>({i}).add(index),\n"
         ));
-
-        // add the field to the total size calculation.
-        single_element_size.push_str(&format!(
-            " + ::core::mem::size_of<{field_type_name}>()"
+        get_mut_fields.push_str(&format!(
+            "{field_name}: &mut *self.field_ptr::<{ty}>({i}).add(index),\n"
         ));
 
-        // add the alignment expression to the list.
-        alignments
-            .push_str(&format!("::core::mem::align_of<{field_type_name}>(),"));
+        alignments.push_str(&format!("::core::mem::align_of::<{ty}>(),"));
 
-        // In the resize method, we move around all the arrays within the
-        // capacity.
-        resize_body.push_str(&format!(
+        // Each field's array starts at the first suitably aligned offset
+        // after the end of the previous field's array.
+        offset_calculation.push_str(&format!(
             "
-            old_cursor = align_up(
-                old_cursor, ::core::mem::align_of<{field_type_name}>());
-            cursor = align_up(
-                cursor, ::core::mem::align_of<{field_type_name}>());
-            ::core::ptr::copy::<u8>(
-                self.ptr.byte_offset(old_cursor).cast(),
-                new_ptr.byte_offset(cursor).cast(),
-                self.len*::core::mem::size_of<{field_type_name}>());
-            cursor += new_cap * ::core::mem::size_of<{field_type_name}>();
-            new_cursor += self.cap * ::core::mem::size_of<{field_type_name}>();
+            cursor = match cursor.checked_next_multiple_of(
+                ::core::mem::align_of::<{ty}>()) {{
+                ::core::option::Option::Some(x) => x,
+                ::core::option::Option::None =>
+                    return ::core::option::Option::None,
+            }};
+            offsets[{i}] = cursor;
+            cursor = match ::core::mem::size_of::<{ty}>().checked_mul(cap) {{
+                ::core::option::Option::Some(size) =>
+                    match cursor.checked_add(size) {{
+                        ::core::option::Option::Some(x) => x,
+                        ::core::option::Option::None =>
+                            return ::core::option::Option::None,
+                    }},
+                ::core::option::Option::None =>
+                    return ::core::option::Option::None,
+            }};
+            "
+        ));
+
+        // When growing, each array is moved from its old offset to its new
+        // one.
+        move_fields.push_str(&format!(
+            "
+            ::core::ptr::copy_nonoverlapping(
+                self.field_ptr::<{ty}>({i}),
+                new_ptr.as_ptr().add(offsets[{i}]).cast::<{ty}>(),
+                self.len,
+            );
             "
         ));
 
-        // In all the push methods, we write a field from a variable called
-        // value.
         write_fields_from_value.push_str(&format!(
             "
-            cursor = align_up(
-                cursor, ::core::mem::align_of<{field_type_name}>());
             ::core::ptr::write(
-                self.ptr.byte_offset(cursor).cast(), value.{field_name});
-            cursor += self.cap * ::core::mem::size_of<{field_type_name}>();
+                self.field_ptr::<{ty}>({i}).add(self.len),
+                value.{field_name},
+            );
+            "
+        ));
+
+        read_fields.push_str(&format!(
+            "{field_name}: ::core::ptr::read(
+                self.field_ptr::<{ty}>({i}).add(index)),\n"
+        ));
+
+        swap_remove_fields.push_str(&format!(
+            "
+            ::core::ptr::copy(
+                self.field_ptr::<{ty}>({i}).add(last),
+                self.field_ptr::<{ty}>({i}).add(index),
+                1,
+            );
             "
         ));
 
-        // add the type to our list of types.
-        field_types.push(field_type_name);
+        drop_fields.push_str(&format!(
+            "
+            ::core::ptr::drop_in_place(::core::ptr::slice_from_raw_parts_mut(
+                self.field_ptr::<{ty}>({i}).add(len),
+                old_len - len,
+            ));
+            "
+        ));
     }
 
     format!(
         r#"
-        struct {struct_name}<A: ::core::alloc::Allocator = ::alloc::alloc::Global>  {{
+        /// A struct-of-arrays container of [`{name}`], generated by
+        /// `derive(Soa)`.
+        {vis} struct {struct_name}<
+            A: ::core::alloc::Allocator = ::std::alloc::Global,
+        > {{
             /// The length of each array in elements.
             len: usize,
             /// The total number of elements we have allocated space for.
             cap: usize,
             /// The pointer to the allocated memory.
             ptr: ::core::ptr::NonNull<u8>,
+            /// The byte offset of each field's array from `ptr`.
+            offsets: [usize; {num_fields}],
             alloc: A,
         }}
-        
-        impl {struct_name} {{
+
+        /// References to the fields of a single element of a
+        /// [`{struct_name}`].
+        {vis} struct {ref_name}<'a> {{
+            {ref_fields}
+        }}
+
+        /// Mutable references to the fields of a single element of a
+        /// [`{struct_name}`].
+        {vis} struct {ref_mut_name}<'a> {{
+            {ref_mut_fields}
+        }}
+
+        impl {struct_name}<::std::alloc::Global> {{
+            /// Returns a new empty [`{struct_name}`] using the global
+            /// allocator.
             #[inline]
-            pub fn new_in<A: ::core::alloc::Allocator>(alloc: A) -> Self<A> {{
-                Self {{
-                    len: 0,
-                    cap: 0,
-                    ptr: ::core::ptr::NonNull::<u8>::dangling(),
-                    alloc: alloc,
-                }}
+            pub const fn new() -> Self {{
+                Self::new_in(::std::alloc::Global)
             }}
+        }}
+
+        impl<A: ::core::alloc::Allocator> {struct_name}<A> {{
+            /// The alignment of the buffer, which is the largest alignment
+            /// of any field.
+            const ALIGN: usize = {{
+                let aligns = [{alignments}];
+                let mut max = 1;
+                let mut i = 0;
+                while i < {num_fields} {{
+                    if aligns[i] > max {{ max = aligns[i]; }}
+                    i += 1;
+                }}
+                max
+            }};
+
+            /// Whether every field is zero sized, in which case nothing ever
+            /// needs to be allocated.
+            const ZERO_SIZED: bool = match Self::offsets(1) {{
+                ::core::option::Option::Some((_, size)) => size == 0,
+                ::core::option::Option::None => false,
+            }};
 
+            /// Returns a new empty [`{struct_name}`] using the allocator
+            /// `alloc`.
             #[inline]
-            pub fn new() -> Self<::alloc::alloc::Global> {{
+            pub const fn new_in(alloc: A) -> Self {{
                 Self {{
                     len: 0,
-                    cap: 0,
-                    ptr: ::core::ptr::NonNull::<u8>::dangling(),
-                    alloc: ::alloc::alloc::Global,
+                    cap: if Self::ZERO_SIZED {{ usize::MAX }} else {{ 0 }},
+                    // SAFETY: ALIGN is never 0. A pointer aligned to ALIGN is
+                    //         aligned for every field, so empty slices can
+                    //         be made from it.
+                    ptr: unsafe {{
+                        ::core::ptr::NonNull::new_unchecked(
+                            ::core::ptr::without_provenance_mut(Self::ALIGN),
+                        )
+                    }},
+                    offsets: [0; {num_fields}],
+                    alloc,
                 }}
             }}
 
+            /// Returns the number of elements.
+            #[inline]
+            pub const fn len(&self) -> usize {{
+                self.len
+            }}
+
+            /// Returns true if there are no elements.
+            #[inline]
+            pub const fn is_empty(&self) -> bool {{
+                self.len == 0
+            }}
+
+            /// Returns the number of elements which can be held without
+            /// reallocating.
+            #[inline]
+            pub const fn capacity(&self) -> usize {{
+                self.cap
+            }}
+
+            /// Returns a reference to the underlying allocator.
+            #[inline]
+            pub const fn allocator(&self) -> &A {{
+                &self.alloc
+            }}
+
+            /// Returns the byte offset of each field's array, and the total
+            /// size of the buffer, for a buffer which can hold `cap`
+            /// elements.
+            const fn offsets(
+                cap: usize,
+            ) -> ::core::option::Option<([usize; {num_fields}], usize)> {{
+                let mut offsets = [0; {num_fields}];
+                let mut cursor: usize = 0;
+                {offset_calculation}
+                ::core::option::Option::Some((offsets, cursor))
+            }}
+
+            /// Returns a pointer to the start of the array of the field with
+            /// the given index.
+            ///
+            /// # Safety
+            ///
+            /// `T` must be the type of the field with the given index.
+            #[inline(always)]
+            unsafe fn field_ptr<T>(&self, field: usize) -> *mut T {{
+                self.ptr.as_ptr().add(self.offsets[field]).cast::<T>()
+            }}
+
             #[inline]
             fn try_reserve_impl(&mut self, additional: usize) ->
                     ::core::result::Result<(), ::core::alloc::Layout> {{
-                if self.len + additional < self.cap {{
+                let required = match self.len.checked_add(additional) {{
+                    ::core::option::Option::Some(x) => x,
+                    ::core::option::Option::None => {{
+                        return ::core::result::Result::Err(
+                            ::core::alloc::Layout::new::<()>());
+                    }}
+                }};
+                if required <= self.cap {{
                     return ::core::result::Result::Ok(());
                 }}
 
-                let new_cap = if self.cap == 0 {{ 16 }} else {{ self.cap*2 }}; 
-                let layout = ::core::alloc::Layout::from_size_align(
-                    new_cap*{single_element_size},
-                    self.buf_align(),
-                );
-                if self.cap == 0 {{
-                    if let Ok(ptr) = self.alloc.allocate(self.ptr, layout) {{
-                        self.resize(new_cap, ptr);
-                        self.ptr = ptr;
-                        return;
+                let new_cap = required.max(self.cap.saturating_mul(2)).max(4);
+                let (offsets, size) = match Self::offsets(new_cap) {{
+                    ::core::option::Option::Some(x) => x,
+                    ::core::option::Option::None => {{
+                        return ::core::result::Result::Err(
+                            ::core::alloc::Layout::new::<()>());
+                    }}
+                }};
+                let layout = match ::core::alloc::Layout::from_size_align(
+                    size,
+                    Self::ALIGN,
+                ) {{
+                    ::core::result::Result::Ok(l) => l,
+                    ::core::result::Result::Err(_) => {{
+                        return ::core::result::Result::Err(
+                            ::core::alloc::Layout::new::<()>());
+                    }}
+                }};
+
+                let new_ptr = match self.alloc.allocate(layout) {{
+                    ::core::result::Result::Ok(p) => p.cast::<u8>(),
+                    ::core::result::Result::Err(_) => {{
+                        return ::core::result::Result::Err(layout);
                     }}
+                }};
 
-                    return ::core::result::Result::Err(layout);
+                // SAFETY: the new buffer has room for new_cap > len elements
+                //         in each array, and doesn't overlap the old one.
+                unsafe {{
+                    {move_fields}
+                    self.deallocate();
                 }}
 
-                if let Ok(ptr) = self.alloc.grow(self.ptr, layout) {{
-                    self.resize(new_cap, ptr.cast());
-                    self.ptr = ptr.cast();
+                self.ptr = new_ptr;
+                self.offsets = offsets;
+                self.cap = new_cap;
+                ::core::result::Result::Ok(())
+            }}
+
+            /// Frees the buffer without dropping any elements.
+            ///
+            /// # Safety
+            ///
+            /// The buffer must not be used again after calling this.
+            #[inline]
+            unsafe fn deallocate(&mut self) {{
+                if Self::ZERO_SIZED || self.cap == 0 {{
                     return;
                 }}
 
-                return ::core::result::Result::Err(layout);
+                // SAFETY: this layout was already created successfully when
+                //         the buffer was allocated.
+                let (_, size) = Self::offsets(self.cap).unwrap_unchecked();
+                let layout = ::core::alloc::Layout::from_size_align_unchecked(
+                    size,
+                    Self::ALIGN,
+                );
+                self.alloc.deallocate(self.ptr, layout);
             }}
 
+            /// Tries to reserve room for at least `additional` more
+            /// elements.
+            ///
+            /// Returns an error if the capacity would overflow or if an
+            /// allocation failed.
             #[inline]
             pub fn try_reserve(&mut self, additional: usize) ->
                     ::core::result::Result<(), ::core::alloc::AllocError> {{
                 match self.try_reserve_impl(additional) {{
                     ::core::result::Result::Err(..) =>
                         ::core::result::Result::Err(::core::alloc::AllocError),
-                    _ => (),
+                    ::core::result::Result::Ok(()) =>
+                        ::core::result::Result::Ok(()),
                 }}
             }}
 
+            /// Reserves room for at least `additional` more elements.
+            ///
+            /// Calls `handle_alloc_error` if an allocation failed. Use
+            /// `try_reserve` to handle the error instead.
             #[inline]
             pub fn reserve(&mut self, additional: usize) {{
-                match self.try_reserve_impl(additional) {{
-                    ::core::result::Result::Err(layout) =>
-                        ::alloc::alloc::handle_alloc_error(layout),
-                    _ => (),
+                if let ::core::result::Result::Err(layout) =
+                    self.try_reserve_impl(additional)
+                {{
+                    ::std::alloc::handle_alloc_error(layout)
                 }}
             }}
 
+            /// Appends an element to the back.
+            ///
+            /// Returns an error if an allocation failed.
             #[inline]
             pub fn try_push(&mut self, value: {name}) ->
                     ::core::result::Result<(), ::core::alloc::AllocError> {{
-                const fn align_up(x: isize, a: isize) -> isize {{
-                    debug_assert!(a.is_power_of_two());
-                    (x + a - 1) & !(a - 1)
+                self.try_reserve(1)?;
+                // SAFETY: we just reserved room for one more element.
+                unsafe {{
+                    {write_fields_from_value}
                 }}
-                self.try_reserve(self.len + 1)?;
-                let mut cursor = 0;
-                {write_fields_from_value}
+                self.len += 1;
                 ::core::result::Result::Ok(())
             }}
 
+            /// Appends an element to the back.
+            ///
+            /// Calls `handle_alloc_error` if an allocation failed. Use
+            /// `try_push` to handle the error instead.
             #[inline]
             pub fn push(&mut self, value: {name}) {{
-                const fn align_up(x: isize, a: isize) -> isize {{
-                    debug_assert!(a.is_power_of_two());
-                    (x + a - 1) & !(a - 1)
+                self.reserve(1);
+                // SAFETY: we just reserved room for one more element.
+                unsafe {{
+                    {write_fields_from_value}
                 }}
-                self.reserve(self.len + 1);
+                self.len += 1;
+            }}
 
-                let mut cursor = 0;
-                {write_fields_from_value}
-                ::core::result::Result::Ok(())
+            /// Removes the last element and returns it, or `None` if there
+            /// are no elements.
+            #[inline]
+            pub fn pop(&mut self) -> ::core::option::Option<{name}> {{
+                if self.len == 0 {{
+                    return ::core::option::Option::None;
+                }}
+
+                self.len -= 1;
+                let index = self.len;
+                // SAFETY: the element at index is initialized, and is no
+                //         longer considered part of the arrays.
+                ::core::option::Option::Some(unsafe {{
+                    {name} {{
+                        {read_fields}
+                    }}
+                }})
             }}
 
+            /// Removes and returns the element at `index`, replacing it with
+            /// the last element.
+            ///
+            /// If the given `index` is out of bounds, returns `None`.
             #[inline]
-            pub fn len() -> usize {{
-                self.len
+            pub fn swap_remove(
+                &mut self,
+                index: usize,
+            ) -> ::core::option::Option<{name}> {{
+                if index >= self.len {{
+                    return ::core::option::Option::None;
+                }}
+
+                let last = self.len - 1;
+                // SAFETY: both index and last are in bounds. The value at
+                //         index is read out before last is moved into it.
+                let value = unsafe {{
+                    let value = {name} {{
+                        {read_fields}
+                    }};
+                    {swap_remove_fields}
+                    value
+                }};
+                self.len = last;
+                ::core::option::Option::Some(value)
             }}
 
+            /// Drops every element past the first `len` elements.
             #[inline]
-            pub fn capacity() -> usize {{
-                self.cap
+            pub fn truncate(&mut self, len: usize) {{
+                if len >= self.len {{
+                    return;
+                }}
+
+                // Like Vec, the length is updated first so that a panicking
+                // drop doesn't lead to a double drop.
+                let old_len = self.len;
+                self.len = len;
+                // SAFETY: the elements in len..old_len are initialized.
+                unsafe {{
+                    {drop_fields}
+                }}
             }}
 
-            /// resizes the contents of `self.ptr` to be arranged in the new
-            /// capacity given.
-            /// 
-            /// # Safety
-            ///
-            /// This function is `unsafe` because it performs raw pointer
-            /// arithmetic and byte-wise moves.  
-            /// 
-            /// The **caller must uphold **all** of the following
-            /// pre-conditions**:
-            ///
-            /// 1. `new_ptr` must be allocated using `self.alloc` to have
-            ///    enough memory to fit `new_cap` elements of each array.
-            /// 
-            /// 2. `self.ptr` and `new_ptr` are aligned to the smallest
-            ///    power-of-two which is greater than or equal to the maximum
-            ///    alignment of each type which has an array in this buffer.
-            /// 
-            /// 3. `self.len` is less than or equal to `self.cap`, and both of
-            ///    those values are strictly less than `new_cap`.
-            /// 
-            /// 4. The arrays in `self.ptr` are arranged like so:
-            /// 
-            ///    * Each array is laid out contiguously in the memory, in the
-            ///      order which they are declared in the source code.
-            /// 
-            ///    * Each array contains `self.len` elements in them.
-            ///
-            /// 5. No external alias may read or write any part of the buffer
-            ///    while this function is executing.
-            ///
-            /// Failure to satisfy **any** of these rules results in undefined
-            /// behaviour.
+            /// Drops every element, keeping the allocated memory.
+            #[inline]
+            pub fn clear(&mut self) {{
+                self.truncate(0)
+            }}
+
+            /// Returns references to the fields of the element at `index`,
+            /// or `None` if it's out of bounds.
             #[inline]
-            unsafe fn resize(&mut self, new_ptr: NonNull<u8>, new_cap: usize) {{
-                const fn align_up(x: isize, a: isize) -> isize {{
-                    debug_assert!(a.is_power_of_two());
-                    (x + a - 1) & !(a - 1)
+            pub fn get(
+                &self,
+                index: usize,
+            ) -> ::core::option::Option<{ref_name}<'_>> {{
+                if index >= self.len {{
+                    return ::core::option::Option::None;
                 }}
 
-                let mut cursor = 0;
-                let mut old_cursor = 0;
-                {resize_body}
+                // SAFETY: index is in bounds.
+                ::core::option::Option::Some(unsafe {{
+                    {ref_name} {{
+                        {get_fields}
+                    }}
+                }})
             }}
 
-            const fn buf_align(&self) -> usize {{
-                let aligns = [
-                    {alignments}
-                ];
-                let mut max = 1;
-                let mut i = 0;
-                while i < {num_fields} {{
-                    if aligns[i] > max {{ max = aligns[i]; }}
-                    i += 1;
+            /// Returns mutable references to the fields of the element at
+            /// `index`, or `None` if it's out of bounds.
+            #[inline]
+            pub fn get_mut(
+                &mut self,
+                index: usize,
+            ) -> ::core::option::Option<{ref_mut_name}<'_>> {{
+                if index >= self.len {{
+                    return ::core::option::Option::None;
                 }}
-                max
+
+                // SAFETY: index is in bounds, and each field is in a
+                //         different array so the references don't alias.
+                ::core::option::Option::Some(unsafe {{
+                    {ref_mut_name} {{
+                        {get_mut_fields}
+                    }}
+                }})
             }}
 
             {field_slice_methods}
         }}
+
+        impl ::core::default::Default for {struct_name}<::std::alloc::Global> {{
+            #[inline]
+            fn default() -> Self {{
+                Self::new()
+            }}
+        }}
+
+        impl<A: ::core::alloc::Allocator> ::core::ops::Drop
+                for {struct_name}<A> {{
+            fn drop(&mut self) {{
+                self.clear();
+                // SAFETY: the buffer isn't used after this.
+                unsafe {{ self.deallocate() }}
+            }}
+        }}
         "#,
     )
     .parse()
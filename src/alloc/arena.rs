@@ -0,0 +1,513 @@
+use core::{
+    alloc::{self, AllocError, Allocator, Layout},
+    cell::UnsafeCell,
+    mem,
+    ptr::{self, NonNull},
+    slice,
+};
+
+/// The size of the first chunk an [Arena] requests from its backing allocator
+/// if no capacity was given.
+const DEFAULT_CHUNK_SIZE: usize = 4096;
+
+/// An allocator which bumps a pointer within chunks of memory requested from
+/// some other [Allocator], requesting a new chunk twice the size of the
+/// previous one whenever it runs out of room.
+///
+/// Like the [FixedBufferAllocator](super::FixedBufferAllocator),
+/// [Allocator::grow], [Allocator::shrink] and [Allocator::deallocate] only
+/// reclaim memory when called with the last allocated pointer. Everything
+/// else is freed all at once by [Arena::reset] or when the arena goes out of
+/// scope.
+pub struct Arena<A: Allocator> {
+    alloc: A,
+    state: UnsafeCell<State>,
+}
+
+struct State {
+    /// The most recently allocated chunk, which is the one being allocated
+    /// from.
+    chunk: Option<NonNull<Chunk>>,
+    /// The free memory in the current chunk is between `begin` and `end`.
+    begin: *mut u8,
+    end: *mut u8,
+    /// The total size of every chunk, including their headers.
+    reserved: usize,
+    /// The number of bytes used in every chunk other than the current one.
+    used_before: usize,
+    /// The minimum size of the next chunk.
+    next_chunk_size: usize,
+}
+
+/// The header placed at the start of every chunk.
+struct Chunk {
+    prev: Option<NonNull<Chunk>>,
+    layout: Layout,
+}
+
+impl Chunk {
+    /// Returns a pointer to the memory following the header.
+    ///
+    /// # Safety
+    ///
+    /// `chunk` must point to a valid chunk.
+    #[inline(always)]
+    unsafe fn data(chunk: NonNull<Chunk>) -> *mut u8 {
+        chunk.as_ptr().add(1).cast()
+    }
+
+    /// Returns a pointer to one byte past the end of the chunk.
+    ///
+    /// # Safety
+    ///
+    /// `chunk` must point to a valid chunk.
+    #[inline(always)]
+    unsafe fn end(chunk: NonNull<Chunk>) -> *mut u8 {
+        chunk
+            .as_ptr()
+            .cast::<u8>()
+            .add((*chunk.as_ptr()).layout.size())
+    }
+}
+
+/// Returns the address `layout` would be placed at if it was allocated at
+/// `begin`, as well as the new value of `begin`, or [None] if it doesn't fit
+/// before `end`.
+#[inline(always)]
+fn bump(
+    begin: *mut u8,
+    end: *mut u8,
+    layout: Layout,
+) -> Option<(usize, usize)> {
+    let align = layout.align();
+    let aligned = begin.addr().checked_add(align - 1)? & !(align - 1);
+    let new_begin = aligned.checked_add(layout.size())?;
+    if new_begin > end.addr() {
+        return None;
+    }
+    Some((aligned, new_begin))
+}
+
+impl<A: Allocator> Arena<A> {
+    /// Creates a new empty [Arena] which requests its chunks from `alloc`.
+    ///
+    /// No memory is allocated until the first allocation is made.
+    pub const fn new(alloc: A) -> Arena<A> {
+        Arena::with_chunk_size(DEFAULT_CHUNK_SIZE, alloc)
+    }
+
+    /// Creates a new [Arena] whose first chunk can hold `capacity` bytes.
+    pub fn with_capacity(
+        capacity: usize,
+        alloc: A,
+    ) -> Result<Arena<A>, AllocError> {
+        let arena = Arena::with_chunk_size(capacity, alloc);
+        // SAFETY: nothing else can access the state yet.
+        let state = unsafe { &mut *arena.state.get() };
+        arena.add_chunk(state, Layout::new::<u8>())?;
+        Ok(arena)
+    }
+
+    const fn with_chunk_size(size: usize, alloc: A) -> Arena<A> {
+        Arena {
+            alloc,
+            state: UnsafeCell::new(State {
+                chunk: None,
+                begin: ptr::null_mut(),
+                end: ptr::null_mut(),
+                reserved: 0,
+                used_before: 0,
+                next_chunk_size: size,
+            }),
+        }
+    }
+
+    /// Returns the total number of bytes requested from the backing
+    /// allocator.
+    #[inline(always)]
+    pub fn reserved(&self) -> usize {
+        unsafe { (*self.state.get()).reserved }
+    }
+
+    /// Returns the number of bytes which have been allocated from the arena,
+    /// including any padding needed for alignment.
+    pub fn used(&self) -> usize {
+        let state = unsafe { &*self.state.get() };
+        match state.chunk {
+            Some(chunk) => {
+                let data = unsafe { Chunk::data(chunk) };
+                state.used_before + (state.begin.addr() - data.addr())
+            }
+            None => 0,
+        }
+    }
+
+    /// Frees everything allocated from the arena. The largest chunk is kept
+    /// to be allocated from again, and every other chunk is returned to the
+    /// backing allocator.
+    pub fn reset(&mut self) {
+        let state = self.state.get_mut();
+        let mut largest: Option<NonNull<Chunk>> = None;
+        let mut chunk = state.chunk;
+        while let Some(c) = chunk {
+            // SAFETY: every chunk in the list is valid until we deallocate
+            //         it.
+            unsafe {
+                chunk = (*c.as_ptr()).prev;
+                match largest {
+                    Some(l)
+                        if (*l.as_ptr()).layout.size()
+                            >= (*c.as_ptr()).layout.size() =>
+                    {
+                        self.alloc.deallocate(c.cast(), (*c.as_ptr()).layout);
+                    }
+                    Some(l) => {
+                        self.alloc.deallocate(l.cast(), (*l.as_ptr()).layout);
+                        largest = Some(c);
+                    }
+                    None => largest = Some(c),
+                }
+            }
+        }
+
+        state.chunk = largest;
+        state.used_before = 0;
+        match largest {
+            Some(c) => unsafe {
+                (*c.as_ptr()).prev = None;
+                state.begin = Chunk::data(c);
+                state.end = Chunk::end(c);
+                state.reserved = (*c.as_ptr()).layout.size();
+            },
+            None => {
+                state.begin = ptr::null_mut();
+                state.end = ptr::null_mut();
+                state.reserved = 0;
+            }
+        }
+    }
+
+    /// Requests a new chunk from the backing allocator which is large enough
+    /// to fit `layout`.
+    fn add_chunk(
+        &self,
+        state: &mut State,
+        layout: Layout,
+    ) -> Result<(), AllocError> {
+        let header = mem::size_of::<Chunk>();
+        let needed = layout
+            .size()
+            .checked_add(layout.align() - 1)
+            .and_then(|x| x.checked_add(header))
+            .ok_or(AllocError)?;
+        let size = needed.max(state.next_chunk_size);
+        let chunk_layout =
+            Layout::from_size_align(size, mem::align_of::<Chunk>())
+                .map_err(|_| AllocError)?;
+
+        let mem = self.alloc.allocate(chunk_layout)?;
+        // The allocator may have given us more than we asked for.
+        let chunk_layout =
+            Layout::from_size_align(mem.len(), mem::align_of::<Chunk>())
+                .map_err(|_| AllocError)?;
+        let chunk = mem.cast::<Chunk>();
+
+        // SAFETY: the memory was just allocated with room for the header.
+        unsafe {
+            chunk.as_ptr().write(Chunk {
+                prev: state.chunk,
+                layout: chunk_layout,
+            });
+            if let Some(prev) = state.chunk {
+                state.used_before +=
+                    state.begin.addr() - Chunk::data(prev).addr();
+            }
+            state.begin = Chunk::data(chunk);
+            state.end = Chunk::end(chunk);
+        }
+        state.chunk = Some(chunk);
+        state.reserved += chunk_layout.size();
+        state.next_chunk_size = size.saturating_mul(2);
+        Ok(())
+    }
+}
+
+impl<A: Allocator> Drop for Arena<A> {
+    fn drop(&mut self) {
+        let mut chunk = self.state.get_mut().chunk;
+        while let Some(c) = chunk {
+            // SAFETY: every chunk in the list was allocated by self.alloc
+            //         with the layout stored in its header.
+            unsafe {
+                let Chunk { prev, layout } = c.as_ptr().read();
+                self.alloc.deallocate(c.cast(), layout);
+                chunk = prev;
+            }
+        }
+    }
+}
+
+unsafe impl<A: Allocator> Allocator for Arena<A> {
+    fn allocate(
+        &self,
+        layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        // SAFETY: the arena is !Sync and nothing holds a reference to the
+        //         state across calls.
+        let state = unsafe { &mut *self.state.get() };
+
+        let (addr, new_begin) = match state.chunk {
+            Some(_) => bump(state.begin, state.end, layout),
+            None => None,
+        }
+        .map_or_else(
+            || {
+                self.add_chunk(state, layout)?;
+                bump(state.begin, state.end, layout).ok_or(AllocError)
+            },
+            Ok,
+        )?;
+
+        state.begin = state.begin.with_addr(new_begin);
+        // SAFETY: bump only returns addresses within the current chunk, which
+        //         is never null.
+        unsafe {
+            Ok(NonNull::new_unchecked(slice::from_raw_parts_mut(
+                state.begin.with_addr(addr),
+                layout.size(),
+            )))
+        }
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: alloc::Layout) {
+        let state = &mut *self.state.get();
+        if ptr::eq(ptr.as_ptr().add(layout.size()), state.begin) {
+            // We can only deallocate if it was the last thing we allocated.
+            state.begin = ptr.as_ptr();
+        }
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: alloc::Layout,
+        new_layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let state = &mut *self.state.get();
+        let is_last =
+            ptr::eq(ptr.as_ptr().add(old_layout.size()), state.begin);
+        let aligned = ptr.as_ptr().addr() & (new_layout.align() - 1) == 0;
+        // Only the last allocation is known to be in the current chunk, so
+        // the space after anything else can't be measured against its end.
+        if is_last
+            && aligned
+            && state.end.addr() - ptr.as_ptr().addr() >= new_layout.size()
+        {
+            state.begin = ptr.as_ptr().add(new_layout.size());
+            return Ok(NonNull::new_unchecked(slice::from_raw_parts_mut(
+                ptr.as_ptr(),
+                new_layout.size(),
+            )));
+        }
+
+        let new_ptr = self.allocate(new_layout)?;
+        ptr::copy_nonoverlapping(
+            ptr.as_ptr(),
+            new_ptr.as_ptr() as *mut u8,
+            old_layout.size(),
+        );
+        self.deallocate(ptr, old_layout);
+        Ok(new_ptr)
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: alloc::Layout,
+        new_layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if ptr.as_ptr().addr() & (new_layout.align() - 1) != 0 {
+            let new_ptr = self.allocate(new_layout)?;
+            ptr::copy_nonoverlapping(
+                ptr.as_ptr(),
+                new_ptr.as_ptr() as *mut u8,
+                new_layout.size(),
+            );
+            self.deallocate(ptr, old_layout);
+            return Ok(new_ptr);
+        }
+
+        let state = &mut *self.state.get();
+        if ptr::eq(ptr.as_ptr().add(old_layout.size()), state.begin) {
+            state.begin = ptr.as_ptr().add(new_layout.size());
+        }
+        Ok(NonNull::new_unchecked(slice::from_raw_parts_mut(
+            ptr.as_ptr(),
+            new_layout.size(),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc::{FixedBufferAllocator, Malloc};
+    use core::cell::Cell;
+
+    /// Hands out memory from the end of a buffer towards its start, so every
+    /// chunk an [Arena] requests is at a lower address than the last one.
+    struct Downward {
+        base: *mut u8,
+        end: Cell<usize>,
+    }
+
+    impl Downward {
+        fn new(buffer: &mut [u8]) -> Downward {
+            Downward {
+                base: buffer.as_mut_ptr(),
+                end: Cell::new(buffer.as_ptr().addr() + buffer.len()),
+            }
+        }
+    }
+
+    unsafe impl Allocator for Downward {
+        fn allocate(
+            &self,
+            layout: Layout,
+        ) -> Result<NonNull<[u8]>, AllocError> {
+            let begin = self
+                .end
+                .get()
+                .checked_sub(layout.size())
+                .ok_or(AllocError)?
+                & !(layout.align() - 1);
+            if begin < self.base.addr() {
+                return Err(AllocError);
+            }
+            self.end.set(begin);
+            let ptr = self.base.with_addr(begin);
+            Ok(NonNull::slice_from_raw_parts(
+                unsafe { NonNull::new_unchecked(ptr) },
+                layout.size(),
+            ))
+        }
+
+        unsafe fn deallocate(&self, _: NonNull<u8>, _: Layout) {}
+    }
+
+    fn layout(size: usize, align: usize) -> alloc::Layout {
+        alloc::Layout::from_size_align(size, align).unwrap()
+    }
+
+    fn pointer_is_aligned_to<T: ?Sized>(ptr: *const T, align: usize) -> bool {
+        ptr.addr() & (align - 1) == 0
+    }
+
+    #[test]
+    fn arena_alloc_works() {
+        let arena = Arena::new(Malloc);
+        assert_eq!(arena.reserved(), 0);
+        assert_eq!(arena.used(), 0);
+
+        let result = arena.allocate(layout(16, 8)).unwrap();
+        assert_eq!(result.len(), 16);
+        assert!(pointer_is_aligned_to(result.as_ptr(), 8));
+        assert_eq!(arena.used(), 16);
+        assert!(arena.reserved() >= DEFAULT_CHUNK_SIZE);
+    }
+
+    #[test]
+    fn arena_grows_geometrically() {
+        let arena = Arena::new(Malloc);
+        for _ in 0..DEFAULT_CHUNK_SIZE / 64 + 1 {
+            assert!(arena.allocate(layout(64, 1)).is_ok());
+        }
+        assert!(arena.reserved() >= DEFAULT_CHUNK_SIZE * 3);
+        assert_eq!(arena.used(), DEFAULT_CHUNK_SIZE + 64);
+    }
+
+    #[test]
+    fn arena_large_allocation_gets_own_chunk() {
+        let arena = Arena::new(Malloc);
+        let result = arena.allocate(layout(DEFAULT_CHUNK_SIZE * 4, 64));
+        assert!(result.is_ok());
+        assert!(pointer_is_aligned_to(result.unwrap().as_ptr(), 64));
+        assert!(arena.reserved() >= DEFAULT_CHUNK_SIZE * 4);
+    }
+
+    #[test]
+    fn arena_reset_keeps_largest_chunk() {
+        let mut arena = Arena::new(Malloc);
+        assert!(arena.allocate(layout(16, 1)).is_ok());
+        assert!(arena.allocate(layout(DEFAULT_CHUNK_SIZE * 4, 1)).is_ok());
+        assert!(arena.allocate(layout(DEFAULT_CHUNK_SIZE * 8, 1)).is_ok());
+        let largest = arena.reserved() - DEFAULT_CHUNK_SIZE;
+
+        arena.reset();
+        assert_eq!(arena.used(), 0);
+        assert!(arena.reserved() <= largest);
+        assert!(arena.reserved() >= DEFAULT_CHUNK_SIZE * 8);
+
+        let before = arena.reserved();
+        assert!(arena.allocate(layout(DEFAULT_CHUNK_SIZE * 8, 1)).is_ok());
+        assert_eq!(arena.reserved(), before);
+    }
+
+    #[test]
+    fn arena_grow_in_place() {
+        let arena = Arena::new(Malloc);
+        let a = arena.allocate(layout(8, 8)).unwrap();
+        let b = unsafe { arena.grow(a.cast(), layout(8, 8), layout(32, 8)) }
+            .unwrap();
+        assert_eq!(a.cast::<u8>(), b.cast::<u8>());
+        assert_eq!(arena.used(), 32);
+    }
+
+    #[test]
+    fn arena_grow_moves_when_not_last() {
+        let arena = Arena::new(Malloc);
+        let a = arena.allocate(layout(8, 8)).unwrap();
+        unsafe { a.cast::<u64>().as_ptr().write(42) };
+        assert!(arena.allocate(layout(8, 8)).is_ok());
+        let b = unsafe { arena.grow(a.cast(), layout(8, 8), layout(32, 8)) }
+            .unwrap();
+        assert_ne!(a.cast::<u8>(), b.cast::<u8>());
+        assert_eq!(unsafe { b.cast::<u64>().as_ptr().read() }, 42);
+    }
+
+    #[test]
+    fn arena_grow_from_chunk_above_current() {
+        let mut buffer = vec![0u8; 1 << 16];
+        let backing = Downward::new(&mut buffer);
+        let arena = Arena::with_capacity(256, &backing).unwrap();
+        let a = arena.allocate(layout(64, 8)).unwrap();
+        unsafe { a.cast::<u64>().as_ptr().write(42) };
+        // This doesn't fit in the first chunk, so it gets a new one below it.
+        let b = arena.allocate(layout(4096, 8)).unwrap();
+        assert!(b.cast::<u8>() < a.cast::<u8>());
+
+        let a = unsafe { arena.grow(a.cast(), layout(64, 8), layout(128, 8)) }
+            .unwrap();
+        assert_eq!(unsafe { a.cast::<u64>().as_ptr().read() }, 42);
+    }
+
+    #[test]
+    fn arena_dealloc_last_works() {
+        let arena = Arena::new(Malloc);
+        let a = arena.allocate(layout(8, 1)).unwrap();
+        let b = arena.allocate(layout(8, 1)).unwrap();
+        unsafe { arena.deallocate(a.cast(), layout(8, 1)) };
+        assert_eq!(arena.used(), 16);
+        unsafe { arena.deallocate(b.cast(), layout(8, 1)) };
+        assert_eq!(arena.used(), 8);
+    }
+
+    #[test]
+    fn arena_alloc_err_when_backing_allocator_full() {
+        let mut buffer = [0u8; 64];
+        let fba = FixedBufferAllocator::from_slice(&mut buffer);
+        let arena = Arena::new(&fba);
+        assert!(arena.allocate(layout(1, 1)).is_err());
+        assert_eq!(arena.reserved(), 0);
+    }
+}
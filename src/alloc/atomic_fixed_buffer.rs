@@ -0,0 +1,303 @@
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    marker::PhantomData,
+    ptr::{self, NonNull},
+    slice,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+use super::Owns;
+
+/// A [FixedBufferAllocator](super::FixedBufferAllocator) which can be shared
+/// between threads.
+///
+/// The cursor is bumped with a compare and exchange instead of being kept in
+/// an [UnsafeCell](core::cell::UnsafeCell), so any number of threads can
+/// allocate from the same buffer at once without locking. Like the
+/// [FixedBufferAllocator](super::FixedBufferAllocator),
+/// [Allocator::grow], [Allocator::shrink] and [Allocator::deallocate] are a
+/// no-op unless called with the last allocated pointer, which with several
+/// threads allocating means the last pointer allocated by any of them.
+pub struct AtomicFixedBufferAllocator<'a> {
+    begin: AtomicPtr<u8>,
+    /// Where `begin` pointed when the [AtomicFixedBufferAllocator] was
+    /// created.
+    start: *mut u8,
+    /// `end` points to one byte past the end of the buffer.
+    end: *const u8,
+    _marker: PhantomData<&'a u8>,
+}
+
+unsafe impl Send for AtomicFixedBufferAllocator<'_> {}
+unsafe impl Sync for AtomicFixedBufferAllocator<'_> {}
+
+impl<'a> AtomicFixedBufferAllocator<'a> {
+    /// Constructs an [AtomicFixedBufferAllocator] given a pointer to the
+    /// beginning of the memory range to allocate from and its length.
+    ///
+    /// # Safety
+    ///
+    /// Behavior is undefined if any of the following conditions are violated:
+    ///
+    /// - `begin` must not be `null`.
+    /// - `begin` must point to `len` bytes of readable and writable memory.
+    /// - The memory referenced by `begin` must not be accessed through any
+    ///   other pointer for the duration of the lifetime `'a`. Both read and
+    ///   write accesses are forbidden.
+    pub unsafe fn from_raw_parts(
+        begin: *mut u8,
+        len: usize,
+    ) -> AtomicFixedBufferAllocator<'a> {
+        let slice = slice::from_raw_parts_mut(begin, len);
+        AtomicFixedBufferAllocator::from_slice(slice)
+    }
+
+    /// Creates an [AtomicFixedBufferAllocator] using the given slice as its
+    /// backing memory.
+    pub fn from_slice(mem: &'a mut [u8]) -> AtomicFixedBufferAllocator<'a> {
+        AtomicFixedBufferAllocator {
+            begin: AtomicPtr::new(mem.as_mut_ptr()),
+            start: mem.as_mut_ptr(),
+            end: unsafe { mem.as_ptr().add(mem.len()) },
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the number of bytes which have been allocated from the
+    /// buffer, including any padding needed for alignment.
+    #[inline(always)]
+    pub fn used(&self) -> usize {
+        self.begin.load(Ordering::Relaxed).addr() - self.start.addr()
+    }
+
+    /// Returns the number of bytes left in the buffer.
+    #[inline(always)]
+    pub fn remaining(&self) -> usize {
+        self.end.addr() - self.begin.load(Ordering::Relaxed).addr()
+    }
+
+    /// Frees everything allocated from the buffer.
+    #[inline(always)]
+    pub fn reset(&mut self) {
+        *self.begin.get_mut() = self.start;
+    }
+
+    /// Moves the cursor from the end of the allocation at `ptr` with
+    /// `old_size` bytes to `new_size` bytes past `ptr`, if it was the last
+    /// thing allocated.
+    #[inline(always)]
+    fn resize_last(
+        &self,
+        ptr: NonNull<u8>,
+        old_size: usize,
+        new_size: usize,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let ptr = ptr.as_ptr();
+        if self.end.addr() - ptr.addr() < new_size {
+            return Err(AllocError);
+        }
+
+        // SAFETY: ptr was allocated from the buffer with old_size bytes, and
+        //         we just checked that new_size bytes fit.
+        let (old_end, new_end) =
+            unsafe { (ptr.add(old_size), ptr.add(new_size)) };
+        self.begin
+            .compare_exchange(
+                old_end,
+                new_end,
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            )
+            // We can only resize if it was the last thing we allocated.
+            .map_err(|_| AllocError)?;
+        Ok(unsafe {
+            NonNull::new_unchecked(slice::from_raw_parts_mut(ptr, new_size))
+        })
+    }
+
+    /// Moves an allocation to a new one with a different alignment.
+    unsafe fn realign(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let new_ptr = self.allocate(new_layout)?;
+        ptr::copy_nonoverlapping(
+            ptr.as_ptr(),
+            new_ptr.as_ptr() as *mut u8,
+            old_layout.size().min(new_layout.size()),
+        );
+        self.deallocate(ptr, old_layout);
+        Ok(new_ptr)
+    }
+}
+
+unsafe impl Allocator for AtomicFixedBufferAllocator<'_> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let size = layout.size();
+        let align = layout.align();
+        let mut begin = self.begin.load(Ordering::Acquire);
+        loop {
+            // SAFETY: We know that this won't overflow since Layout says that
+            //         the size (after being aligned) will not exceed
+            //         isize::MAX.
+            let begin_aligned = (begin.addr() + (align - 1)) & !(align - 1);
+            let new_begin = begin.with_addr(begin_aligned + size);
+            if self.end.addr().checked_sub(new_begin.addr()).is_none() {
+                return Err(AllocError);
+            }
+
+            match self.begin.compare_exchange_weak(
+                begin,
+                new_begin,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => unsafe {
+                    return Ok(NonNull::new_unchecked(
+                        slice::from_raw_parts_mut(
+                            begin.with_addr(begin_aligned),
+                            size,
+                        ),
+                    ));
+                },
+                Err(current) => begin = current,
+            }
+        }
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        // We can only deallocate if it was the last thing we allocated, and
+        // if it wasn't, there's nothing to do.
+        let _ = self.begin.compare_exchange(
+            ptr.as_ptr().add(layout.size()),
+            ptr.as_ptr(),
+            Ordering::AcqRel,
+            Ordering::Relaxed,
+        );
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if old_layout.align() != new_layout.align() {
+            return self.realign(ptr, old_layout, new_layout);
+        }
+        self.resize_last(ptr, old_layout.size(), new_layout.size())
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if old_layout.align() != new_layout.align() {
+            return self.realign(ptr, old_layout, new_layout);
+        }
+        self.resize_last(ptr, old_layout.size(), new_layout.size())
+    }
+}
+
+impl Owns for AtomicFixedBufferAllocator<'_> {
+    #[inline(always)]
+    fn owns(&self, ptr: NonNull<u8>, layout: Layout) -> bool {
+        let addr = ptr.as_ptr().addr();
+        addr >= self.start.addr()
+            && addr
+                .checked_add(layout.size())
+                .is_some_and(|end| end <= self.end.addr())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::array::Array;
+    use std::thread;
+
+    fn layout(size: usize, align: usize) -> Layout {
+        Layout::from_size_align(size, align).unwrap()
+    }
+
+    #[test]
+    fn atomic_fixed_buffer_resizes_last_allocation() {
+        let mut buffer = [0; 64];
+        let alloc = AtomicFixedBufferAllocator::from_slice(&mut buffer);
+        let a = alloc.allocate(layout(8, 8)).unwrap();
+        let b = alloc.allocate(layout(8, 8)).unwrap();
+        assert_eq!(alloc.used(), 16);
+
+        // a isn't the last allocation, so it can't be resized or freed.
+        assert!(unsafe { alloc.grow(a.cast(), layout(8, 8), layout(16, 8)) }
+            .is_err());
+        unsafe { alloc.deallocate(a.cast(), layout(8, 8)) };
+        assert_eq!(alloc.used(), 16);
+
+        let b = unsafe { alloc.grow(b.cast(), layout(8, 8), layout(40, 8)) }
+            .unwrap();
+        assert_eq!(alloc.used(), 48);
+        assert!(
+            unsafe { alloc.grow(b.cast(), layout(40, 8), layout(64, 8)) }
+                .is_err()
+        );
+        let b = unsafe { alloc.shrink(b.cast(), layout(40, 8), layout(4, 8)) }
+            .unwrap();
+        assert_eq!(alloc.used(), 12);
+        unsafe { alloc.deallocate(b.cast(), layout(4, 8)) };
+        assert_eq!(alloc.used(), 8);
+        assert_eq!(alloc.remaining(), 56);
+    }
+
+    #[test]
+    fn atomic_fixed_buffer_works_with_arrays() {
+        let mut buffer = [0; 1024];
+        let alloc = AtomicFixedBufferAllocator::from_slice(&mut buffer);
+        let mut array = Array::new(&alloc);
+        array.try_extend(0..100u32).unwrap();
+        assert_eq!(array[99], 99);
+    }
+
+    /// Many threads allocating at once must never get overlapping memory,
+    /// which they'd notice as another thread's bytes in their allocations.
+    #[test]
+    fn atomic_fixed_buffer_stress() {
+        const THREADS: usize = 8;
+        const ALLOCATIONS: usize = 2000;
+        let mut buffer = vec![0u8; THREADS * ALLOCATIONS * 64];
+        let alloc = AtomicFixedBufferAllocator::from_slice(&mut buffer);
+
+        thread::scope(|s| {
+            for t in 0..THREADS {
+                let alloc = &alloc;
+                s.spawn(move || {
+                    let tag = t as u8 + 1;
+                    let mut kept = Vec::new();
+                    for i in 0..ALLOCATIONS {
+                        let l = layout(i % 48 + 1, 1 << (i % 4));
+                        let a = alloc.allocate(l).unwrap();
+                        assert_eq!(a.cast::<u8>().addr().get() % l.align(), 0);
+                        unsafe { a.cast::<u8>().write_bytes(tag, l.size()) };
+                        if i % 3 == 0 {
+                            // Frees only work if nothing else was allocated
+                            // since, which races with the other threads.
+                            unsafe { alloc.deallocate(a.cast(), l) };
+                        } else {
+                            kept.push((a, l));
+                        }
+                        thread::yield_now();
+                    }
+                    for (a, l) in kept {
+                        let bytes = unsafe { &a.as_ref()[..l.size()] };
+                        assert!(bytes.iter().all(|&x| x == tag));
+                    }
+                });
+            }
+        });
+        assert!(alloc.used() <= THREADS * ALLOCATIONS * 64);
+    }
+}
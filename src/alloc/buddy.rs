@@ -0,0 +1,551 @@
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    cell::UnsafeCell,
+    marker::PhantomData,
+    ptr::{self, NonNull},
+    slice,
+};
+
+/// The size of the smallest block, which is large enough to hold a
+/// [FreeBlock].
+const MIN_BLOCK_SHIFT: u32 = 5;
+const MIN_BLOCK: usize = 1 << MIN_BLOCK_SHIFT;
+/// The number of block sizes, enough for a block to cover the whole address
+/// space.
+const ORDERS: usize = (usize::BITS - MIN_BLOCK_SHIFT) as usize;
+
+/// A binary buddy allocator which manages a fixed buffer of memory.
+///
+/// The buffer is split into blocks whose sizes are powers of two. Allocations
+/// are rounded up to the nearest block size, and larger blocks are split in
+/// half until there's one of the right size. When a block is freed, it's
+/// merged with its buddy, the other half of the block it was split from, as
+/// long as the buddy is free too. Allocating and freeing both take
+/// `O(log n)` time, and unlike the
+/// [FixedBufferAllocator](super::FixedBufferAllocator), memory can be freed
+/// in any order.
+///
+/// A small part of the end of the buffer is used to keep track of which
+/// blocks are free. Memory from a
+/// [VirtualMemoryAllocator](super::VirtualMemoryAllocator) can be managed by
+/// allocating a large range from it and passing it to
+/// [Buddy::from_raw_parts].
+pub struct Buddy<'a> {
+    /// The start of the blocks, aligned to [MIN_BLOCK].
+    base: *mut u8,
+    /// The number of bytes of blocks after `base`, which is a multiple of
+    /// [MIN_BLOCK].
+    len: usize,
+    state: UnsafeCell<State>,
+    _marker: PhantomData<&'a mut [u8]>,
+}
+
+struct State {
+    /// `free[k]` is the list of free blocks of `MIN_BLOCK << k` bytes.
+    free: [Option<NonNull<FreeBlock>>; ORDERS],
+    /// One bit for every [MIN_BLOCK] bytes after `base`, set if a free block
+    /// starts there.
+    bitmap: *mut u8,
+    /// The number of bytes in blocks which have been allocated.
+    used: usize,
+}
+
+/// The header written to the start of every free block.
+struct FreeBlock {
+    next: Option<NonNull<FreeBlock>>,
+    prev: Option<NonNull<FreeBlock>>,
+    order: usize,
+}
+
+/// Returns the size of the blocks of order `k`.
+#[inline(always)]
+const fn block_size(k: usize) -> usize {
+    MIN_BLOCK << k
+}
+
+/// Returns the order of the smallest block which can hold `layout`, or [None]
+/// if it's too large for any block.
+#[inline(always)]
+fn order_of(layout: Layout) -> Option<usize> {
+    let size = layout.size().max(layout.align()).max(MIN_BLOCK);
+    let size = size.checked_next_power_of_two()?;
+    Some((size.trailing_zeros() - MIN_BLOCK_SHIFT) as usize)
+}
+
+unsafe impl Send for Buddy<'_> {}
+
+impl<'a> Buddy<'a> {
+    /// Constructs a [Buddy] allocator managing `len` bytes starting at
+    /// `begin`.
+    ///
+    /// # Safety
+    ///
+    /// Behavior is undefined if any of the following conditions are violated:
+    ///
+    /// - `begin` must not be `null`.
+    /// - `begin` must point to `len` bytes of readable and writable memory.
+    /// - The memory referenced by `begin` must not be accessed through any
+    ///   other pointer for the duration of the lifetime `'a`. Both read and
+    ///   write accesses are forbidden.
+    pub unsafe fn from_raw_parts(begin: *mut u8, len: usize) -> Buddy<'a> {
+        let slice = slice::from_raw_parts_mut(begin, len);
+        Buddy::from_slice(slice)
+    }
+
+    /// Creates a [Buddy] allocator using the given slice as its backing
+    /// memory.
+    pub fn from_slice(mem: &'a mut [u8]) -> Buddy<'a> {
+        let offset = mem.as_ptr().align_offset(MIN_BLOCK).min(mem.len());
+        let total = mem.len() - offset;
+        // Every MIN_BLOCK bytes of blocks need one bit of the bitmap.
+        let units = (total as u128 * 8 / (MIN_BLOCK as u128 * 8 + 1)) as usize;
+        let len = units * MIN_BLOCK;
+
+        // SAFETY: offset + len + units.div_ceil(8) <= mem.len().
+        let base = unsafe { mem.as_mut_ptr().add(offset) };
+        let bitmap = unsafe { base.add(len) };
+        unsafe { bitmap.write_bytes(0, units.div_ceil(8)) };
+
+        let buddy = Buddy {
+            base,
+            len,
+            state: UnsafeCell::new(State {
+                free: [None; ORDERS],
+                bitmap,
+                used: 0,
+            }),
+            _marker: PhantomData,
+        };
+
+        // Carve the memory into the largest blocks which fit, each aligned
+        // to its own size. Their buddies would be past the end, so they're
+        // never merged.
+        let state = unsafe { &mut *buddy.state.get() };
+        let mut start = 0;
+        while start < len {
+            let mut k =
+                (len - start).ilog2() as usize - MIN_BLOCK_SHIFT as usize;
+            if start != 0 {
+                k = k.min(
+                    start.trailing_zeros() as usize - MIN_BLOCK_SHIFT as usize,
+                );
+            }
+            unsafe { buddy.push(state, start, k) };
+            start += block_size(k);
+        }
+        buddy
+    }
+
+    /// Returns the number of bytes which can be allocated in total, not
+    /// counting the memory used to keep track of free blocks.
+    #[inline(always)]
+    pub fn capacity(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the number of bytes in blocks which are currently allocated,
+    /// which includes the rounding up of allocations to the block sizes.
+    #[inline(always)]
+    pub fn used(&self) -> usize {
+        unsafe { (*self.state.get()).used }
+    }
+
+    /// Checks that the allocator's internal state is consistent, panicking
+    /// with a description of the problem if it isn't.
+    ///
+    /// This walks every free block, so it's slow, but it's useful in tests of
+    /// code using the allocator to catch memory corruption early.
+    pub fn check_invariants(&self) {
+        let state = unsafe { &*self.state.get() };
+        let mut blocks = Vec::new();
+        for (k, head) in state.free.iter().enumerate() {
+            let mut prev = None;
+            let mut block = *head;
+            while let Some(b) = block {
+                let offset = b.as_ptr().addr() - self.base.addr();
+                let header = unsafe { b.as_ref() };
+                assert!(
+                    offset % block_size(k) == 0
+                        && offset + block_size(k) <= self.len,
+                    "free block at {offset} of order {k} is out of place"
+                );
+                assert_eq!(header.order, k, "free block at {offset}");
+                assert_eq!(header.prev, prev, "free block at {offset}");
+                assert!(
+                    unsafe { self.is_free(state, offset) },
+                    "free block at {offset} isn't marked as free"
+                );
+                let buddy = offset ^ block_size(k);
+                assert!(
+                    unsafe { !self.is_free_block(state, buddy, k) },
+                    "free block at {offset} of order {k} wasn't merged"
+                );
+                blocks.push((offset, block_size(k)));
+                prev = block;
+                block = header.next;
+            }
+        }
+
+        let marked = (0..self.len / MIN_BLOCK)
+            .filter(|x| unsafe { self.is_free(state, x * MIN_BLOCK) })
+            .count();
+        assert_eq!(marked, blocks.len(), "blocks marked as free");
+
+        blocks.sort();
+        for pair in blocks.windows(2) {
+            assert!(
+                pair[0].0 + pair[0].1 <= pair[1].0,
+                "free blocks at {} and {} overlap",
+                pair[0].0,
+                pair[1].0
+            );
+        }
+        let free = blocks.iter().map(|x| x.1).sum::<usize>();
+        assert_eq!(free + state.used, self.len, "free and used bytes");
+    }
+
+    /// Returns whether a free block starts `offset` bytes after `base`.
+    #[inline(always)]
+    unsafe fn is_free(&self, state: &State, offset: usize) -> bool {
+        let unit = offset / MIN_BLOCK;
+        *state.bitmap.add(unit / 8) & (1 << (unit % 8)) != 0
+    }
+
+    #[inline(always)]
+    unsafe fn set_free(&self, state: &mut State, offset: usize, free: bool) {
+        let unit = offset / MIN_BLOCK;
+        let byte = state.bitmap.add(unit / 8);
+        if free {
+            *byte |= 1 << (unit % 8);
+        } else {
+            *byte &= !(1 << (unit % 8));
+        }
+    }
+
+    /// Returns whether the block of order `k` at `offset` exists and is
+    /// free, meaning it can be merged with its buddy or grown into.
+    #[inline(always)]
+    unsafe fn is_free_block(
+        &self,
+        state: &State,
+        offset: usize,
+        k: usize,
+    ) -> bool {
+        offset + block_size(k) <= self.len
+            && self.is_free(state, offset)
+            && (*self.base.add(offset).cast::<FreeBlock>()).order == k
+    }
+
+    /// Adds the block of order `k` at `offset` to its free list.
+    unsafe fn push(&self, state: &mut State, offset: usize, k: usize) {
+        let block = NonNull::new_unchecked(self.base.add(offset)).cast();
+        block.write(FreeBlock {
+            next: state.free[k],
+            prev: None,
+            order: k,
+        });
+        if let Some(mut next) = state.free[k] {
+            next.as_mut().prev = Some(block);
+        }
+        state.free[k] = Some(block);
+        self.set_free(state, offset, true);
+    }
+
+    /// Removes the free block of order `k` at `offset` from its free list.
+    unsafe fn remove(&self, state: &mut State, offset: usize, k: usize) {
+        let block = self.base.add(offset).cast::<FreeBlock>();
+        let FreeBlock { next, prev, .. } = block.read();
+        match prev {
+            Some(mut p) => p.as_mut().next = next,
+            None => state.free[k] = next,
+        }
+        if let Some(mut n) = next {
+            n.as_mut().prev = prev;
+        }
+        self.set_free(state, offset, false);
+    }
+
+    /// Returns true if a block at `offset` can satisfy `align`. Blocks are
+    /// aligned to their size relative to `base`, so this only fails if the
+    /// alignment is larger than `base`'s.
+    #[inline(always)]
+    fn aligned(&self, offset: usize, align: usize) -> bool {
+        (self.base.addr() + offset) & (align - 1) == 0
+    }
+
+    /// Moves an allocation to a new block, for when it can't be resized in
+    /// place.
+    unsafe fn reallocate(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let new_ptr = self.allocate(new_layout)?;
+        ptr::copy_nonoverlapping(
+            ptr.as_ptr(),
+            new_ptr.as_ptr() as *mut u8,
+            old_layout.size().min(new_layout.size()),
+        );
+        self.deallocate(ptr, old_layout);
+        Ok(new_ptr)
+    }
+}
+
+unsafe impl Allocator for Buddy<'_> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let k = order_of(layout).ok_or(AllocError)?;
+        // SAFETY: the allocator is !Sync and nothing holds a reference to the
+        //         state across calls.
+        let state = unsafe { &mut *self.state.get() };
+        let j = (k..ORDERS)
+            .find(|&j| state.free[j].is_some())
+            .ok_or(AllocError)?;
+        let block = state.free[j].unwrap();
+        let offset = block.as_ptr().addr() - self.base.addr();
+        if !self.aligned(offset, layout.align()) {
+            return Err(AllocError);
+        }
+
+        // SAFETY: the block is on the free list, so it's within the buffer
+        //         and not in use. Its upper halves are too.
+        unsafe {
+            self.remove(state, offset, j);
+            for i in (k..j).rev() {
+                self.push(state, offset + block_size(i), i);
+            }
+        }
+        state.used += block_size(k);
+
+        Ok(NonNull::slice_from_raw_parts(block.cast(), block_size(k)))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        let state = &mut *self.state.get();
+        let Some(mut k) = order_of(layout) else {
+            return;
+        };
+        state.used -= block_size(k);
+
+        let mut offset = ptr.as_ptr().addr() - self.base.addr();
+        while k + 1 < ORDERS {
+            let buddy = offset ^ block_size(k);
+            if !self.is_free_block(state, buddy, k) {
+                break;
+            }
+            self.remove(state, buddy, k);
+            offset = offset.min(buddy);
+            k += 1;
+        }
+        self.push(state, offset, k);
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let old = order_of(old_layout).ok_or(AllocError)?;
+        let new = order_of(new_layout).ok_or(AllocError)?;
+        let state = &mut *self.state.get();
+        let offset = ptr.as_ptr().addr() - self.base.addr();
+        if !self.aligned(offset, new_layout.align()) {
+            return self.reallocate(ptr, old_layout, new_layout);
+        }
+
+        // The block can only grow in place if it's the lower half at every
+        // order up to the new one, and every upper half is free.
+        let in_place = (old..new).all(|k| {
+            offset & block_size(k) == 0
+                && self.is_free_block(state, offset + block_size(k), k)
+        });
+        if !in_place {
+            return self.reallocate(ptr, old_layout, new_layout);
+        }
+
+        for k in old..new {
+            self.remove(state, offset + block_size(k), k);
+        }
+        state.used += block_size(new) - block_size(old);
+        Ok(NonNull::slice_from_raw_parts(ptr, block_size(new)))
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let old = order_of(old_layout).ok_or(AllocError)?;
+        let new = order_of(new_layout).ok_or(AllocError)?;
+        let state = &mut *self.state.get();
+        let offset = ptr.as_ptr().addr() - self.base.addr();
+        if !self.aligned(offset, new_layout.align()) {
+            return self.reallocate(ptr, old_layout, new_layout);
+        }
+
+        // The upper halves being freed can't be merged, since their buddies
+        // are the lower halves still in use.
+        for k in new..old {
+            self.push(state, offset + block_size(k), k);
+        }
+        state.used -= block_size(old) - block_size(new);
+        Ok(NonNull::slice_from_raw_parts(ptr, block_size(new)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::array::Array;
+
+    fn layout(size: usize, align: usize) -> Layout {
+        Layout::from_size_align(size, align).unwrap()
+    }
+
+    fn buffer() -> Vec<u8> {
+        vec![0u8; 8192]
+    }
+
+    /// Returns `len` bytes of `mem` starting at an address aligned to 4096
+    /// bytes, so the first block a [Buddy] makes from it is too.
+    fn aligned(mem: &mut [u8], len: usize) -> &mut [u8] {
+        let offset = mem.as_ptr().align_offset(4096);
+        &mut mem[offset..offset + len]
+    }
+
+    #[test]
+    fn buddy_splits_and_merges() {
+        let mut mem = buffer();
+        // Room for 4096 bytes of blocks and their bitmap.
+        let buddy = Buddy::from_slice(aligned(&mut mem, 4096 + 16));
+        assert_eq!(buddy.capacity(), 4096);
+        buddy.check_invariants();
+
+        let blocks = (0..4096 / MIN_BLOCK)
+            .map(|_| buddy.allocate(layout(1, 1)).unwrap())
+            .collect::<Vec<_>>();
+        assert!(buddy.allocate(layout(1, 1)).is_err());
+        assert_eq!(buddy.used(), 4096);
+        buddy.check_invariants();
+
+        // Freeing in an arbitrary order still merges everything back.
+        for i in (0..blocks.len())
+            .step_by(2)
+            .chain((1..blocks.len()).step_by(2))
+        {
+            unsafe { buddy.deallocate(blocks[i].cast(), layout(1, 1)) };
+            buddy.check_invariants();
+        }
+        assert_eq!(buddy.used(), 0);
+        let all = buddy.allocate(layout(4096, 1)).unwrap();
+        assert_eq!(all.len(), 4096);
+    }
+
+    #[test]
+    fn buddy_rounds_to_block_sizes() {
+        let mut mem = buffer();
+        let buddy = Buddy::from_slice(aligned(&mut mem, 4096 + 16));
+        let a = buddy.allocate(layout(100, 4)).unwrap();
+        assert_eq!(a.len(), 128);
+        let b = buddy.allocate(layout(8, 256)).unwrap();
+        assert_eq!(b.len(), 256);
+        assert_eq!(b.cast::<u8>().addr().get() % 256, 0);
+        assert_eq!(buddy.used(), 384);
+        buddy.check_invariants();
+    }
+
+    #[test]
+    fn buddy_odd_sized_buffer() {
+        let mut mem = [0u8; 1000];
+        let buddy = Buddy::from_slice(&mut mem[3..]);
+        buddy.check_invariants();
+        let capacity = buddy.capacity();
+        assert!(capacity % MIN_BLOCK == 0 && capacity > 900);
+
+        let mut blocks = Vec::new();
+        while let Ok(x) = buddy.allocate(layout(MIN_BLOCK, 1)) {
+            blocks.push(x);
+        }
+        assert_eq!(blocks.len() * MIN_BLOCK, capacity);
+        for x in blocks {
+            unsafe { buddy.deallocate(x.cast(), layout(MIN_BLOCK, 1)) };
+        }
+        buddy.check_invariants();
+    }
+
+    #[test]
+    fn buddy_grow_in_place() {
+        let mut mem = buffer();
+        let buddy = Buddy::from_slice(aligned(&mut mem, 4096 + 16));
+        let a = buddy.allocate(layout(32, 8)).unwrap();
+        unsafe { a.cast::<u8>().write_bytes(9, 32) };
+        let b =
+            unsafe { buddy.grow(a.cast(), layout(32, 8), layout(1000, 8)) }
+                .unwrap();
+        assert_eq!(a.cast::<u8>(), b.cast::<u8>());
+        assert_eq!(b.len(), 1024);
+        assert_eq!(buddy.used(), 1024);
+        buddy.check_invariants();
+
+        // The upper half is in use, so growing has to move.
+        let c = buddy.allocate(layout(1024, 8)).unwrap();
+        let d =
+            unsafe { buddy.grow(b.cast(), layout(1000, 8), layout(2000, 8)) }
+                .unwrap();
+        assert_ne!(b.cast::<u8>(), d.cast::<u8>());
+        assert!(unsafe { &d.as_ref()[..32] }.iter().all(|&x| x == 9));
+        buddy.check_invariants();
+
+        unsafe { buddy.deallocate(c.cast(), layout(1024, 8)) };
+        let e =
+            unsafe { buddy.shrink(d.cast(), layout(2000, 8), layout(40, 8)) }
+                .unwrap();
+        assert_eq!(d.cast::<u8>(), e.cast::<u8>());
+        assert_eq!(buddy.used(), 64);
+        buddy.check_invariants();
+    }
+
+    #[test]
+    fn buddy_random_workload() {
+        let mut mem = vec![0u8; 1 << 16];
+        let buddy = Buddy::from_slice(&mut mem);
+        let mut live = Vec::new();
+        let mut rng = 0x2545f4914f6cdd1du64;
+        for _ in 0..5000 {
+            rng ^= rng << 13;
+            rng ^= rng >> 7;
+            rng ^= rng << 17;
+            let size = (rng % 2000) as usize + 1;
+            if rng % 3 == 0 && !live.is_empty() {
+                let (ptr, size): (NonNull<[u8]>, usize) =
+                    live.swap_remove(rng as usize % live.len());
+                unsafe { buddy.deallocate(ptr.cast(), layout(size, 8)) };
+            } else if let Ok(ptr) = buddy.allocate(layout(size, 8)) {
+                live.push((ptr, size));
+            }
+        }
+        buddy.check_invariants();
+        for (ptr, size) in live {
+            unsafe { buddy.deallocate(ptr.cast(), layout(size, 8)) };
+        }
+        buddy.check_invariants();
+        assert_eq!(buddy.used(), 0);
+    }
+
+    #[test]
+    fn buddy_array() {
+        let mut mem = vec![0u8; 1 << 16];
+        let buddy = Buddy::from_slice(&mut mem);
+        let mut a = Array::new(&buddy);
+        let mut b = Array::new(&buddy);
+        for i in 0..1000u32 {
+            a.push(i).unwrap();
+            b.push(i * 2).unwrap();
+        }
+        drop(a);
+        buddy.check_invariants();
+        assert_eq!(b[999], 1998);
+    }
+}
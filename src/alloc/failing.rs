@@ -0,0 +1,429 @@
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    cell::{Cell, RefCell},
+    ptr::NonNull,
+};
+use std::collections::HashMap;
+
+use super::Malloc;
+
+/// Decides which requests a [FailingAllocator] fails.
+///
+/// Requests are counted from 0, and include every call to
+/// [Allocator::allocate], [Allocator::allocate_zeroed], [Allocator::grow],
+/// [Allocator::grow_zeroed] and [Allocator::shrink].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailurePolicy {
+    /// Never fail, which is useful for counting requests.
+    Never,
+    /// Fail only the request with the given number.
+    Nth(usize),
+    /// Fail every request whose number plus one is a multiple of the given
+    /// number, so `EveryNth(3)` fails requests 2, 5, 8 and so on.
+    EveryNth(usize),
+    /// Fail any request which would make the number of bytes allocated larger
+    /// than the given budget.
+    Budget(usize),
+    /// Fail each request with a chance of one in `one_in`, using a random
+    /// number generator seeded with `seed` so runs can be reproduced.
+    Random { seed: u64, one_in: u64 },
+}
+
+/// An allocator which passes everything through to another [Allocator], but
+/// fails some of the requests according to a [FailurePolicy].
+///
+/// This is meant for testing that code handles [AllocError] correctly. See
+/// [fail_each_allocation] for a convenient way to test every failure path in
+/// some code.
+///
+/// The slices returned are always exactly the size requested, even if the
+/// inner allocator returned more.
+pub struct FailingAllocator<A: Allocator> {
+    alloc: A,
+    policy: FailurePolicy,
+    requests: Cell<usize>,
+    failures: Cell<usize>,
+    live_bytes: Cell<usize>,
+    rng: Cell<u64>,
+}
+
+impl<A: Allocator> FailingAllocator<A> {
+    /// Returns a [FailingAllocator] which allocates from `alloc`, failing the
+    /// requests chosen by `policy`.
+    #[inline(always)]
+    pub fn new(alloc: A, policy: FailurePolicy) -> FailingAllocator<A> {
+        let seed = match policy {
+            FailurePolicy::Random { seed, .. } => seed,
+            _ => 0,
+        };
+        FailingAllocator {
+            alloc,
+            policy,
+            requests: Cell::new(0),
+            failures: Cell::new(0),
+            live_bytes: Cell::new(0),
+            rng: Cell::new(seed),
+        }
+    }
+
+    /// Returns the number of requests made so far, including failed ones.
+    #[inline(always)]
+    pub fn requests(&self) -> usize {
+        self.requests.get()
+    }
+
+    /// Returns the number of requests which were failed on purpose.
+    #[inline(always)]
+    pub fn failures(&self) -> usize {
+        self.failures.get()
+    }
+
+    /// Returns the allocator requests are passed to.
+    #[inline(always)]
+    pub fn inner(&self) -> &A {
+        &self.alloc
+    }
+
+    /// Counts a request which would change the number of live bytes to
+    /// `new_live_bytes`, and returns true if it should fail.
+    fn should_fail(&self, new_live_bytes: usize) -> bool {
+        let request = self.requests.get();
+        self.requests.set(request + 1);
+        let fail = match self.policy {
+            FailurePolicy::Never => false,
+            FailurePolicy::Nth(n) => request == n,
+            FailurePolicy::EveryNth(n) => n != 0 && (request + 1) % n == 0,
+            FailurePolicy::Budget(budget) => new_live_bytes > budget,
+            FailurePolicy::Random { one_in, .. } => {
+                one_in != 0 && self.next_random() % one_in == 0
+            }
+        };
+        if fail {
+            self.failures.set(self.failures.get() + 1);
+        }
+        fail
+    }
+
+    /// Returns the next number from a splitmix64 generator.
+    #[inline(always)]
+    fn next_random(&self) -> u64 {
+        let state = self.rng.get().wrapping_add(0x9e3779b97f4a7c15);
+        self.rng.set(state);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    #[inline(always)]
+    fn allocate_with(
+        &self,
+        layout: Layout,
+        f: impl FnOnce() -> Result<NonNull<[u8]>, AllocError>,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let live_bytes = self.live_bytes.get().saturating_add(layout.size());
+        if self.should_fail(live_bytes) {
+            return Err(AllocError);
+        }
+        let result = f()?;
+        self.live_bytes.set(live_bytes);
+        Ok(NonNull::slice_from_raw_parts(result.cast(), layout.size()))
+    }
+
+    #[inline(always)]
+    fn resize_with(
+        &self,
+        old_layout: Layout,
+        new_layout: Layout,
+        f: impl FnOnce() -> Result<NonNull<[u8]>, AllocError>,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let live_bytes = (self.live_bytes.get() - old_layout.size())
+            .saturating_add(new_layout.size());
+        if self.should_fail(live_bytes) {
+            return Err(AllocError);
+        }
+        let result = f()?;
+        self.live_bytes.set(live_bytes);
+        Ok(NonNull::slice_from_raw_parts(
+            result.cast(),
+            new_layout.size(),
+        ))
+    }
+}
+
+unsafe impl<A: Allocator> Allocator for FailingAllocator<A> {
+    #[inline(always)]
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        self.allocate_with(layout, || self.alloc.allocate(layout))
+    }
+
+    #[inline(always)]
+    fn allocate_zeroed(
+        &self,
+        layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        self.allocate_with(layout, || self.alloc.allocate_zeroed(layout))
+    }
+
+    #[inline(always)]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.live_bytes.set(self.live_bytes.get() - layout.size());
+        self.alloc.deallocate(ptr, layout)
+    }
+
+    #[inline(always)]
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        self.resize_with(old_layout, new_layout, || {
+            self.alloc.grow(ptr, old_layout, new_layout)
+        })
+    }
+
+    #[inline(always)]
+    unsafe fn grow_zeroed(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        self.resize_with(old_layout, new_layout, || {
+            self.alloc.grow_zeroed(ptr, old_layout, new_layout)
+        })
+    }
+
+    #[inline(always)]
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        self.resize_with(old_layout, new_layout, || {
+            self.alloc.shrink(ptr, old_layout, new_layout)
+        })
+    }
+}
+
+/// An allocator which allocates from [Malloc], panicking if anything is
+/// freed twice or with the wrong [Layout], and which can check that
+/// everything allocated from it has been freed.
+///
+/// The slices returned are always exactly the size requested, so the
+/// [Layout] something is freed with has to match the one it was allocated
+/// with exactly.
+pub struct LeakCheck {
+    live: RefCell<HashMap<usize, Layout>>,
+}
+
+impl LeakCheck {
+    #[inline(always)]
+    pub fn new() -> LeakCheck {
+        LeakCheck {
+            live: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the number of allocations which haven't been freed.
+    #[inline(always)]
+    pub fn live(&self) -> usize {
+        self.live.borrow().len()
+    }
+
+    fn insert(&self, ptr: NonNull<[u8]>, layout: Layout) {
+        self.live
+            .borrow_mut()
+            .insert(ptr.cast::<u8>().addr().get(), layout);
+    }
+
+    fn remove(&self, ptr: NonNull<u8>, layout: Layout) {
+        match self.live.borrow_mut().remove(&ptr.addr().get()) {
+            Some(x) if x == layout => {}
+            Some(x) => panic!(
+                "{ptr:?} was allocated with {x:?}, but freed with {layout:?}"
+            ),
+            None => panic!("{ptr:?} was freed twice or never allocated"),
+        }
+    }
+}
+
+impl Default for LeakCheck {
+    #[inline(always)]
+    fn default() -> Self {
+        LeakCheck::new()
+    }
+}
+
+unsafe impl Allocator for LeakCheck {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let result = Malloc.allocate(layout)?;
+        self.insert(result, layout);
+        Ok(NonNull::slice_from_raw_parts(result.cast(), layout.size()))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.remove(ptr, layout);
+        Malloc.deallocate(ptr, layout)
+    }
+}
+
+/// Runs `f` over and over, failing its first allocation request, then its
+/// second, and so on until it runs without making a request which could be
+/// failed. This tests that every allocation failure in `f` is handled without
+/// leaking memory or freeing something twice, which panics.
+///
+/// `f` has to make the same requests every time it's run.
+pub fn fail_each_allocation(mut f: impl FnMut(&FailingAllocator<LeakCheck>)) {
+    for n in 0.. {
+        let alloc =
+            FailingAllocator::new(LeakCheck::new(), FailurePolicy::Nth(n));
+        f(&alloc);
+        let leaked = alloc.inner().live();
+        assert!(
+            leaked == 0,
+            "{leaked} allocations leaked when request {n} failed"
+        );
+        if alloc.failures() == 0 {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        alloc::String,
+        array::Array,
+        url::{ParseError, Url},
+    };
+
+    fn layout(size: usize) -> Layout {
+        Layout::from_size_align(size, 1).unwrap()
+    }
+
+    /// Returns which of the first `n` requests `alloc` fails.
+    fn failed<A: Allocator>(
+        alloc: &FailingAllocator<A>,
+        n: usize,
+    ) -> Vec<usize> {
+        let mut result = Vec::new();
+        for i in 0..n {
+            match alloc.allocate(layout(1)) {
+                Ok(x) => unsafe { alloc.deallocate(x.cast(), layout(1)) },
+                Err(_) => result.push(i),
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn failing_policies() {
+        let alloc = FailingAllocator::new(Malloc, FailurePolicy::Never);
+        assert!(failed(&alloc, 10).is_empty());
+        assert_eq!(alloc.requests(), 10);
+
+        let alloc = FailingAllocator::new(Malloc, FailurePolicy::Nth(3));
+        assert_eq!(failed(&alloc, 10), [3]);
+
+        let alloc = FailingAllocator::new(Malloc, FailurePolicy::EveryNth(3));
+        assert_eq!(failed(&alloc, 10), [2, 5, 8]);
+        assert_eq!(alloc.failures(), 3);
+    }
+
+    #[test]
+    fn failing_budget() {
+        let alloc = FailingAllocator::new(Malloc, FailurePolicy::Budget(100));
+        let a = alloc.allocate(layout(60)).unwrap();
+        assert!(alloc.allocate(layout(41)).is_err());
+        let b = alloc.allocate(layout(40)).unwrap();
+        assert!(
+            unsafe { alloc.grow(b.cast(), layout(40), layout(50)) }.is_err()
+        );
+
+        unsafe { alloc.deallocate(a.cast(), layout(60)) };
+        let b =
+            unsafe { alloc.grow(b.cast(), layout(40), layout(100)) }.unwrap();
+        unsafe { alloc.deallocate(b.cast(), layout(100)) };
+    }
+
+    #[test]
+    fn failing_random_is_reproducible() {
+        let policy = FailurePolicy::Random {
+            seed: 1234,
+            one_in: 4,
+        };
+        let a = failed(&FailingAllocator::new(Malloc, policy), 1000);
+        let b = failed(&FailingAllocator::new(Malloc, policy), 1000);
+        assert_eq!(a, b);
+        assert!(a.len() > 150 && a.len() < 350);
+    }
+
+    #[test]
+    fn fail_each_allocation_array() {
+        fail_each_allocation(|alloc| {
+            let mut array = Array::new(alloc);
+            for i in 0..100 {
+                if array.push(Box::new(i)).is_err() {
+                    return;
+                }
+            }
+            let Ok(copy) = array.try_clone() else {
+                return;
+            };
+            assert_eq!(copy.len(), 100);
+        });
+    }
+
+    #[test]
+    fn fail_each_allocation_string() {
+        fail_each_allocation(|alloc| {
+            let mut s = String::new_in(alloc);
+            for _ in 0..20 {
+                if s.try_push_str("hello ").is_err() {
+                    return;
+                }
+            }
+            let _ = s.try_insert_str(3, "world");
+            let _ = s.try_split_off(10);
+        });
+    }
+
+    #[test]
+    fn fail_each_allocation_url() {
+        let mut failures = 0;
+        fail_each_allocation(|alloc| {
+            match Url::parse_in("https://user@example.com/a/../b?q#f", alloc) {
+                Ok(url) => assert_eq!(url.path(), "/b"),
+                Err(ParseError::AllocError(_)) => failures += 1,
+                Err(e) => panic!("unexpected error {e}"),
+            }
+        });
+        assert!(failures > 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "leaked")]
+    fn fail_each_allocation_detects_leaks() {
+        fail_each_allocation(|alloc| {
+            if let Ok(x) = alloc.allocate(layout(8)) {
+                if alloc.allocate(layout(8)).is_err() {
+                    return;
+                }
+                unsafe { alloc.deallocate(x.cast(), layout(8)) };
+            }
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "freed twice")]
+    fn leak_check_detects_double_free() {
+        let alloc = LeakCheck::new();
+        let x = alloc.allocate(layout(8)).unwrap();
+        unsafe { alloc.deallocate(x.cast(), layout(8)) };
+        unsafe { alloc.deallocate(x.cast(), layout(8)) };
+    }
+}
@@ -0,0 +1,244 @@
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    ptr::{self, NonNull},
+};
+
+/// An [Allocator] which can tell whether some memory was allocated by it.
+///
+/// This is what lets a [Fallback] send memory back to the allocator it came
+/// from. Allocators which hand out memory from a single range of addresses,
+/// like the [FixedBufferAllocator](super::FixedBufferAllocator), can answer
+/// this by checking whether the memory is in their range.
+pub trait Owns: Allocator {
+    /// Returns true if the memory at `ptr`, described by `layout`, was
+    /// allocated by this allocator.
+    fn owns(&self, ptr: NonNull<u8>, layout: Layout) -> bool;
+}
+
+impl<A: Owns + ?Sized> Owns for &A {
+    #[inline(always)]
+    fn owns(&self, ptr: NonNull<u8>, layout: Layout) -> bool {
+        (**self).owns(ptr, layout)
+    }
+}
+
+/// An allocator which allocates from a primary allocator, and uses a
+/// secondary allocator once the primary one fails.
+///
+/// Memory is freed and resized by whichever allocator it came from, which
+/// the primary allocator tells by implementing [Owns]. The most common use is
+/// keeping small amounts of memory in a buffer on the stack, and only going
+/// to the heap when it runs out.
+///
+/// ```
+/// use stdx::{
+///     alloc::{Fallback, FixedBufferAllocator, Malloc},
+///     array::Array,
+/// };
+///
+/// let mut buffer = [0; 64];
+/// let alloc =
+///     Fallback::new(FixedBufferAllocator::from_slice(&mut buffer), Malloc);
+/// let mut numbers = Array::new(&alloc);
+/// numbers.try_extend(0..1000).unwrap();
+/// ```
+pub struct Fallback<P: Owns, S: Allocator> {
+    primary: P,
+    secondary: S,
+}
+
+impl<P: Owns, S: Allocator> Fallback<P, S> {
+    /// Returns a [Fallback] which allocates from `primary` until it fails,
+    /// and from `secondary` after that.
+    #[inline(always)]
+    pub const fn new(primary: P, secondary: S) -> Fallback<P, S> {
+        Fallback { primary, secondary }
+    }
+
+    /// Returns the primary allocator.
+    #[inline(always)]
+    pub fn primary(&self) -> &P {
+        &self.primary
+    }
+
+    /// Returns the secondary allocator.
+    #[inline(always)]
+    pub fn secondary(&self) -> &S {
+        &self.secondary
+    }
+
+    /// Moves memory owned by the primary allocator into a new allocation,
+    /// for when the primary allocator can't resize it.
+    unsafe fn reallocate(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let new_ptr = self.allocate(new_layout)?;
+        ptr::copy_nonoverlapping(
+            ptr.as_ptr(),
+            new_ptr.as_ptr() as *mut u8,
+            old_layout.size().min(new_layout.size()),
+        );
+        self.primary.deallocate(ptr, old_layout);
+        Ok(new_ptr)
+    }
+}
+
+unsafe impl<P: Owns, S: Allocator> Allocator for Fallback<P, S> {
+    #[inline(always)]
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        self.primary
+            .allocate(layout)
+            .or_else(|_| self.secondary.allocate(layout))
+    }
+
+    #[inline(always)]
+    fn allocate_zeroed(
+        &self,
+        layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        self.primary
+            .allocate_zeroed(layout)
+            .or_else(|_| self.secondary.allocate_zeroed(layout))
+    }
+
+    #[inline(always)]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if self.primary.owns(ptr, layout) {
+            self.primary.deallocate(ptr, layout)
+        } else {
+            self.secondary.deallocate(ptr, layout)
+        }
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if !self.primary.owns(ptr, old_layout) {
+            return self.secondary.grow(ptr, old_layout, new_layout);
+        }
+
+        match self.primary.grow(ptr, old_layout, new_layout) {
+            Ok(result) => Ok(result),
+            Err(_) => self.reallocate(ptr, old_layout, new_layout),
+        }
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if !self.primary.owns(ptr, old_layout) {
+            return self.secondary.shrink(ptr, old_layout, new_layout);
+        }
+
+        match self.primary.shrink(ptr, old_layout, new_layout) {
+            Ok(result) => Ok(result),
+            Err(_) => self.reallocate(ptr, old_layout, new_layout),
+        }
+    }
+}
+
+impl<P: Owns, S: Owns> Owns for Fallback<P, S> {
+    #[inline(always)]
+    fn owns(&self, ptr: NonNull<u8>, layout: Layout) -> bool {
+        self.primary.owns(ptr, layout) || self.secondary.owns(ptr, layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        alloc::{FixedBufferAllocator, LeakCheck, VirtualMemoryAllocator},
+        array::Array,
+    };
+
+    fn layout(size: usize, align: usize) -> Layout {
+        Layout::from_size_align(size, align).unwrap()
+    }
+
+    #[test]
+    fn fallback_spills_to_secondary() {
+        let mut buffer = [0; 64];
+        let alloc = Fallback::new(
+            FixedBufferAllocator::from_slice(&mut buffer),
+            LeakCheck::new(),
+        );
+
+        let a = alloc.allocate(layout(32, 8)).unwrap();
+        assert!(alloc.primary().owns(a.cast(), layout(32, 8)));
+        assert_eq!(alloc.secondary().live(), 0);
+        let b = alloc.allocate(layout(64, 8)).unwrap();
+        assert!(!alloc.primary().owns(b.cast(), layout(64, 8)));
+        assert_eq!(alloc.secondary().live(), 1);
+
+        unsafe {
+            alloc.deallocate(b.cast(), layout(64, 8));
+            alloc.deallocate(a.cast(), layout(32, 8));
+        }
+        assert_eq!(alloc.secondary().live(), 0);
+        assert_eq!(alloc.primary().used(), 0);
+    }
+
+    #[test]
+    fn fallback_grow_moves_to_secondary() {
+        let mut buffer = [0; 64];
+        let alloc = Fallback::new(
+            FixedBufferAllocator::from_slice(&mut buffer),
+            LeakCheck::new(),
+        );
+        {
+            let mut array = Array::new(&alloc);
+            array.try_extend(0..1000u32).unwrap();
+            assert_eq!(array[999], 999);
+            assert_eq!(alloc.secondary().live(), 1);
+        }
+        assert_eq!(alloc.secondary().live(), 0);
+    }
+
+    #[test]
+    fn fallback_shrink_keeps_contents() {
+        let mut buffer = [0; 64];
+        let alloc = Fallback::new(
+            FixedBufferAllocator::from_slice(&mut buffer),
+            LeakCheck::new(),
+        );
+        let a = alloc.allocate(layout(16, 8)).unwrap();
+        let _b = alloc.allocate(layout(16, 8)).unwrap();
+        unsafe { a.cast::<u8>().write_bytes(7, 16) };
+        // a isn't the last allocation, so the buffer can't shrink it in
+        // place.
+        let a = unsafe {
+            alloc.shrink(a.cast(), layout(16, 8), layout(8, 8)).unwrap()
+        };
+        assert!(unsafe { &a.as_ref()[..8] }.iter().all(|&x| x == 7));
+    }
+
+    #[test]
+    fn fallback_owns_nests() {
+        let mut small = [0; 16];
+        let vm = VirtualMemoryAllocator::new(1 << 20).unwrap();
+        let alloc = Fallback::new(
+            Fallback::new(FixedBufferAllocator::from_slice(&mut small), &vm),
+            LeakCheck::new(),
+        );
+        let a = alloc.allocate(layout(16, 1)).unwrap();
+        let b = alloc.allocate(layout(16, 1)).unwrap();
+        assert!(alloc.primary().primary().owns(a.cast(), layout(16, 1)));
+        assert!(vm.owns(b.cast(), layout(16, 1)));
+        assert!(alloc.primary().owns(b.cast(), layout(16, 1)));
+        unsafe {
+            alloc.deallocate(b.cast(), layout(16, 1));
+            alloc.deallocate(a.cast(), layout(16, 1));
+        }
+        assert_eq!(vm.used(), 0);
+    }
+}
@@ -6,6 +6,8 @@ use std::{
     slice,
 };
 
+use super::Owns;
+
 /// An allocator backed by some `[u8]` which simply bumps a pointer within that
 /// buffer to allocate memory.
 ///
@@ -24,6 +26,8 @@ pub struct FixedBufferAllocator<'a> {
     /// one of many possible invalid pointer values and we're already doing all
     /// the necessary leg work to make this safe to use.
     begin: UnsafeCell<*mut u8>,
+    /// Where `begin` pointed when the [FixedBufferAllocator] was created.
+    start: *mut u8,
     /// `end` points to one byte past the end of the buffer. In other words, if
     /// `begin >= end`, `begin` does not point to valid memory.
     end: *const u8,
@@ -34,6 +38,14 @@ pub struct FixedBufferAllocator<'a> {
 
 unsafe impl<'a> Send for FixedBufferAllocator<'a> {}
 
+/// A position in a [FixedBufferAllocator] returned by
+/// [FixedBufferAllocator::checkpoint], which the allocator can later be
+/// rewound to using [FixedBufferAllocator::rewind].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Mark {
+    begin: *mut u8,
+}
+
 impl<'a> FixedBufferAllocator<'a> {
     /// Constructs a [FixedBufferAllocator] given a pointer to the
     /// beginning and the end of the memory range to allocate from.
@@ -60,10 +72,91 @@ impl<'a> FixedBufferAllocator<'a> {
     pub fn from_slice(mem: &'a mut [u8]) -> FixedBufferAllocator<'a> {
         FixedBufferAllocator {
             begin: UnsafeCell::new(mem.as_mut_ptr()),
+            start: mem.as_mut_ptr(),
             end: unsafe { mem.as_ptr().add(mem.len()) },
             _marker: PhantomData,
         }
     }
+
+    /// Returns the number of bytes which have been allocated from the
+    /// buffer, including any padding needed for alignment.
+    #[inline(always)]
+    pub fn used(&self) -> usize {
+        unsafe { *self.begin.get() }.addr() - self.start.addr()
+    }
+
+    /// Returns the number of bytes left in the buffer.
+    #[inline(always)]
+    pub fn remaining(&self) -> usize {
+        self.end.addr() - unsafe { *self.begin.get() }.addr()
+    }
+
+    /// Returns the current position of the allocator, which can be passed to
+    /// [FixedBufferAllocator::rewind] to free everything allocated after this
+    /// call at once.
+    #[inline(always)]
+    pub fn checkpoint(&self) -> Mark {
+        Mark {
+            begin: unsafe { *self.begin.get() },
+        }
+    }
+
+    /// Frees everything allocated since `mark` was returned by
+    /// [FixedBufferAllocator::checkpoint].
+    ///
+    /// # Safety
+    ///
+    /// Behavior is undefined if any of the following conditions are violated:
+    ///
+    /// - `mark` must have been returned by [FixedBufferAllocator::checkpoint]
+    ///   on this allocator.
+    /// - Nothing allocated after `mark` was returned may be used after calling
+    ///   this function.
+    #[inline(always)]
+    pub unsafe fn rewind(&self, mark: Mark) {
+        debug_assert!(
+            self.start.addr() <= mark.begin.addr()
+                && mark.begin.addr() <= self.end.addr()
+        );
+        self.begin.get().write(mark.begin);
+    }
+
+    /// Frees everything allocated from the buffer.
+    #[inline(always)]
+    pub fn reset(&mut self) {
+        *self.begin.get_mut() = self.start;
+    }
+
+    /// Calls `f` with a [FixedBufferAllocator] using the rest of this
+    /// allocator's buffer. Everything allocated from it is freed once `f`
+    /// returns, making it useful for scratch memory which only lives for the
+    /// duration of some computation.
+    pub fn scope<R>(
+        &mut self,
+        f: impl FnOnce(&FixedBufferAllocator<'_>) -> R,
+    ) -> R {
+        // SAFETY: the memory after begin hasn't been handed out, and since we
+        //         have a mutable reference, nothing can allocate it from self
+        //         while the child is alive.
+        let child = unsafe {
+            FixedBufferAllocator::from_raw_parts(
+                *self.begin.get_mut(),
+                self.remaining(),
+            )
+        };
+        f(&child)
+    }
+}
+
+impl Owns for FixedBufferAllocator<'_> {
+    #[inline(always)]
+    fn owns(&self, ptr: NonNull<u8>, layout: alloc::Layout) -> bool {
+        let addr = ptr.as_ptr().addr();
+        addr >= self.start.addr()
+            && addr
+                .checked_add(layout.size())
+                .is_some_and(|end| end <= self.end.addr())
+    }
 }
 
 unsafe impl<'a> Allocator for FixedBufferAllocator<'a> {
@@ -120,7 +213,7 @@ unsafe impl<'a> Allocator for FixedBufferAllocator<'a> {
         //         allocated, meaning we don't have to worry about the case
         //         where this underflows.
         let prev_begin = (begin.addr() - old_size) & !(align - 1);
-        if ptr.as_ptr().cast_const() != begin.with_addr(prev_begin) {
+        if !ptr::eq(ptr.as_ptr().cast_const(), begin.with_addr(prev_begin)) {
             // We can only resize if it was the last thing we allocated.
             return Err(alloc::AllocError);
         }
@@ -128,12 +221,12 @@ unsafe impl<'a> Allocator for FixedBufferAllocator<'a> {
         // SAFETY: Layout guarantees the following: sizes are less than or
         //         equal to isize::MAX, new_layout.size() >= old_layout.size().
         //         These mean this shouldn't overflow.
-        let difference = new_size as isize - old_size as isize;
-        if self.end.byte_offset_from(ptr.as_ptr().offset(difference)) < 0 {
+        let difference = new_size - old_size;
+        if self.end.addr() - begin.addr() < difference {
             return Err(alloc::AllocError);
         }
 
-        self.begin.get().write(begin.offset(difference));
+        self.begin.get().write(begin.add(difference));
         Ok(NonNull::new_unchecked(slice::from_raw_parts_mut(
             begin.with_addr(prev_begin),
             new_layout.size(),
@@ -166,7 +259,7 @@ unsafe impl<'a> Allocator for FixedBufferAllocator<'a> {
         //         allocated, meaning we don't have to worry about the case
         //         where this underflows.
         let prev_begin = (begin.addr() - old_size) & !(align - 1);
-        if ptr.as_ptr().cast_const() != begin.with_addr(prev_begin) {
+        if !ptr::eq(ptr.as_ptr().cast_const(), begin.with_addr(prev_begin)) {
             // We can only resize if it was the last thing we allocated.
             return Err(alloc::AllocError);
         }
@@ -192,7 +285,7 @@ unsafe impl<'a> Allocator for FixedBufferAllocator<'a> {
         //         allocated, meaning we don't have to worry about the case
         //         where this underflows.
         let prev_begin = (begin.addr() - size) & !(align - 1);
-        if ptr.as_ptr().cast_const() != begin.with_addr(prev_begin) {
+        if !ptr::eq(ptr.as_ptr().cast_const(), begin.with_addr(prev_begin)) {
             // We can only deallocate if it was the last thing we allocated.
             return;
         }
@@ -213,10 +306,6 @@ mod tests {
         alloc::Layout::from_size_align(size, align).unwrap()
     }
 
-    fn remaining_size(fba: &FixedBufferAllocator<'_>) -> usize {
-        fba.end.addr() - unsafe { *fba.begin.get() }.addr()
-    }
-
     #[test]
     fn fba_alloc_result_length_is_correct() {
         let mut buffer = [0u8; 8];
@@ -228,7 +317,7 @@ mod tests {
         let result = fba.allocate(layout(7, 1));
         assert!(result.is_ok());
         assert_eq!(result.unwrap().len(), 7);
-        assert_eq!(remaining_size(&fba), 0);
+        assert_eq!(fba.remaining(), 0);
     }
 
     #[test]
@@ -245,7 +334,7 @@ mod tests {
         let mut buffer = [0u8; 1];
         let fba = FixedBufferAllocator::from_slice(&mut buffer);
         assert!(fba.allocate(layout(1, 1)).is_ok());
-        assert_eq!(remaining_size(&fba), 0);
+        assert_eq!(fba.remaining(), 0);
         assert!(fba.allocate(layout(1, 1)).is_err());
     }
 
@@ -261,7 +350,7 @@ mod tests {
         let mut buffer = [0u8; 2];
         let fba = FixedBufferAllocator::from_slice(&mut buffer);
 
-        let size = remaining_size(&fba);
+        let size = fba.remaining();
 
         let alloc1 = fba.allocate(layout(1, 1));
         assert!(alloc1.is_ok());
@@ -270,16 +359,16 @@ mod tests {
         let alloc1 = alloc1.unwrap();
         let alloc2 = alloc2.unwrap();
 
-        assert_eq!(remaining_size(&fba), 0);
+        assert_eq!(fba.remaining(), 0);
 
         unsafe { fba.deallocate(alloc1.cast(), layout(1, 1)) };
-        assert_eq!(remaining_size(&fba), 0);
+        assert_eq!(fba.remaining(), 0);
 
         unsafe { fba.deallocate(alloc2.cast(), layout(1, 1)) };
-        assert_eq!(remaining_size(&fba), alloc1.len());
+        assert_eq!(fba.remaining(), alloc1.len());
 
         unsafe { fba.deallocate(alloc1.cast(), layout(1, 1)) };
-        assert_eq!(remaining_size(&fba), size);
+        assert_eq!(fba.remaining(), size);
     }
 
     #[test]
@@ -294,18 +383,89 @@ mod tests {
         let alloc1 = alloc1.unwrap();
         let alloc2 = alloc2.unwrap();
 
-        assert_eq!(remaining_size(&fba), 1);
+        assert_eq!(fba.remaining(), 1);
 
         assert!(unsafe {
             fba.grow(alloc1.cast(), layout(1, 1), layout(2, 1))
         }
         .is_err());
-        assert_eq!(remaining_size(&fba), 1);
+        assert_eq!(fba.remaining(), 1);
 
         assert!(unsafe {
             fba.grow(alloc2.cast(), layout(1, 1), layout(2, 1))
         }
         .is_ok());
-        assert_eq!(remaining_size(&fba), 0);
+        assert_eq!(fba.remaining(), 0);
+    }
+
+    #[test]
+    fn fba_grow_past_end_fails() {
+        let mut buffer = [0u8; 4];
+        let fba = FixedBufferAllocator::from_slice(&mut buffer);
+
+        let alloc = fba.allocate(layout(4, 1)).unwrap();
+        assert!(
+            unsafe { fba.grow(alloc.cast(), layout(4, 1), layout(8, 1)) }
+                .is_err()
+        );
+        assert_eq!(fba.remaining(), 0);
+    }
+
+    #[test]
+    fn fba_used_and_remaining() {
+        let mut buffer = [0u8; 8];
+        let fba = FixedBufferAllocator::from_slice(&mut buffer);
+        assert_eq!(fba.used(), 0);
+        assert_eq!(fba.remaining(), 8);
+
+        assert!(fba.allocate(layout(3, 1)).is_ok());
+        assert_eq!(fba.used(), 3);
+        assert_eq!(fba.remaining(), 5);
+    }
+
+    #[test]
+    fn fba_rewind_works() {
+        let mut buffer = [0u8; 8];
+        let fba = FixedBufferAllocator::from_slice(&mut buffer);
+
+        assert!(fba.allocate(layout(2, 1)).is_ok());
+        let mark = fba.checkpoint();
+        let alloc1 = fba.allocate(layout(2, 1)).unwrap();
+        assert!(fba.allocate(layout(4, 1)).is_ok());
+        assert_eq!(fba.remaining(), 0);
+
+        unsafe { fba.rewind(mark) };
+        assert_eq!(fba.used(), 2);
+        let alloc2 = fba.allocate(layout(2, 1)).unwrap();
+        assert_eq!(alloc1.cast::<u8>(), alloc2.cast::<u8>());
+    }
+
+    #[test]
+    fn fba_reset_works() {
+        let mut buffer = [0u8; 8];
+        let mut fba = FixedBufferAllocator::from_slice(&mut buffer);
+
+        assert!(fba.allocate(layout(8, 1)).is_ok());
+        assert_eq!(fba.remaining(), 0);
+        fba.reset();
+        assert_eq!(fba.used(), 0);
+        assert_eq!(fba.remaining(), 8);
+    }
+
+    #[test]
+    fn fba_scope_restores_cursor() {
+        let mut buffer = [0u8; 8];
+        let mut fba = FixedBufferAllocator::from_slice(&mut buffer);
+
+        assert!(fba.allocate(layout(2, 1)).is_ok());
+        let len = fba.scope(|scratch| {
+            assert_eq!(scratch.remaining(), 6);
+            let alloc = scratch.allocate(layout(6, 1)).unwrap();
+            assert!(scratch.allocate(layout(1, 1)).is_err());
+            alloc.len()
+        });
+        assert_eq!(len, 6);
+        assert_eq!(fba.used(), 2);
+        assert_eq!(fba.remaining(), 6);
     }
 }
@@ -0,0 +1,296 @@
+use core::{
+    alloc::{AllocError, Allocator, GlobalAlloc, Layout},
+    cell::UnsafeCell,
+    hint,
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use super::VirtualMemoryAllocator;
+
+/// The amount of address space reserved by [GlobalAdapter::virtual_memory].
+/// Only the memory which is actually allocated gets committed, so this can
+/// be far more than the machine has.
+#[cfg(target_pointer_width = "64")]
+const GLOBAL_RESERVATION: usize = 64 << 30;
+#[cfg(not(target_pointer_width = "64"))]
+const GLOBAL_RESERVATION: usize = 1 << 30;
+
+enum State<A> {
+    Uninit(fn() -> Result<A, AllocError>),
+    Ready(A),
+    Failed,
+}
+
+/// An adapter which implements [GlobalAlloc] for any [Allocator], so it can
+/// be installed with `#[global_allocator]`.
+///
+/// Every call locks a spinlock around the inner allocator, which is what
+/// makes it safe to use allocators which are `!Sync` from every thread. The
+/// allocator only needs to be [Send]. It can be created up front with
+/// [GlobalAdapter::new], or on the first allocation with
+/// [GlobalAdapter::lazy].
+///
+/// ```ignore
+/// #[global_allocator]
+/// static GLOBAL: GlobalAdapter<VirtualMemoryAllocator> =
+///     GlobalAdapter::virtual_memory();
+/// ```
+pub struct GlobalAdapter<A> {
+    locked: AtomicBool,
+    state: UnsafeCell<State<A>>,
+}
+
+unsafe impl<A: Send> Sync for GlobalAdapter<A> {}
+
+/// Unlocks a [GlobalAdapter] when dropped.
+struct Guard<'a> {
+    locked: &'a AtomicBool,
+}
+
+impl Drop for Guard<'_> {
+    #[inline(always)]
+    fn drop(&mut self) {
+        self.locked.store(false, Ordering::Release);
+    }
+}
+
+impl<A> GlobalAdapter<A> {
+    /// Returns a [GlobalAdapter] which allocates from `alloc`.
+    #[inline(always)]
+    pub const fn new(alloc: A) -> GlobalAdapter<A> {
+        GlobalAdapter {
+            locked: AtomicBool::new(false),
+            state: UnsafeCell::new(State::Ready(alloc)),
+        }
+    }
+
+    /// Returns a [GlobalAdapter] which calls `init` to create its allocator
+    /// the first time something is allocated. If `init` fails, every
+    /// allocation does too.
+    ///
+    /// `init` is called with the lock held, so it must not allocate through
+    /// the global allocator.
+    #[inline(always)]
+    pub const fn lazy(
+        init: fn() -> Result<A, AllocError>,
+    ) -> GlobalAdapter<A> {
+        GlobalAdapter {
+            locked: AtomicBool::new(false),
+            state: UnsafeCell::new(State::Uninit(init)),
+        }
+    }
+
+    #[inline(always)]
+    fn lock(&self) -> Guard<'_> {
+        while self
+            .locked
+            .compare_exchange_weak(
+                false,
+                true,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_err()
+        {
+            while self.locked.load(Ordering::Relaxed) {
+                hint::spin_loop();
+            }
+        }
+        Guard {
+            locked: &self.locked,
+        }
+    }
+
+    /// Calls `f` with the inner allocator while holding the lock, creating
+    /// the allocator first if needed. Returns null if it couldn't be
+    /// created.
+    #[inline(always)]
+    fn with<F>(&self, f: F) -> *mut u8
+    where
+        F: FnOnce(&A) -> Result<NonNull<[u8]>, AllocError>,
+    {
+        let _guard = self.lock();
+        // SAFETY: the lock is held, so nothing else can access the state.
+        let state = unsafe { &mut *self.state.get() };
+        if let State::Uninit(init) = *state {
+            *state = match init() {
+                Ok(alloc) => State::Ready(alloc),
+                Err(_) => State::Failed,
+            };
+        }
+        match state {
+            State::Ready(alloc) => match f(alloc) {
+                Ok(ptr) => ptr.cast::<u8>().as_ptr(),
+                Err(_) => ptr::null_mut(),
+            },
+            _ => ptr::null_mut(),
+        }
+    }
+}
+
+impl GlobalAdapter<VirtualMemoryAllocator> {
+    /// Returns a [GlobalAdapter] which reserves a large range of virtual
+    /// memory the first time something is allocated, and allocates from it
+    /// with a [VirtualMemoryAllocator].
+    ///
+    /// Memory is only reclaimed when the last thing allocated is freed, so
+    /// this is best suited to short-lived programs, like command line tools
+    /// and batch jobs, which allocate as they go and exit when they're done.
+    #[inline(always)]
+    pub const fn virtual_memory() -> GlobalAdapter<VirtualMemoryAllocator> {
+        GlobalAdapter::lazy(|| VirtualMemoryAllocator::new(GLOBAL_RESERVATION))
+    }
+}
+
+unsafe impl<A: Allocator> GlobalAlloc for GlobalAdapter<A> {
+    #[inline(always)]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.with(|alloc| alloc.allocate(layout))
+    }
+
+    #[inline(always)]
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        self.with(|alloc| alloc.allocate_zeroed(layout))
+    }
+
+    #[inline(always)]
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let _guard = self.lock();
+        // Anything allocated came from a ready allocator, so it must still
+        // be ready.
+        if let State::Ready(alloc) = &*self.state.get() {
+            alloc.deallocate(NonNull::new_unchecked(ptr), layout);
+        }
+    }
+
+    unsafe fn realloc(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+        new_size: usize,
+    ) -> *mut u8 {
+        let Ok(new_layout) = Layout::from_size_align(new_size, layout.align())
+        else {
+            return ptr::null_mut();
+        };
+        let ptr = NonNull::new_unchecked(ptr);
+        self.with(|alloc| {
+            let result = if new_size >= layout.size() {
+                alloc.grow(ptr, layout, new_layout)
+            } else {
+                alloc.shrink(ptr, layout, new_layout)
+            };
+            // Some allocators, like FixedBufferAllocator, can only resize in
+            // place, but realloc is allowed to move memory.
+            result.or_else(|_| {
+                let new_ptr = alloc.allocate(new_layout)?;
+                ptr::copy_nonoverlapping(
+                    ptr.as_ptr(),
+                    new_ptr.as_ptr() as *mut u8,
+                    layout.size().min(new_size),
+                );
+                alloc.deallocate(ptr, layout);
+                Ok(new_ptr)
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc::{FixedBufferAllocator, Malloc, Mallocator};
+    use std::thread;
+
+    fn layout(size: usize, align: usize) -> Layout {
+        Layout::from_size_align(size, align).unwrap()
+    }
+
+    #[test]
+    fn global_adapter_works_across_threads() {
+        static GLOBAL: GlobalAdapter<&Mallocator> = GlobalAdapter::new(Malloc);
+        let threads = (0..8)
+            .map(|i| {
+                thread::spawn(move || {
+                    for j in 0..1000usize {
+                        let size = (i * 1000 + j) % 500 + 1;
+                        unsafe {
+                            let ptr = GLOBAL.alloc(layout(size, 8));
+                            assert!(!ptr.is_null());
+                            ptr.write_bytes(i as u8, size);
+                            let ptr =
+                                GLOBAL.realloc(ptr, layout(size, 8), 1000);
+                            assert!(!ptr.is_null());
+                            assert_eq!(ptr.add(size - 1).read(), i as u8);
+                            GLOBAL.dealloc(ptr, layout(1000, 8));
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn global_adapter_lazy() {
+        static GLOBAL: GlobalAdapter<FixedBufferAllocator> =
+            GlobalAdapter::lazy(|| {
+                static mut BUFFER: [u8; 256] = [0; 256];
+                #[allow(static_mut_refs)]
+                Ok(FixedBufferAllocator::from_slice(unsafe { &mut BUFFER }))
+            });
+        unsafe {
+            let a = GLOBAL.alloc_zeroed(layout(200, 1));
+            assert!(!a.is_null());
+            assert_eq!(a.add(199).read(), 0);
+            assert!(GLOBAL.alloc(layout(100, 1)).is_null());
+            let a = GLOBAL.realloc(a, layout(200, 1), 50);
+            assert!(!GLOBAL.alloc(layout(100, 1)).is_null());
+            assert!(!a.is_null());
+        }
+    }
+
+    #[test]
+    fn global_adapter_realloc_moves() {
+        let global = GlobalAdapter::virtual_memory();
+        unsafe {
+            let a = global.alloc(layout(100, 8));
+            let b = global.alloc(layout(100, 8));
+            assert!(!a.is_null() && !b.is_null());
+            a.write_bytes(3, 100);
+            // a isn't the last allocation, so it can't grow in place.
+            let c = global.realloc(a, layout(100, 8), 200);
+            assert!(!c.is_null());
+            assert_ne!(a, c);
+            assert_eq!(c.add(99).read(), 3);
+            global.dealloc(b, layout(100, 8));
+            global.dealloc(c, layout(200, 8));
+        }
+    }
+
+    #[test]
+    fn global_adapter_init_failure() {
+        let global: GlobalAdapter<&Mallocator> =
+            GlobalAdapter::lazy(|| Err(AllocError));
+        assert!(unsafe { global.alloc(layout(8, 8)) }.is_null());
+        assert!(unsafe { global.alloc(layout(8, 8)) }.is_null());
+    }
+
+    #[test]
+    fn global_adapter_virtual_memory() {
+        let global = GlobalAdapter::virtual_memory();
+        unsafe {
+            let a = global.alloc(layout(100, 16));
+            assert!(!a.is_null());
+            assert_eq!(a.addr() % 16, 0);
+            a.write_bytes(1, 100);
+            let a = global.realloc(a, layout(100, 16), 1 << 20);
+            assert!(!a.is_null());
+            assert_eq!(a.add(99).read(), 1);
+            global.dealloc(a, layout(1 << 20, 16));
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    ptr::{self, NonNull},
+};
+
+use super::vmem::internal;
+
+/// An allocator which gives every allocation its own pages, placed directly
+/// against an inaccessible guard page, so that reading or writing past the
+/// end of an allocation crashes the program right away.
+///
+/// Freed memory is made inaccessible instead of being unmapped, and its
+/// address is never reused, so any use after free crashes too. This makes the
+/// [GuardAllocator] very slow and wasteful, and it should only be used to
+/// debug unsafe code.
+///
+/// By default, allocations end exactly at a guard page, catching overflows.
+/// [GuardAllocator::left_aligned] places them directly after a guard page
+/// instead, catching underflows. Either way, the alignment of the allocation
+/// can leave a few bytes of padding on the other end, where out of bounds
+/// accesses aren't caught.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GuardAllocator {
+    left_aligned: bool,
+}
+
+impl GuardAllocator {
+    /// Returns a [GuardAllocator] which places allocations right before a
+    /// guard page, catching overflows.
+    #[inline(always)]
+    pub const fn new() -> GuardAllocator {
+        GuardAllocator {
+            left_aligned: false,
+        }
+    }
+
+    /// Returns a [GuardAllocator] which places allocations right after a
+    /// guard page, catching underflows.
+    #[inline(always)]
+    pub const fn left_aligned() -> GuardAllocator {
+        GuardAllocator { left_aligned: true }
+    }
+}
+
+/// Returns the range of pages an allocation at `addr` with the size given
+/// uses, including the guard pages on either side.
+#[inline(always)]
+fn pages(addr: usize, size: usize, page: usize) -> (usize, usize) {
+    let start = (addr / page) * page - page;
+    let end = (addr + size).next_multiple_of(page) + page;
+    (start, end)
+}
+
+unsafe impl Allocator for GuardAllocator {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let page = internal::page_size();
+        // Alignments larger than a page need some slack to align within,
+        // which is unmapped again once the allocation has been placed.
+        let slack = if layout.align() > page {
+            layout.align()
+        } else {
+            0
+        };
+        let len = layout
+            .size()
+            .checked_next_multiple_of(page)
+            .and_then(|x| x.checked_add(2 * page + slack))
+            .ok_or(AllocError)?;
+
+        let base = unsafe { internal::virtual_memory_reserve(len)? };
+        let addr = if self.left_aligned {
+            (base.addr() + page).next_multiple_of(layout.align())
+        } else {
+            let end = base.addr() + len - page - layout.size();
+            end & !(layout.align() - 1)
+        };
+
+        let (start, end) = pages(addr, layout.size(), page);
+        unsafe {
+            if start > base.addr() {
+                internal::virtual_memory_free(base, start - base.addr());
+            }
+            if end < base.addr() + len {
+                internal::virtual_memory_free(
+                    base.with_addr(end),
+                    base.addr() + len - end,
+                );
+            }
+        }
+
+        let data = base.with_addr(start + page);
+        let data_len = end - start - 2 * page;
+        if data_len > 0 {
+            if let Err(e) =
+                unsafe { internal::virtual_memory_commit(data, data_len) }
+            {
+                unsafe {
+                    internal::virtual_memory_free(
+                        base.with_addr(start),
+                        end - start,
+                    )
+                };
+                return Err(e);
+            }
+        }
+
+        let ptr = base.with_addr(addr);
+        Ok(unsafe {
+            NonNull::new_unchecked(ptr::slice_from_raw_parts_mut(
+                ptr,
+                layout.size(),
+            ))
+        })
+    }
+
+    #[inline(always)]
+    fn allocate_zeroed(
+        &self,
+        layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        // Freshly mapped pages are always zeroed.
+        self.allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        let page = internal::page_size();
+        let (start, end) = pages(ptr.addr().get(), layout.size(), page);
+        let data_len = end - start - 2 * page;
+        if data_len > 0 {
+            // The pages stay reserved so their addresses are never reused,
+            // and anything still pointing into them crashes when used.
+            internal::virtual_memory_decommit(
+                ptr.as_ptr().with_addr(start + page),
+                data_len,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::array::Array;
+    use core::ffi::c_int;
+    use std::os::{fd::AsRawFd, unix::net::UnixStream};
+
+    #[link(name = "c")]
+    extern "C" {
+        fn write(fd: c_int, buf: *const u8, count: usize) -> isize;
+    }
+
+    fn layout(size: usize, align: usize) -> Layout {
+        Layout::from_size_align(size, align).unwrap()
+    }
+
+    /// Returns whether the byte at `ptr` can be read, without crashing if it
+    /// can't. The kernel returns EFAULT instead of faulting when given a
+    /// buffer it can't read.
+    fn readable(ptr: *const u8) -> bool {
+        let (a, _b) = UnixStream::pair().unwrap();
+        unsafe { write(a.as_raw_fd(), ptr, 1) == 1 }
+    }
+
+    #[test]
+    fn guard_overflow_hits_guard_page() {
+        for (size, align) in [(1, 1), (13, 4), (4096, 8), (5000, 16)] {
+            let alloc = GuardAllocator::new();
+            let a = alloc.allocate(layout(size, align)).unwrap();
+            let ptr = a.cast::<u8>().as_ptr();
+            assert!(ptr.addr() % align == 0);
+            unsafe { ptr.write_bytes(0xAA, size) };
+            assert!(readable(ptr));
+            assert!(readable(ptr.wrapping_add(size - 1)));
+            // Padding is only ever needed to align the start.
+            let end = size.next_multiple_of(align);
+            assert!(!readable(ptr.wrapping_add(end)));
+            unsafe { alloc.deallocate(a.cast(), layout(size, align)) };
+        }
+    }
+
+    #[test]
+    fn guard_underflow_hits_guard_page() {
+        let alloc = GuardAllocator::left_aligned();
+        let a = alloc.allocate(layout(100, 8)).unwrap();
+        let ptr = a.cast::<u8>().as_ptr();
+        assert!(readable(ptr));
+        assert!(readable(ptr.wrapping_add(99)));
+        assert!(!readable(ptr.wrapping_sub(1)));
+        unsafe { alloc.deallocate(a.cast(), layout(100, 8)) };
+    }
+
+    #[test]
+    fn guard_large_alignment() {
+        let page = internal::page_size();
+        for alloc in [GuardAllocator::new(), GuardAllocator::left_aligned()] {
+            let a = alloc.allocate(layout(100, page * 4)).unwrap();
+            let ptr = a.cast::<u8>().as_ptr();
+            assert!(ptr.addr() % (page * 4) == 0);
+            assert!(readable(ptr) && readable(ptr.wrapping_add(99)));
+            unsafe { alloc.deallocate(a.cast(), layout(100, page * 4)) };
+            assert!(!readable(ptr));
+        }
+    }
+
+    #[test]
+    fn guard_use_after_free() {
+        let alloc = GuardAllocator::new();
+        let a = alloc.allocate_zeroed(layout(64, 8)).unwrap();
+        let ptr = a.cast::<u8>().as_ptr();
+        assert_eq!(unsafe { ptr.add(63).read() }, 0);
+        unsafe { alloc.deallocate(a.cast(), layout(64, 8)) };
+        assert!(!readable(ptr));
+
+        // Addresses are never handed out again.
+        let b = alloc.allocate(layout(64, 8)).unwrap();
+        assert_ne!(b.cast::<u8>().as_ptr(), ptr);
+        unsafe { alloc.deallocate(b.cast(), layout(64, 8)) };
+    }
+
+    #[test]
+    fn guard_zero_sized() {
+        let alloc = GuardAllocator::new();
+        let a = alloc.allocate(layout(0, 1)).unwrap();
+        assert_eq!(a.len(), 0);
+        assert!(!readable(a.cast::<u8>().as_ptr()));
+        unsafe { alloc.deallocate(a.cast(), layout(0, 1)) };
+    }
+
+    #[test]
+    fn guard_array() {
+        let alloc = GuardAllocator::new();
+        let mut array = Array::new(&alloc);
+        for i in 0..1000u64 {
+            array.push(i).unwrap();
+        }
+        assert_eq!(array.iter().sum::<u64>(), 999 * 1000 / 2);
+        let end = array.as_ptr().wrapping_add(array.capacity());
+        assert!(!readable(end.cast()));
+    }
+}
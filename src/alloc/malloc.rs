@@ -3,7 +3,6 @@ use core::{
     ffi::c_int,
     mem,
     ptr::{self, NonNull},
-    slice,
 };
 
 #[link(name = "c")]
@@ -13,10 +12,23 @@ extern "C" {
         alignment: usize,
         size: usize,
     ) -> c_int;
+    fn calloc(nmemb: usize, size: usize) -> *mut u8;
+    fn realloc(ptr: *mut u8, size: usize) -> *mut u8;
     fn free(ptr: *mut u8);
+    #[cfg(target_os = "linux")]
+    fn malloc_usable_size(ptr: *mut u8) -> usize;
+    #[cfg(target_os = "macos")]
+    fn malloc_size(ptr: *const u8) -> usize;
 }
 
+/// The alignment every pointer returned by `malloc`, `calloc` and `realloc`
+/// has, at least for allocations of at least that many bytes.
+const MIN_ALIGN: usize = 2 * mem::size_of::<usize>();
+
 /// An [Allocator] implementation using the system C allocator.
+///
+/// The slices returned cover all the memory the C allocator actually reserved,
+/// which is often a bit more than what was requested.
 pub struct Mallocator {
     _marker: (),
 }
@@ -30,6 +42,54 @@ pub const Malloc: &Mallocator = &INTERNAL_MALLOC;
 unsafe impl Send for Mallocator {}
 unsafe impl Sync for Mallocator {}
 
+/// Returns true if memory with the layout given can come from `calloc` or
+/// `realloc`, which don't take an alignment.
+#[inline(always)]
+fn malloc_aligned(layout: alloc::Layout) -> bool {
+    layout.align() <= MIN_ALIGN && layout.align() <= layout.size()
+}
+
+/// Returns the slice of usable memory at `ptr`, which was just allocated with
+/// at least `size` bytes, or an error if `ptr` is null.
+#[inline(always)]
+unsafe fn usable(
+    ptr: *mut u8,
+    size: usize,
+) -> Result<NonNull<[u8]>, AllocError> {
+    if ptr.is_null() {
+        return Err(AllocError);
+    }
+
+    #[cfg(target_os = "linux")]
+    let size = malloc_usable_size(ptr).max(size);
+    #[cfg(target_os = "macos")]
+    let size = malloc_size(ptr).max(size);
+
+    Ok(NonNull::new_unchecked(ptr::slice_from_raw_parts_mut(
+        ptr, size,
+    )))
+}
+
+impl Mallocator {
+    /// Moves the memory at `ptr` into a new allocation, for when `realloc`
+    /// can't be used.
+    unsafe fn reallocate(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: alloc::Layout,
+        new_layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let new_ptr = self.allocate(new_layout)?;
+        ptr::copy_nonoverlapping(
+            ptr.as_ptr(),
+            new_ptr.as_ptr() as *mut u8,
+            old_layout.size().min(new_layout.size()),
+        );
+        free(ptr.as_ptr());
+        Ok(new_ptr)
+    }
+}
+
 unsafe impl Allocator for Mallocator {
     #[inline(always)]
     fn allocate(
@@ -46,36 +106,156 @@ unsafe impl Allocator for Mallocator {
             return Err(AllocError);
         }
 
-        unsafe {
-            Ok(NonNull::new_unchecked(slice::from_raw_parts_mut(
-                memptr,
-                layout.size(),
-            )))
+        unsafe { usable(memptr, layout.size()) }
+    }
+
+    #[inline(always)]
+    fn allocate_zeroed(
+        &self,
+        layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if malloc_aligned(layout) {
+            return unsafe { usable(calloc(1, layout.size()), layout.size()) };
         }
+
+        let result = self.allocate(layout)?;
+        unsafe { result.cast::<u8>().write_bytes(0, result.len()) };
+        Ok(result)
     }
 
     #[inline(always)]
     unsafe fn deallocate(&self, ptr: NonNull<u8>, _: alloc::Layout) {
         free(ptr.as_ptr());
     }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: alloc::Layout,
+        new_layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if old_layout.align() != new_layout.align()
+            || !malloc_aligned(new_layout)
+        {
+            return self.reallocate(ptr, old_layout, new_layout);
+        }
+
+        usable(realloc(ptr.as_ptr(), new_layout.size()), new_layout.size())
+    }
+
+    unsafe fn grow_zeroed(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: alloc::Layout,
+        new_layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let result = self.grow(ptr, old_layout, new_layout)?;
+        result
+            .cast::<u8>()
+            .add(old_layout.size())
+            .write_bytes(0, result.len() - old_layout.size());
+        Ok(result)
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: alloc::Layout,
+        new_layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if old_layout.align() != new_layout.align()
+            || !malloc_aligned(new_layout)
+        {
+            return self.reallocate(ptr, old_layout, new_layout);
+        }
+
+        usable(realloc(ptr.as_ptr(), new_layout.size()), new_layout.size())
+    }
 }
 
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
-
-//     fn layout(size: usize, align: usize) -> alloc::Layout {
-//         alloc::Layout::from_size_align(size, align).unwrap()
-//     }
-
-//     /// The purpose of this test is not to evaluate if the system's malloc/free
-//     /// work, rather, its just to ensure we can use the functions successfully.
-//     #[test]
-//     fn malloc_works() {
-//         let result = Malloc.allocate(layout(1, 1));
-//         assert!(result.is_ok());
-//         let alloc = result.unwrap();
-//         assert_eq!(alloc.len(), 1);
-//         unsafe { Malloc.deallocate(alloc.cast(), layout(1, 1)) };
-//     }
-// }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::array::Array;
+
+    fn layout(size: usize, align: usize) -> alloc::Layout {
+        alloc::Layout::from_size_align(size, align).unwrap()
+    }
+
+    /// The purpose of this test is not to evaluate if the system's malloc/free
+    /// work, rather, its just to ensure we can use the functions successfully.
+    #[test]
+    fn malloc_works() {
+        let result = Malloc.allocate(layout(1, 1));
+        assert!(result.is_ok());
+        let alloc = result.unwrap();
+        assert!(!alloc.is_empty());
+        unsafe { Malloc.deallocate(alloc.cast(), layout(1, 1)) };
+    }
+
+    #[test]
+    fn malloc_grow_and_shrink_keep_contents() {
+        for align in [1, 8, 64, 4096] {
+            let a = Malloc.allocate(layout(16, align)).unwrap();
+            let ptr = a.cast::<u8>();
+            unsafe { ptr.write_bytes(7, 16) };
+
+            let a = unsafe {
+                Malloc.grow(ptr, layout(16, align), layout(4000, align))
+            }
+            .unwrap();
+            assert!(a.len() >= 4000);
+            assert_eq!(a.cast::<u8>().addr().get() % align, 0);
+            let bytes = unsafe { &a.as_ref()[..16] };
+            assert!(bytes.iter().all(|&x| x == 7));
+
+            let a = unsafe {
+                Malloc.shrink(a.cast(), layout(4000, align), layout(8, align))
+            }
+            .unwrap();
+            assert!(a.len() >= 8);
+            assert_eq!(a.cast::<u8>().addr().get() % align, 0);
+            let bytes = unsafe { &a.as_ref()[..8] };
+            assert!(bytes.iter().all(|&x| x == 7));
+            unsafe { Malloc.deallocate(a.cast(), layout(8, align)) };
+        }
+    }
+
+    #[test]
+    fn malloc_zeroed() {
+        for align in [1, 16, 256] {
+            let a = Malloc.allocate_zeroed(layout(100, align)).unwrap();
+            assert!(unsafe { a.as_ref() }.iter().all(|&x| x == 0));
+            unsafe { a.cast::<u8>().write_bytes(1, a.len()) };
+
+            let a = unsafe {
+                Malloc.grow_zeroed(
+                    a.cast(),
+                    layout(100, align),
+                    layout(300, align),
+                )
+            }
+            .unwrap();
+            let bytes = unsafe { a.as_ref() };
+            assert!(bytes[..100].iter().all(|&x| x == 1));
+            assert!(bytes[100..].iter().all(|&x| x == 0));
+            unsafe { Malloc.deallocate(a.cast(), layout(300, align)) };
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn malloc_reports_usable_size() {
+        let a = Malloc.allocate(layout(1, 1)).unwrap();
+        let ptr = a.cast::<u8>().as_ptr();
+        assert_eq!(a.len(), unsafe { malloc_usable_size(ptr) });
+        unsafe { ptr.write_bytes(1, a.len()) };
+        unsafe { Malloc.deallocate(a.cast(), layout(1, 1)) };
+
+        // Arrays use the extra space as capacity.
+        let mut array = Array::new(Malloc);
+        array.push(1u8).unwrap();
+        let ptr = array.as_ptr() as *mut u8;
+        assert_eq!(array.capacity(), unsafe { malloc_usable_size(ptr) });
+    }
+}
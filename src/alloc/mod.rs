@@ -14,6 +14,48 @@
 //! `FixedBufferAllocator` can live longer than it, since once the backing
 //! buffer goes out of scope, the slice memory cannot be relied on.
 //!
+//! Memory can only be freed in the reverse order it was allocated in, but
+//! `FixedBufferAllocator::checkpoint` and `FixedBufferAllocator::scope` make it
+//! easy to free everything allocated after some point at once, which is
+//! perfect for per-request scratch memory.
+//!
+//! `AtomicFixedBufferAllocator` works the same way, but bumps its pointer
+//! with atomic operations instead, so worker threads can all allocate from
+//! one buffer without locking.
+//!
+//! ## `Arena`
+//!
+//! When you want the convenience of a `FixedBufferAllocator` but don't know
+//! how much memory you'll need ahead of time, the `Arena` requests chunks of
+//! memory from some other allocator as it needs them, growing geometrically.
+//! Everything is freed at once when the arena is reset or goes out of scope.
+//!
+//! ## `Buddy`
+//!
+//! When all the memory available is a fixed buffer, but it has to be freed
+//! in any order rather than all at once, the `Buddy` allocator splits the
+//! buffer into power of two sized blocks and merges them back together as
+//! they're freed. Allocations are rounded up to a power of two, so some
+//! memory is wasted, but allocating and freeing are both fast and
+//! predictable.
+//!
+//! ## `Tlsf`
+//!
+//! Code with real-time requirements can't afford an allocator which
+//! sometimes takes much longer than usual. The `Tlsf` allocator manages a
+//! fixed buffer like the `Buddy` allocator, but allocating, freeing and
+//! resizing all take constant time, and less memory is lost to rounding.
+//!
+//! ## Scratch arenas
+//!
+//! Most temporary memory only lives until the function which allocated it
+//! returns. `scratch` returns a guard for a bump allocated arena belonging to
+//! the current thread, which is freed all at once when the outermost guard is
+//! dropped, so no allocator has to be passed around just for temporaries.
+//! Each thread has two, and `scratch_avoiding` picks whichever one a caller's
+//! scratch arena isn't, so a function can return results in its caller's
+//! arena without its own temporaries piling up there too.
+//!
 //! ## `Pool`
 //!
 //! When you have a lot of a specific type whose lifetime is the same, use a
@@ -21,6 +63,25 @@
 //! to each other in memory. If you're frequently accessing these, this will
 //! result in less cache misses and better performance.
 //!
+//! ## `Slab`
+//!
+//! When many objects of different sizes are allocated and freed in no
+//! particular order, like in a long running service, a `Slab` is a general
+//! purpose replacement for `malloc`. Small allocations are sorted into power
+//! of two size classes, each with its own free list, so freed memory is
+//! reused quickly and fragmentation stays predictable. Large allocations go
+//! straight to the backing allocator.
+//!
+//! ## `Fallback` and `Segregator`
+//!
+//! Allocators can be combined to get the best of each. A `Fallback` allocates
+//! from a primary allocator until it runs out, then from a secondary one, so
+//! a `FixedBufferAllocator` over a stack buffer can spill to `Malloc` when the
+//! buffer is too small. It sends memory back to the allocator it came from by
+//! asking the primary allocator whether it `Owns` it. A `Segregator` sends
+//! small allocations to one allocator and large ones to another, based on a
+//! size threshold.
+//!
 //! ## `VirtualMemoryAllocator`
 //!
 //! On modern operating systems, the computer's actual memory is typically
@@ -48,20 +109,81 @@
 //! give you memory locality, and free you from worrying about lifetimes (since
 //! everything could be `'static` if the `VirtualMemoryAllocator` is).
 //!
+//! Very large reservations can use huge pages, be faulted in up front, or be
+//! bound to a NUMA node, with `VirtualMemoryAllocator::builder`, which can
+//! also build an `AtomicVirtualMemoryAllocator` to share between threads.
+//!
 //! ## `Mallocator`
 //!
 //! This is just the libc `malloc`/`free` wrapped up to implement the
 //! `Allocator` trait. Use it whenever you would use the normal `malloc`.
 //!
+//! ## `Tracking`
+//!
+//! Wrapping any other allocator in a `Tracking` allocator counts how many
+//! bytes are live, the peak number of live bytes, and how many allocations of
+//! each size were made. Measuring a real workload like this is the easiest
+//! way to decide how large a `FixedBufferAllocator` or an `Arena`'s first
+//! chunk should be.
+//!
+//! ## `GuardAllocator`
+//!
+//! When unsafe code is reading or writing out of bounds, or using memory
+//! after freeing it, the `GuardAllocator` makes it crash right where the bug
+//! is instead of corrupting something unrelated. Every allocation gets its own
+//! pages next to an inaccessible guard page, and freed pages are made
+//! inaccessible rather than reused. It's very slow and wastes a lot of memory,
+//! so it's only meant for debugging.
+//!
+//! ## `GlobalAdapter`
+//!
+//! Any of these allocators can be installed as the global allocator by
+//! wrapping it in a `GlobalAdapter`, which guards it with a spinlock so it
+//! can be used from every thread. `GlobalAdapter::virtual_memory` is a ready
+//! made global allocator which allocates from a `VirtualMemoryAllocator`,
+//! which is very fast for short-lived programs that don't free much.
+//!
+//! ## `FailingAllocator`
+//!
+//! Code which handles allocation failure is rarely run, so it's rarely
+//! tested. A `FailingAllocator` fails allocations on purpose, either a
+//! specific one, every Nth one, any past a byte budget, or randomly.
+//! `fail_each_allocation` runs some code once for every allocation it makes,
+//! failing each in turn, and panics if anything leaks or is freed twice.
+//!
 
+mod arena;
+mod atomic_fixed_buffer;
+mod buddy;
+mod failing;
+mod fallback;
 mod fixed_buffer;
+mod global;
+mod guard;
 mod malloc;
 mod pool;
+mod scratch;
+mod segregator;
+mod slab;
 mod string;
+mod tlsf;
+mod tracking;
 mod vmem;
 
+pub use arena::*;
+pub use atomic_fixed_buffer::*;
+pub use buddy::*;
+pub use failing::*;
+pub use fallback::*;
 pub use fixed_buffer::*;
+pub use global::*;
+pub use guard::*;
 pub use malloc::*;
 pub use pool::*;
+pub use scratch::*;
+pub use segregator::*;
+pub use slab::*;
 pub use string::*;
+pub use tlsf::*;
+pub use tracking::*;
 pub use vmem::*;
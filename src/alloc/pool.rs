@@ -1,12 +1,353 @@
-use core::{alloc::Allocator, marker::PhantomData};
+use core::{
+    alloc::{self, AllocError, Allocator, Layout},
+    cell::UnsafeCell,
+    marker::PhantomData,
+    mem,
+    ptr::{self, NonNull},
+    slice,
+};
+
+/// The number of slots in the first chunk a [Pool] requests from its backing
+/// allocator. Every chunk after that is twice the size of the previous one.
+const INITIAL_CHUNK_SLOTS: usize = 16;
 
 /// An allocator which allocates objects of only a single type, but does so in
 /// a way that they will all be located in contiguous memory.
 ///
-/// Additionally, memory is only deallocated when the pool goes out of scope.
+/// Objects are carved out of chunks requested from the backing allocator, and
+/// freed objects are placed on an intrusive free list so they can be reused
+/// in `O(1)` time. The chunks themselves are only deallocated when the pool
+/// goes out of scope.
+///
+/// [Allocator] is implemented for any layout which fits in a `T`, meaning a
+/// `Pool` can be handed to containers which only ever allocate one `T` at a
+/// time.
 pub struct Pool<'a, T> {
-    _alloc: &'a dyn Allocator,
+    alloc: &'a dyn Allocator,
+    state: UnsafeCell<State>,
     _marker: PhantomData<T>,
 }
 
-// TODO: implement this
+struct State {
+    /// The most recently freed slot, whose first bytes point to the slot
+    /// freed before it.
+    free: Option<NonNull<FreeSlot>>,
+    /// The most recently allocated chunk.
+    chunks: Option<NonNull<Chunk>>,
+    /// The next slot in the most recent chunk which has never been handed
+    /// out. Once `next == end`, a new chunk has to be allocated.
+    next: *mut u8,
+    end: *mut u8,
+    /// The number of slots the next chunk will contain.
+    next_chunk_slots: usize,
+}
+
+struct FreeSlot {
+    next: Option<NonNull<FreeSlot>>,
+}
+
+/// The header placed at the start of every chunk, used to release the chunks
+/// when the [Pool] is dropped.
+struct Chunk {
+    next: Option<NonNull<Chunk>>,
+    layout: Layout,
+}
+
+impl<'a, T> Pool<'a, T> {
+    /// The layout of a single slot. Slots need to be able to store a
+    /// [FreeSlot] when they aren't in use, so they're at least the size of a
+    /// pointer.
+    const SLOT: Layout = {
+        let size = if mem::size_of::<T>() > mem::size_of::<FreeSlot>() {
+            mem::size_of::<T>()
+        } else {
+            mem::size_of::<FreeSlot>()
+        };
+        let align = if mem::align_of::<T>() > mem::align_of::<FreeSlot>() {
+            mem::align_of::<T>()
+        } else {
+            mem::align_of::<FreeSlot>()
+        };
+        match Layout::from_size_align(size, align) {
+            Ok(layout) => layout.pad_to_align(),
+            Err(_) => panic!("invalid slot layout"),
+        }
+    };
+
+    /// Creates a new empty [Pool] which requests its memory from `alloc`.
+    ///
+    /// No memory is allocated until the first object is.
+    pub const fn new(alloc: &'a impl Allocator) -> Pool<'a, T> {
+        Pool {
+            alloc,
+            state: UnsafeCell::new(State {
+                free: None,
+                chunks: None,
+                next: ptr::null_mut(),
+                end: ptr::null_mut(),
+                next_chunk_slots: INITIAL_CHUNK_SLOTS,
+            }),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Moves `value` into a slot in the pool, returning a reference to it.
+    ///
+    /// Returns an error if a new chunk was needed and the backing allocator
+    /// failed to allocate it.
+    // Each call hands out a different slot, so the returned references never
+    // alias each other.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc(&self, value: T) -> Result<&mut T, AllocError> {
+        let slot = self.allocate_slot()?.cast::<T>();
+        // SAFETY: the slot is large enough and aligned for a T, and nothing
+        //         else references it until it's freed.
+        unsafe {
+            slot.as_ptr().write(value);
+            Ok(&mut *slot.as_ptr())
+        }
+    }
+
+    /// Drops the value referenced by `value` and returns its slot to the pool.
+    ///
+    /// # Safety
+    ///
+    /// Behavior is undefined if any of the following conditions are violated:
+    ///
+    /// - `value` must have been returned by [Pool::alloc] on this pool.
+    /// - `value` must not be used after calling this function.
+    pub unsafe fn free(&self, value: &mut T) {
+        let slot = NonNull::from(value);
+        ptr::drop_in_place(slot.as_ptr());
+        self.free_slot(slot.cast());
+    }
+
+    /// Returns true if `layout` can be stored in one of the pool's slots.
+    #[inline(always)]
+    fn fits(layout: Layout) -> bool {
+        layout.size() <= mem::size_of::<T>()
+            && layout.align() <= mem::align_of::<T>()
+    }
+
+    fn allocate_slot(&self) -> Result<NonNull<u8>, AllocError> {
+        // SAFETY: the pool is !Sync and nothing holds a reference to the
+        //         state across calls.
+        let state = unsafe { &mut *self.state.get() };
+
+        if let Some(slot) = state.free {
+            // SAFETY: every slot on the free list had a FreeSlot written to
+            //         it when it was freed.
+            state.free = unsafe { slot.as_ref().next };
+            return Ok(slot.cast());
+        }
+
+        if ptr::eq(state.next, state.end) {
+            self.allocate_chunk(state)?;
+        }
+
+        // SAFETY: allocate_chunk guarantees there is at least one slot
+        //         between next and end.
+        unsafe {
+            let slot = NonNull::new_unchecked(state.next);
+            state.next = state.next.add(Self::SLOT.size());
+            Ok(slot)
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `slot` must have been returned by [Pool::allocate_slot] on this pool
+    /// and must not be in use.
+    unsafe fn free_slot(&self, slot: NonNull<u8>) {
+        let state = &mut *self.state.get();
+        let slot = slot.cast::<FreeSlot>();
+        slot.as_ptr().write(FreeSlot { next: state.free });
+        state.free = Some(slot);
+    }
+
+    fn allocate_chunk(&self, state: &mut State) -> Result<(), AllocError> {
+        let slots = state.next_chunk_slots;
+        let (slots_layout, _) =
+            Self::SLOT.repeat(slots).map_err(|_| AllocError)?;
+        let (layout, offset) = Layout::new::<Chunk>()
+            .extend(slots_layout)
+            .map_err(|_| AllocError)?;
+        let layout = layout.pad_to_align();
+
+        let mem = self.alloc.allocate(layout)?.cast::<u8>();
+        let chunk = mem.cast::<Chunk>();
+        // SAFETY: the memory was just allocated using a layout which starts
+        //         with a Chunk and is followed by `slots` slots at `offset`.
+        unsafe {
+            chunk.as_ptr().write(Chunk {
+                next: state.chunks,
+                layout,
+            });
+            state.next = mem.as_ptr().add(offset);
+            state.end = state.next.add(slots_layout.size());
+        }
+        state.chunks = Some(chunk);
+        state.next_chunk_slots = slots.saturating_mul(2);
+        Ok(())
+    }
+}
+
+impl<T> Drop for Pool<'_, T> {
+    /// Releases every chunk back to the backing allocator. Objects which are
+    /// still allocated in the pool are not dropped.
+    fn drop(&mut self) {
+        let mut chunk = self.state.get_mut().chunks;
+        while let Some(c) = chunk {
+            // SAFETY: every chunk in the list was allocated by self.alloc
+            //         with the layout stored in its header.
+            unsafe {
+                let Chunk { next, layout } = c.as_ptr().read();
+                self.alloc.deallocate(c.cast(), layout);
+                chunk = next;
+            }
+        }
+    }
+}
+
+unsafe impl<T> Allocator for Pool<'_, T> {
+    fn allocate(
+        &self,
+        layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if !Self::fits(layout) {
+            return Err(AllocError);
+        }
+
+        let slot = self.allocate_slot()?;
+        // SAFETY: the slot is valid for SLOT.size() bytes.
+        unsafe {
+            Ok(NonNull::new_unchecked(slice::from_raw_parts_mut(
+                slot.as_ptr(),
+                mem::size_of::<T>(),
+            )))
+        }
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, _: alloc::Layout) {
+        self.free_slot(ptr)
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        _: alloc::Layout,
+        new_layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        // Every slot is the size of a T, so the only way to grow is if the
+        // new layout still fits in the slot we already have.
+        if !Self::fits(new_layout) {
+            return Err(AllocError);
+        }
+
+        Ok(NonNull::new_unchecked(slice::from_raw_parts_mut(
+            ptr.as_ptr(),
+            mem::size_of::<T>(),
+        )))
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        _: alloc::Layout,
+        new_layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if !Self::fits(new_layout) {
+            return Err(AllocError);
+        }
+
+        Ok(NonNull::new_unchecked(slice::from_raw_parts_mut(
+            ptr.as_ptr(),
+            mem::size_of::<T>(),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc::FixedBufferAllocator;
+
+    fn layout(size: usize, align: usize) -> alloc::Layout {
+        alloc::Layout::from_size_align(size, align).unwrap()
+    }
+
+    #[test]
+    fn pool_alloc_returns_distinct_values() {
+        let mut buffer = [0u8; 1024];
+        let fba = FixedBufferAllocator::from_slice(&mut buffer);
+        let pool = Pool::<u64>::new(&fba);
+
+        let a = pool.alloc(1).unwrap() as *mut u64;
+        let b = pool.alloc(2).unwrap() as *mut u64;
+        assert_ne!(a, b);
+        assert_eq!(unsafe { *a }, 1);
+        assert_eq!(unsafe { *b }, 2);
+    }
+
+    #[test]
+    fn pool_free_reuses_slot() {
+        let mut buffer = [0u8; 1024];
+        let fba = FixedBufferAllocator::from_slice(&mut buffer);
+        let pool = Pool::<u64>::new(&fba);
+
+        let a = pool.alloc(1).unwrap();
+        let addr = a as *mut u64;
+        unsafe { pool.free(a) };
+        let b = pool.alloc(2).unwrap();
+        assert_eq!(b as *mut u64, addr);
+    }
+
+    #[test]
+    fn pool_grows_past_first_chunk() {
+        let mut buffer = [0u8; 4096];
+        let fba = FixedBufferAllocator::from_slice(&mut buffer);
+        let pool = Pool::<u32>::new(&fba);
+
+        let values = (0..INITIAL_CHUNK_SLOTS as u32 * 3)
+            .map(|i| pool.alloc(i).unwrap() as *mut u32)
+            .collect::<Vec<_>>();
+        for (i, value) in values.iter().enumerate() {
+            assert_eq!(unsafe { **value }, i as u32);
+        }
+    }
+
+    #[test]
+    fn pool_alloc_err_when_backing_allocator_full() {
+        let mut buffer = [0u8; 8];
+        let fba = FixedBufferAllocator::from_slice(&mut buffer);
+        let pool = Pool::<u64>::new(&fba);
+        assert!(pool.alloc(1).is_err());
+    }
+
+    #[test]
+    fn pool_allocator_rejects_other_layouts() {
+        let mut buffer = [0u8; 1024];
+        let fba = FixedBufferAllocator::from_slice(&mut buffer);
+        let pool = Pool::<u32>::new(&fba);
+
+        assert!(pool.allocate(layout(4, 4)).is_ok());
+        assert!(pool.allocate(layout(2, 2)).is_ok());
+        assert!(pool.allocate(layout(8, 4)).is_err());
+        assert!(pool.allocate(layout(4, 8)).is_err());
+    }
+
+    #[test]
+    fn pool_drops_freed_values() {
+        use std::rc::Rc;
+
+        let mut buffer = [0u8; 1024];
+        let fba = FixedBufferAllocator::from_slice(&mut buffer);
+        let pool = Pool::<Rc<()>>::new(&fba);
+
+        let rc = Rc::new(());
+        let value = pool.alloc(rc.clone()).unwrap();
+        assert_eq!(Rc::strong_count(&rc), 2);
+        unsafe { pool.free(value) };
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+}
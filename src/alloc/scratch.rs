@@ -0,0 +1,327 @@
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    cell::{Cell, UnsafeCell},
+    ptr::{self, NonNull},
+};
+
+use super::VirtualMemoryAllocator;
+
+/// The amount of address space reserved for each scratch arena. Only the
+/// memory which is actually allocated gets committed.
+#[cfg(target_pointer_width = "64")]
+const SCRATCH_RESERVATION: usize = 8 << 30;
+#[cfg(not(target_pointer_width = "64"))]
+const SCRATCH_RESERVATION: usize = 256 << 20;
+
+/// The number of committed bytes a scratch arena keeps when it's reset.
+/// Anything past this is returned to the OS, so one unusually large scratch
+/// doesn't stay committed for the rest of the thread's life.
+const SCRATCH_RETAIN: usize = 1 << 20;
+
+/// One of the two scratch arenas each thread has.
+struct ScratchArena {
+    /// None until the first [scratch] on this thread, or if reserving the
+    /// memory failed.
+    vmem: UnsafeCell<Option<VirtualMemoryAllocator>>,
+    /// The number of [Scratch] guards currently using this arena.
+    depth: Cell<usize>,
+}
+
+impl ScratchArena {
+    /// Rewinds the arena to the start, once the last [Scratch] using it is
+    /// gone.
+    ///
+    /// # Safety
+    ///
+    /// Nothing can be borrowing the arena's allocator.
+    unsafe fn reset(&self) {
+        if let Some(vmem) = &mut *self.vmem.get() {
+            if vmem.committed() > SCRATCH_RETAIN {
+                vmem.reset();
+            } else {
+                vmem.rewind_to(0);
+            }
+        }
+    }
+}
+
+/// The scratch arenas belonging to a thread.
+struct ScratchArenas {
+    arenas: [NonNull<ScratchArena>; 2],
+}
+
+impl ScratchArenas {
+    fn new() -> ScratchArenas {
+        let arena = || {
+            NonNull::from(Box::leak(Box::new(ScratchArena {
+                vmem: UnsafeCell::new(None),
+                depth: Cell::new(0),
+            })))
+        };
+        ScratchArenas {
+            arenas: [arena(), arena()],
+        }
+    }
+}
+
+impl Drop for ScratchArenas {
+    fn drop(&mut self) {
+        for arena in self.arenas {
+            // A Scratch can outlive the thread local if it's stored in some
+            // other thread local, in which case the arena has to be leaked.
+            if unsafe { arena.as_ref() }.depth.get() == 0 {
+                drop(unsafe { Box::from_raw(arena.as_ptr()) });
+            }
+        }
+    }
+}
+
+thread_local! {
+    static SCRATCH: ScratchArenas = ScratchArenas::new();
+}
+
+/// A guard for one of the current thread's scratch arenas, returned by
+/// [scratch] and [scratch_avoiding].
+///
+/// Scratch arenas are bump allocators backed by a [VirtualMemoryAllocator],
+/// meant for temporary memory which doesn't outlive the function which
+/// allocated it. Guards for the same arena can be nested, and everything
+/// allocated in it is freed at once when the outermost one is dropped.
+pub struct Scratch {
+    arena: NonNull<ScratchArena>,
+}
+
+impl Scratch {
+    fn new(arena: NonNull<ScratchArena>) -> Scratch {
+        // SAFETY: arenas are only freed when the thread exits with no
+        //         guards using them.
+        let a = unsafe { arena.as_ref() };
+        if a.depth.get() == 0 {
+            // SAFETY: there are no other guards, so nothing can be
+            //         borrowing the allocator.
+            let vmem = unsafe { &mut *a.vmem.get() };
+            if vmem.is_none() {
+                *vmem = VirtualMemoryAllocator::new(SCRATCH_RESERVATION).ok();
+            }
+        }
+        a.depth.set(a.depth.get() + 1);
+        Scratch { arena }
+    }
+
+    /// Returns the allocator backing this guard's arena, or an error if its
+    /// memory couldn't be reserved.
+    #[inline(always)]
+    fn vmem(&self) -> Result<&VirtualMemoryAllocator, AllocError> {
+        // SAFETY: the allocator is only mutated when there are no guards.
+        unsafe { &*self.arena.as_ref().vmem.get() }
+            .as_ref()
+            .ok_or(AllocError)
+    }
+
+    /// Returns the number of bytes allocated in this guard's arena, including
+    /// anything allocated through outer guards for the same arena.
+    #[inline(always)]
+    pub fn used(&self) -> usize {
+        self.vmem().map_or(0, |vmem| vmem.used())
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        // SAFETY: this guard is keeping the arena alive.
+        let arena = unsafe { self.arena.as_ref() };
+        let depth = arena.depth.get() - 1;
+        arena.depth.set(depth);
+        if depth == 0 {
+            // SAFETY: allocations borrow the guard they were made through,
+            //         and this was the last one.
+            unsafe { arena.reset() };
+        }
+    }
+}
+
+/// Returns a guard for one of the current thread's scratch arenas.
+///
+/// Everything allocated through the guard is freed once it, and every other
+/// guard for the same arena, have been dropped. A function which is given a
+/// [Scratch] to return its results in should use [scratch_avoiding] instead,
+/// so its temporary memory doesn't end up in the same arena as its results.
+///
+/// ```
+/// use stdx::{alloc, array::Array};
+///
+/// let scratch = alloc::scratch();
+/// let mut numbers = Array::new(&scratch);
+/// numbers.push(1).unwrap();
+/// ```
+pub fn scratch() -> Scratch {
+    SCRATCH.with(|s| Scratch::new(s.arenas[0]))
+}
+
+/// Returns a guard for whichever of the current thread's scratch arenas
+/// `conflict` isn't using.
+///
+/// This lets a function allocate temporary memory without it getting mixed up
+/// with memory it's allocating in a caller's scratch arena, which would keep
+/// the temporary memory alive until the caller is done.
+///
+/// ```
+/// use stdx::{
+///     alloc::{self, Scratch},
+///     array::Array,
+/// };
+///
+/// fn squares(n: u32, out: &Scratch) -> Array<'_, u32> {
+///     let tmp = alloc::scratch_avoiding(out);
+///     let mut numbers = Array::new(&tmp);
+///     numbers.try_extend(0..n).unwrap();
+///
+///     let mut result = Array::new(out);
+///     result.try_extend(numbers.iter().map(|x| x * x)).unwrap();
+///     result
+/// }
+///
+/// let scratch = alloc::scratch();
+/// assert_eq!(squares(4, &scratch), [0, 1, 4, 9]);
+/// ```
+pub fn scratch_avoiding(conflict: &Scratch) -> Scratch {
+    SCRATCH.with(|s| {
+        let arena = if ptr::eq(s.arenas[0].as_ptr(), conflict.arena.as_ptr()) {
+            s.arenas[1]
+        } else {
+            s.arenas[0]
+        };
+        Scratch::new(arena)
+    })
+}
+
+unsafe impl Allocator for Scratch {
+    #[inline(always)]
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        self.vmem()?.allocate(layout)
+    }
+
+    #[inline(always)]
+    fn allocate_zeroed(
+        &self,
+        layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        self.vmem()?.allocate_zeroed(layout)
+    }
+
+    #[inline(always)]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if let Ok(vmem) = self.vmem() {
+            vmem.deallocate(ptr, layout)
+        }
+    }
+
+    #[inline(always)]
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        self.vmem()?.grow(ptr, old_layout, new_layout)
+    }
+
+    #[inline(always)]
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        self.vmem()?.shrink(ptr, old_layout, new_layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::array::Array;
+    use std::thread;
+
+    fn layout(size: usize, align: usize) -> Layout {
+        Layout::from_size_align(size, align).unwrap()
+    }
+
+    #[test]
+    fn scratch_resets_when_outermost_guard_drops() {
+        let outer = scratch();
+        outer.allocate(layout(100, 8)).unwrap();
+        {
+            let inner = scratch();
+            inner.allocate(layout(100, 8)).unwrap();
+            assert!(inner.used() >= 200);
+        }
+        // The outer guard still needs its memory.
+        assert!(outer.used() >= 200);
+        drop(outer);
+        assert_eq!(scratch().used(), 0);
+    }
+
+    #[test]
+    fn scratch_avoiding_uses_the_other_arena() {
+        let a = scratch();
+        let b = scratch_avoiding(&a);
+        let c = scratch_avoiding(&b);
+        assert!(!ptr::eq(a.arena.as_ptr(), b.arena.as_ptr()));
+        assert!(ptr::eq(a.arena.as_ptr(), c.arena.as_ptr()));
+
+        a.allocate(layout(64, 8)).unwrap();
+        b.allocate(layout(1000, 8)).unwrap();
+        drop(b);
+        // b's arena was reset without touching a's.
+        assert!(a.used() >= 64 && a.used() < 1000);
+        assert_eq!(scratch_avoiding(&a).used(), 0);
+    }
+
+    #[test]
+    fn scratch_works_with_containers() {
+        let out = scratch();
+        let mut result = Array::new(&out);
+        {
+            let tmp = scratch_avoiding(&out);
+            let mut numbers = Array::new(&tmp);
+            numbers.try_extend(0..1000u32).unwrap();
+            result.try_extend(numbers.iter().map(|x| x * 2)).unwrap();
+        }
+        assert_eq!(result.len(), 1000);
+        assert_eq!(result[999], 1998);
+    }
+
+    #[test]
+    fn scratch_grows_interleaved_allocations() {
+        let s = scratch();
+        let mut a = Array::new(&s);
+        let mut b = Array::new(&s);
+        for i in 0..1000u32 {
+            a.push(i).unwrap();
+            b.push(i * 2).unwrap();
+        }
+        assert_eq!(a[999], 999);
+        assert_eq!(b[999], 1998);
+    }
+
+    #[test]
+    fn scratch_is_per_thread() {
+        let scratch = scratch();
+        scratch.allocate(layout(100, 8)).unwrap();
+        thread::spawn(|| assert_eq!(super::scratch().used(), 0))
+            .join()
+            .unwrap();
+    }
+
+    #[test]
+    fn scratch_returns_large_memory_on_reset() {
+        let s = scratch();
+        let a = s.allocate(layout(4 * SCRATCH_RETAIN, 8)).unwrap();
+        unsafe { a.cast::<u8>().write_bytes(1, a.len()) };
+        let arena = s.arena;
+        drop(s);
+        let vmem = unsafe { &*arena.as_ref().vmem.get() }.as_ref().unwrap();
+        assert_eq!(vmem.committed(), 0);
+    }
+}
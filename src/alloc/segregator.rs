@@ -0,0 +1,265 @@
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    ptr::{self, NonNull},
+};
+
+use super::Owns;
+
+/// An allocator which sends allocations of at most `N` bytes to one
+/// allocator, and larger ones to another.
+///
+/// Since the size of an allocation is always known when it's freed, memory is
+/// always returned to the allocator it came from. Resizing an allocation past
+/// `N` bytes in either direction moves it to the other allocator.
+///
+/// Memory from the small allocator is never reported as being larger than `N`
+/// bytes, even if the small allocator had more to give, since callers are
+/// allowed to free it with any size up to the one reported.
+///
+/// ```
+/// use stdx::alloc::{Malloc, Segregator, Slab};
+///
+/// // Small objects come from a slab, and anything over 256 bytes goes
+/// // straight to malloc.
+/// let alloc = Segregator::<256, _, _>::new(Slab::new(Malloc), Malloc);
+/// ```
+pub struct Segregator<const N: usize, Small: Allocator, Large: Allocator> {
+    small: Small,
+    large: Large,
+}
+
+impl<const N: usize, Small: Allocator, Large: Allocator>
+    Segregator<N, Small, Large>
+{
+    /// Returns a [Segregator] which allocates memory of at most `N` bytes
+    /// from `small`, and anything larger from `large`.
+    #[inline(always)]
+    pub const fn new(
+        small: Small,
+        large: Large,
+    ) -> Segregator<N, Small, Large> {
+        Segregator { small, large }
+    }
+
+    /// Returns the allocator used for allocations of at most `N` bytes.
+    #[inline(always)]
+    pub fn small(&self) -> &Small {
+        &self.small
+    }
+
+    /// Returns the allocator used for allocations larger than `N` bytes.
+    #[inline(always)]
+    pub fn large(&self) -> &Large {
+        &self.large
+    }
+
+    /// Cuts memory from the small allocator down to at most `N` bytes, so that
+    /// freeing or resizing it with the size returned still sends it back to
+    /// the small allocator.
+    #[inline(always)]
+    fn clamp(ptr: NonNull<[u8]>) -> NonNull<[u8]> {
+        NonNull::slice_from_raw_parts(ptr.cast(), ptr.len().min(N))
+    }
+
+    /// Moves memory from one of the allocators to the other.
+    unsafe fn reallocate(
+        from: &dyn Allocator,
+        to: &dyn Allocator,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let new_ptr = to.allocate(new_layout)?;
+        ptr::copy_nonoverlapping(
+            ptr.as_ptr(),
+            new_ptr.as_ptr() as *mut u8,
+            old_layout.size().min(new_layout.size()),
+        );
+        from.deallocate(ptr, old_layout);
+        Ok(new_ptr)
+    }
+}
+
+unsafe impl<const N: usize, Small: Allocator, Large: Allocator> Allocator
+    for Segregator<N, Small, Large>
+{
+    #[inline(always)]
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if layout.size() <= N {
+            self.small.allocate(layout).map(Self::clamp)
+        } else {
+            self.large.allocate(layout)
+        }
+    }
+
+    #[inline(always)]
+    fn allocate_zeroed(
+        &self,
+        layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if layout.size() <= N {
+            self.small.allocate_zeroed(layout).map(Self::clamp)
+        } else {
+            self.large.allocate_zeroed(layout)
+        }
+    }
+
+    #[inline(always)]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() <= N {
+            self.small.deallocate(ptr, layout)
+        } else {
+            self.large.deallocate(ptr, layout)
+        }
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        match (old_layout.size() <= N, new_layout.size() <= N) {
+            (true, true) => self
+                .small
+                .grow(ptr, old_layout, new_layout)
+                .map(Self::clamp),
+            (false, false) => self.large.grow(ptr, old_layout, new_layout),
+            _ => Self::reallocate(
+                &self.small,
+                &self.large,
+                ptr,
+                old_layout,
+                new_layout,
+            ),
+        }
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        match (old_layout.size() <= N, new_layout.size() <= N) {
+            (true, true) => self
+                .small
+                .shrink(ptr, old_layout, new_layout)
+                .map(Self::clamp),
+            (false, false) => self.large.shrink(ptr, old_layout, new_layout),
+            _ => Self::reallocate(
+                &self.large,
+                &self.small,
+                ptr,
+                old_layout,
+                new_layout,
+            )
+            .map(Self::clamp),
+        }
+    }
+}
+
+impl<const N: usize, Small: Owns, Large: Owns> Owns
+    for Segregator<N, Small, Large>
+{
+    #[inline(always)]
+    fn owns(&self, ptr: NonNull<u8>, layout: Layout) -> bool {
+        if layout.size() <= N {
+            self.small.owns(ptr, layout)
+        } else {
+            self.large.owns(ptr, layout)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        alloc::{Fallback, FixedBufferAllocator, LeakCheck, Malloc, Slab},
+        array::Array,
+    };
+
+    fn layout(size: usize, align: usize) -> Layout {
+        Layout::from_size_align(size, align).unwrap()
+    }
+
+    #[test]
+    fn segregator_dispatches_by_size() {
+        let alloc =
+            Segregator::<64, _, _>::new(LeakCheck::new(), LeakCheck::new());
+        let a = alloc.allocate(layout(64, 8)).unwrap();
+        let b = alloc.allocate(layout(65, 8)).unwrap();
+        assert_eq!(alloc.small().live(), 1);
+        assert_eq!(alloc.large().live(), 1);
+        unsafe {
+            alloc.deallocate(a.cast(), layout(64, 8));
+            alloc.deallocate(b.cast(), layout(65, 8));
+        }
+        assert_eq!(alloc.small().live(), 0);
+        assert_eq!(alloc.large().live(), 0);
+    }
+
+    #[test]
+    fn segregator_moves_across_threshold() {
+        let alloc =
+            Segregator::<64, _, _>::new(LeakCheck::new(), LeakCheck::new());
+        let a = alloc.allocate(layout(32, 8)).unwrap();
+        unsafe { a.cast::<u8>().write_bytes(3, 32) };
+
+        let a = unsafe { alloc.grow(a.cast(), layout(32, 8), layout(128, 8)) }
+            .unwrap();
+        assert_eq!(alloc.small().live(), 0);
+        assert_eq!(alloc.large().live(), 1);
+        assert!(unsafe { &a.as_ref()[..32] }.iter().all(|&x| x == 3));
+
+        let a =
+            unsafe { alloc.shrink(a.cast(), layout(128, 8), layout(16, 8)) }
+                .unwrap();
+        assert_eq!(alloc.small().live(), 1);
+        assert_eq!(alloc.large().live(), 0);
+        assert!(unsafe { &a.as_ref()[..16] }.iter().all(|&x| x == 3));
+        unsafe { alloc.deallocate(a.cast(), layout(16, 8)) };
+    }
+
+    #[test]
+    fn segregator_composes_with_fallback() {
+        let mut buffer = [0; 256];
+        let alloc = Segregator::<128, _, _>::new(
+            Fallback::new(
+                FixedBufferAllocator::from_slice(&mut buffer),
+                LeakCheck::new(),
+            ),
+            LeakCheck::new(),
+        );
+        {
+            let mut array = Array::new(&alloc);
+            array.try_extend((0..1000).map(|x| x as u8)).unwrap();
+            assert_eq!(array[999], (999 % 256) as u8);
+        }
+        assert_eq!(alloc.small().secondary().live(), 0);
+        assert_eq!(alloc.large().live(), 0);
+    }
+
+    /// The slab rounds 90 bytes up to a 128 byte size class, which is past
+    /// the threshold, but freeing with the whole size returned must still go
+    /// to the slab.
+    #[test]
+    fn segregator_clamps_small_allocations() {
+        let alloc =
+            Segregator::<100, _, _>::new(Slab::new(Malloc), LeakCheck::new());
+        let a = alloc.allocate(layout(90, 1)).unwrap();
+        assert!(a.len() >= 90 && a.len() <= 100);
+        unsafe { alloc.deallocate(a.cast(), layout(a.len(), 1)) };
+
+        {
+            let mut array = Array::<u8>::with_capacity(90, &alloc).unwrap();
+            assert!(array.capacity() <= 100);
+            array.try_extend(0..100).unwrap();
+            assert_eq!(alloc.large().live(), 0);
+            array.try_extend(0..100).unwrap();
+            assert_eq!(alloc.large().live(), 1);
+        }
+        assert_eq!(alloc.large().live(), 0);
+    }
+}
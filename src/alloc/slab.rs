@@ -0,0 +1,368 @@
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    cell::UnsafeCell,
+    ptr::{self, NonNull},
+};
+
+/// The smallest size class, which is large enough to hold a [FreeSlot].
+const MIN_CLASS_SHIFT: u32 = 4;
+/// The largest size class. Anything larger goes straight to the backing
+/// allocator.
+const MAX_CLASS_SHIFT: u32 = 12;
+const MAX_CLASS: usize = 1 << MAX_CLASS_SHIFT;
+const CLASSES: usize = (MAX_CLASS_SHIFT - MIN_CLASS_SHIFT + 1) as usize;
+
+/// The size of each slab requested from the backing allocator. Slabs are
+/// aligned to [MAX_CLASS], so every slot is aligned to its own size.
+const SLAB_SIZE: usize = 64 * 1024;
+const SLAB: Layout = match Layout::from_size_align(SLAB_SIZE, MAX_CLASS) {
+    Ok(layout) => layout,
+    Err(_) => panic!("invalid slab layout"),
+};
+
+/// A general purpose allocator which sorts allocations into power of two size
+/// classes, each with its own free list.
+///
+/// Allocations of up to 4 KiB are carved out of 64 KiB slabs requested from
+/// the backing allocator, and are put on their size class's free list when
+/// they're freed, to be reused by the next allocation of that class in `O(1)`
+/// time. Since memory never moves between size classes, fragmentation stays
+/// predictable no matter the order things are freed in. Anything larger is
+/// passed straight through to the backing allocator.
+///
+/// The slabs are only returned to the backing allocator when the [Slab] goes
+/// out of scope.
+pub struct Slab<A: Allocator> {
+    alloc: A,
+    state: UnsafeCell<State>,
+}
+
+struct State {
+    classes: [Class; CLASSES],
+    /// The most recently allocated slab.
+    slabs: Option<NonNull<SlabHeader>>,
+    /// The total size of every slab.
+    reserved: usize,
+}
+
+#[derive(Clone, Copy)]
+struct Class {
+    /// The most recently freed slot, whose first bytes point to the slot
+    /// freed before it.
+    free: Option<NonNull<FreeSlot>>,
+    /// The next slot in the class's most recent slab which has never been
+    /// handed out. Once `next == end`, a new slab has to be allocated.
+    next: *mut u8,
+    end: *mut u8,
+}
+
+struct FreeSlot {
+    next: Option<NonNull<FreeSlot>>,
+}
+
+/// The header placed in the first slot of every slab, used to release the
+/// slabs when the [Slab] is dropped.
+struct SlabHeader {
+    next: Option<NonNull<SlabHeader>>,
+}
+
+/// Returns the index of the size class `layout` belongs to, or [None] if it's
+/// too large for any of them.
+#[inline(always)]
+fn class_index(layout: Layout) -> Option<usize> {
+    let size = layout.size().max(layout.align());
+    if size > MAX_CLASS {
+        return None;
+    }
+    let shift = size.next_power_of_two().trailing_zeros();
+    Some(shift.saturating_sub(MIN_CLASS_SHIFT) as usize)
+}
+
+/// Returns the size of every slot in the size class `index`.
+#[inline(always)]
+const fn class_size(index: usize) -> usize {
+    1 << (index as u32 + MIN_CLASS_SHIFT)
+}
+
+impl<A: Allocator> Slab<A> {
+    /// Creates a new empty [Slab] which requests its memory from `alloc`.
+    ///
+    /// No memory is allocated until the first allocation is made.
+    pub const fn new(alloc: A) -> Slab<A> {
+        Slab {
+            alloc,
+            state: UnsafeCell::new(State {
+                classes: [Class {
+                    free: None,
+                    next: ptr::null_mut(),
+                    end: ptr::null_mut(),
+                }; CLASSES],
+                slabs: None,
+                reserved: 0,
+            }),
+        }
+    }
+
+    /// Returns the total number of bytes of slabs requested from the backing
+    /// allocator, not counting allocations which were passed through to it.
+    #[inline(always)]
+    pub fn reserved(&self) -> usize {
+        unsafe { (*self.state.get()).reserved }
+    }
+
+    fn allocate_slot(&self, index: usize) -> Result<NonNull<u8>, AllocError> {
+        // SAFETY: the slab is !Sync and nothing holds a reference to the
+        //         state across calls.
+        let state = unsafe { &mut *self.state.get() };
+
+        if let Some(slot) = state.classes[index].free {
+            // SAFETY: every slot on the free list had a FreeSlot written to
+            //         it when it was freed.
+            state.classes[index].free = unsafe { slot.as_ref().next };
+            return Ok(slot.cast());
+        }
+
+        if ptr::eq(state.classes[index].next, state.classes[index].end) {
+            self.allocate_slab(state, index)?;
+        }
+
+        // SAFETY: allocate_slab guarantees there is at least one slot
+        //         between next and end.
+        let class = &mut state.classes[index];
+        unsafe {
+            let slot = NonNull::new_unchecked(class.next);
+            class.next = class.next.add(class_size(index));
+            Ok(slot)
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `slot` must have been returned by [Slab::allocate_slot] with the same
+    /// `index` and must not be in use.
+    unsafe fn free_slot(&self, slot: NonNull<u8>, index: usize) {
+        let class = &mut (*self.state.get()).classes[index];
+        let slot = slot.cast::<FreeSlot>();
+        slot.as_ptr().write(FreeSlot { next: class.free });
+        class.free = Some(slot);
+    }
+
+    fn allocate_slab(
+        &self,
+        state: &mut State,
+        index: usize,
+    ) -> Result<(), AllocError> {
+        let mem = self.alloc.allocate(SLAB)?.cast::<u8>();
+        let header = mem.cast::<SlabHeader>();
+        // SAFETY: the memory was just allocated with room for SLAB_SIZE
+        //         bytes. The header takes up the first slot, which every size
+        //         class is large enough to hold.
+        unsafe {
+            header.as_ptr().write(SlabHeader { next: state.slabs });
+            let class = &mut state.classes[index];
+            class.next = mem.as_ptr().add(class_size(index));
+            class.end = mem.as_ptr().add(SLAB_SIZE);
+        }
+        state.slabs = Some(header);
+        state.reserved += SLAB_SIZE;
+        Ok(())
+    }
+
+    /// Moves an allocation to a new one in a different size class.
+    unsafe fn reallocate(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let new_ptr = self.allocate(new_layout)?;
+        ptr::copy_nonoverlapping(
+            ptr.as_ptr(),
+            new_ptr.as_ptr() as *mut u8,
+            old_layout.size().min(new_layout.size()),
+        );
+        self.deallocate(ptr, old_layout);
+        Ok(new_ptr)
+    }
+}
+
+impl<A: Allocator> Drop for Slab<A> {
+    /// Releases every slab back to the backing allocator. Allocations which
+    /// were passed through to the backing allocator are not freed.
+    fn drop(&mut self) {
+        let mut slab = self.state.get_mut().slabs;
+        while let Some(s) = slab {
+            // SAFETY: every slab in the list was allocated by self.alloc
+            //         with the SLAB layout.
+            unsafe {
+                slab = s.as_ptr().read().next;
+                self.alloc.deallocate(s.cast(), SLAB);
+            }
+        }
+    }
+}
+
+unsafe impl<A: Allocator> Allocator for Slab<A> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let Some(index) = class_index(layout) else {
+            return self.alloc.allocate(layout);
+        };
+
+        let slot = self.allocate_slot(index)?;
+        Ok(NonNull::slice_from_raw_parts(slot, class_size(index)))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        match class_index(layout) {
+            Some(index) => self.free_slot(ptr, index),
+            None => self.alloc.deallocate(ptr, layout),
+        }
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        match (class_index(old_layout), class_index(new_layout)) {
+            (Some(old), Some(new)) if old == new => {
+                Ok(NonNull::slice_from_raw_parts(ptr, class_size(new)))
+            }
+            (None, None) => self.alloc.grow(ptr, old_layout, new_layout),
+            _ => self.reallocate(ptr, old_layout, new_layout),
+        }
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        match (class_index(old_layout), class_index(new_layout)) {
+            (Some(old), Some(new)) if old == new => {
+                Ok(NonNull::slice_from_raw_parts(ptr, class_size(new)))
+            }
+            (None, None) => self.alloc.shrink(ptr, old_layout, new_layout),
+            _ => self.reallocate(ptr, old_layout, new_layout),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        alloc::{fail_each_allocation, Malloc, Tracking},
+        array::Array,
+    };
+
+    fn layout(size: usize, align: usize) -> Layout {
+        Layout::from_size_align(size, align).unwrap()
+    }
+
+    #[test]
+    fn slab_size_classes() {
+        assert_eq!(class_index(layout(0, 1)), Some(0));
+        assert_eq!(class_index(layout(16, 8)), Some(0));
+        assert_eq!(class_index(layout(17, 1)), Some(1));
+        assert_eq!(class_index(layout(8, 64)), Some(2));
+        assert_eq!(class_index(layout(4096, 8)), Some(CLASSES - 1));
+        assert_eq!(class_index(layout(4097, 8)), None);
+        assert_eq!(class_size(CLASSES - 1), MAX_CLASS);
+    }
+
+    #[test]
+    fn slab_reuses_freed_slots() {
+        let slab = Slab::new(Malloc);
+        let a = slab.allocate(layout(24, 8)).unwrap();
+        let b = slab.allocate(layout(100, 4)).unwrap();
+        assert_eq!(a.len(), 32);
+        assert_eq!(b.len(), 128);
+
+        unsafe { slab.deallocate(a.cast(), layout(24, 8)) };
+        let c = slab.allocate(layout(100, 4)).unwrap();
+        assert_ne!(c.cast::<u8>(), a.cast::<u8>());
+        let d = slab.allocate(layout(30, 2)).unwrap();
+        assert_eq!(d.cast::<u8>(), a.cast::<u8>());
+        assert_eq!(slab.reserved(), 2 * SLAB_SIZE);
+    }
+
+    #[test]
+    fn slab_alignment() {
+        let slab = Slab::new(Malloc);
+        for align in [1, 2, 8, 64, 512, 4096] {
+            for _ in 0..10 {
+                let a = slab.allocate(layout(3, align)).unwrap();
+                assert_eq!(a.cast::<u8>().addr().get() % align, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn slab_large_allocations_pass_through() {
+        let tracking = Tracking::new(Malloc);
+        let slab = Slab::new(&tracking);
+        let a = slab.allocate(layout(10000, 8)).unwrap();
+        assert_eq!(slab.reserved(), 0);
+        assert_eq!(tracking.stats().live_bytes, 10000);
+
+        let a =
+            unsafe { slab.shrink(a.cast(), layout(10000, 8), layout(100, 8)) }
+                .unwrap();
+        assert_eq!(slab.reserved(), SLAB_SIZE);
+        assert_eq!(tracking.stats().live_bytes, SLAB_SIZE);
+        unsafe { slab.deallocate(a.cast(), layout(100, 8)) };
+
+        drop(slab);
+        assert_eq!(tracking.stats().live_bytes, 0);
+    }
+
+    #[test]
+    fn slab_grow_within_class_is_in_place() {
+        let slab = Slab::new(Malloc);
+        let a = slab.allocate(layout(40, 8)).unwrap();
+        unsafe { a.cast::<u8>().write_bytes(3, 40) };
+        let b = unsafe { slab.grow(a.cast(), layout(40, 8), layout(64, 8)) }
+            .unwrap();
+        assert_eq!(a.cast::<u8>(), b.cast::<u8>());
+
+        let c = unsafe { slab.grow(b.cast(), layout(64, 8), layout(65, 8)) }
+            .unwrap();
+        assert_ne!(b.cast::<u8>(), c.cast::<u8>());
+        let bytes = unsafe { &c.as_ref()[..40] };
+        assert!(bytes.iter().all(|&x| x == 3));
+    }
+
+    #[test]
+    fn slab_many_slabs() {
+        let tracking = Tracking::new(Malloc);
+        {
+            let slab = Slab::new(&tracking);
+            let mut arrays = Array::new(Malloc);
+            for i in 0..200 {
+                let mut array = Array::new(&slab);
+                for j in 0..i * 10 {
+                    array.push(j).unwrap();
+                }
+                arrays.push(array).unwrap();
+            }
+            assert!(slab.reserved() > SLAB_SIZE);
+        }
+        assert_eq!(tracking.stats().live_bytes, 0);
+    }
+
+    #[test]
+    fn slab_fail_each_allocation() {
+        fail_each_allocation(|alloc| {
+            let slab = Slab::new(alloc);
+            let mut array = Array::new(&slab);
+            for i in 0..5000u32 {
+                if array.push(i).is_err() {
+                    return;
+                }
+            }
+        });
+    }
+}
@@ -5,14 +5,24 @@
 //! at the time of writing the standard library doesn't expose the Vec's type
 //! parameter (for whatever reason). This is mostly duplicated effort for
 //! really no reason unfortunately.
+//!
+//! Every method which can grow the string has a `try_` variant returning a
+//! [TryReserveError] instead of aborting when an allocation fails.
 
 use std::{
     alloc::{Allocator, Global},
+    borrow::Borrow,
+    cmp::Ordering,
     collections::TryReserveError,
-    string::String as StdString,
+    error::Error,
+    fmt,
+    hash::{Hash, Hasher},
+    iter::FusedIterator,
+    ops::{self, Bound, RangeBounds},
+    str::{self, Utf8Error},
+    vec,
 };
 
-#[derive(PartialEq, PartialOrd, Eq, Ord)]
 pub struct String<A: Allocator = Global> {
     vec: Vec<u8, A>,
 }
@@ -20,21 +30,25 @@ pub struct String<A: Allocator = Global> {
 impl String {
     #[inline]
     #[must_use]
-    pub const fn new() -> StdString {
-        StdString::new()
+    pub const fn new() -> String {
+        String { vec: Vec::new() }
     }
 
     #[inline]
     pub fn try_with_capacity(
         capacity: usize,
-    ) -> Result<StdString, TryReserveError> {
-        StdString::try_with_capacity(capacity)
+    ) -> Result<String, TryReserveError> {
+        Ok(String {
+            vec: Vec::try_with_capacity(capacity)?,
+        })
     }
 
     #[inline]
     #[must_use]
-    pub fn with_capacity(capacity: usize) -> StdString {
-        StdString::with_capacity(capacity)
+    pub fn with_capacity(capacity: usize) -> String {
+        String {
+            vec: Vec::with_capacity(capacity),
+        }
     }
 
     #[inline]
@@ -57,7 +71,6 @@ impl String {
     }
 
     #[inline]
-    #[must_use]
     pub fn try_with_capacity_in<A: Allocator>(
         capacity: usize,
         alloc: A,
@@ -66,21 +79,348 @@ impl String {
             vec: Vec::<u8, A>::try_with_capacity_in(capacity, alloc)?,
         })
     }
+
+    /// Converts `bytes` to a string, replacing any invalid UTF-8 sequences
+    /// with U+FFFD REPLACEMENT CHARACTER.
+    #[inline]
+    #[must_use]
+    pub fn from_utf8_lossy(bytes: &[u8]) -> String {
+        String::from_utf8_lossy_in(bytes, Global)
+    }
+
+    /// Like [String::from_utf8_lossy], but uses the allocator `alloc`.
+    #[must_use]
+    pub fn from_utf8_lossy_in<A: Allocator>(
+        bytes: &[u8],
+        alloc: A,
+    ) -> String<A> {
+        let mut result = String::with_capacity_in(bytes.len(), alloc);
+        for chunk in bytes.utf8_chunks() {
+            result.push_str(chunk.valid());
+            if !chunk.invalid().is_empty() {
+                result.push(char::REPLACEMENT_CHARACTER);
+            }
+        }
+        result
+    }
+
+    /// Like [String::from_utf8_lossy_in], but returns an error if an
+    /// allocation fails.
+    pub fn try_from_utf8_lossy_in<A: Allocator>(
+        bytes: &[u8],
+        alloc: A,
+    ) -> Result<String<A>, TryReserveError> {
+        let mut result = String::try_with_capacity_in(bytes.len(), alloc)?;
+        for chunk in bytes.utf8_chunks() {
+            result.try_push_str(chunk.valid())?;
+            if !chunk.invalid().is_empty() {
+                result.try_push(char::REPLACEMENT_CHARACTER)?;
+            }
+        }
+        Ok(result)
+    }
+}
+
+impl Default for String {
+    #[inline]
+    fn default() -> Self {
+        String::new()
+    }
 }
 
 impl<A: Allocator> String<A> {
+    /// Converts `vec` to a string, returning an error containing `vec` if it
+    /// isn't valid UTF-8.
+    #[inline]
+    pub fn from_utf8(vec: Vec<u8, A>) -> Result<String<A>, FromUtf8Error<A>> {
+        match str::from_utf8(&vec) {
+            Ok(_) => Ok(String { vec }),
+            Err(error) => Err(FromUtf8Error { bytes: vec, error }),
+        }
+    }
+
+    /// Converts `vec` to a string without checking that it's valid UTF-8.
+    ///
+    /// # Safety
+    ///
+    /// This function is safe to use if `vec` is valid UTF-8.
+    #[inline]
+    #[must_use]
+    pub unsafe fn from_utf8_unchecked(vec: Vec<u8, A>) -> String<A> {
+        String { vec }
+    }
+
+    /// Returns the bytes of the string, without copying them.
+    #[inline]
+    #[must_use]
+    pub fn into_bytes(self) -> Vec<u8, A> {
+        self.vec
+    }
+
     #[inline]
     #[must_use]
     pub const fn as_bytes(&self) -> &[u8] {
         self.vec.as_slice()
     }
 
+    #[inline]
+    #[must_use]
+    pub const fn as_str(&self) -> &str {
+        // SAFETY: the contents are always valid UTF-8.
+        unsafe { str::from_utf8_unchecked(self.vec.as_slice()) }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn as_mut_str(&mut self) -> &mut str {
+        // SAFETY: the contents are always valid UTF-8.
+        unsafe { str::from_utf8_unchecked_mut(self.vec.as_mut_slice()) }
+    }
+
     #[inline]
     #[must_use]
     pub const fn len(&self) -> usize {
         self.vec.len()
     }
 
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.vec.is_empty()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.vec.capacity()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn allocator(&self) -> &A {
+        self.vec.allocator()
+    }
+
+    /// Reserves room for at least `additional` more bytes.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.vec.reserve(additional)
+    }
+
+    /// Like [String::reserve], but returns an error if an allocation fails.
+    #[inline]
+    pub fn try_reserve(
+        &mut self,
+        additional: usize,
+    ) -> Result<(), TryReserveError> {
+        self.vec.try_reserve(additional)
+    }
+
+    #[inline]
+    pub fn shrink_to_fit(&mut self) {
+        self.vec.shrink_to_fit()
+    }
+
+    /// Appends `ch` to the end of the string.
+    #[inline]
+    pub fn push(&mut self, ch: char) {
+        self.push_str(ch.encode_utf8(&mut [0; 4]))
+    }
+
+    /// Like [String::push], but returns an error if an allocation fails.
+    #[inline]
+    pub fn try_push(&mut self, ch: char) -> Result<(), TryReserveError> {
+        self.try_push_str(ch.encode_utf8(&mut [0; 4]))
+    }
+
+    /// Appends `s` to the end of the string.
+    #[inline]
+    pub fn push_str(&mut self, s: &str) {
+        self.vec.extend_from_slice(s.as_bytes())
+    }
+
+    /// Like [String::push_str], but returns an error if an allocation fails,
+    /// leaving the string unchanged.
+    #[inline]
+    pub fn try_push_str(&mut self, s: &str) -> Result<(), TryReserveError> {
+        self.vec.try_reserve(s.len())?;
+        self.vec.extend_from_slice(s.as_bytes());
+        Ok(())
+    }
+
+    /// Appends every character of `iter` to the end of the string.
+    ///
+    /// This is the fallible version of the string's [Extend] implementation.
+    /// Returns an error if an allocation failed. The characters which were
+    /// appended before the failure are kept.
+    pub fn try_extend<I: IntoIterator<Item = char>>(
+        &mut self,
+        iter: I,
+    ) -> Result<(), TryReserveError> {
+        let iter = iter.into_iter();
+        self.try_reserve(iter.size_hint().0)?;
+        for ch in iter {
+            self.try_push(ch)?;
+        }
+        Ok(())
+    }
+
+    /// Appends every string in `iter` to the end of the string.
+    ///
+    /// This is the fallible version of the string's [Extend] implementation.
+    /// Returns an error if an allocation failed. The strings which were
+    /// appended before the failure are kept.
+    pub fn try_extend_str<'a, I: IntoIterator<Item = &'a str>>(
+        &mut self,
+        iter: I,
+    ) -> Result<(), TryReserveError> {
+        for s in iter {
+            self.try_push_str(s)?;
+        }
+        Ok(())
+    }
+
+    /// Removes the last character and returns it, or [None] if the string is
+    /// empty.
+    #[inline]
+    pub fn pop(&mut self) -> Option<char> {
+        let ch = self.as_str().chars().next_back()?;
+        self.vec.truncate(self.len() - ch.len_utf8());
+        Some(ch)
+    }
+
+    /// Inserts `ch` at the byte index `index`.
+    ///
+    /// Panics if `index` isn't on a character boundary.
+    #[inline]
+    pub fn insert(&mut self, index: usize, ch: char) {
+        self.insert_str(index, ch.encode_utf8(&mut [0; 4]))
+    }
+
+    /// Like [String::insert], but returns an error if an allocation fails.
+    #[inline]
+    pub fn try_insert(
+        &mut self,
+        index: usize,
+        ch: char,
+    ) -> Result<(), TryReserveError> {
+        self.try_insert_str(index, ch.encode_utf8(&mut [0; 4]))
+    }
+
+    /// Inserts `s` at the byte index `index`.
+    ///
+    /// Panics if `index` isn't on a character boundary.
+    #[inline]
+    pub fn insert_str(&mut self, index: usize, s: &str) {
+        assert!(self.is_char_boundary(index));
+        self.vec.reserve(s.len());
+        self.insert_bytes(index, s.as_bytes());
+    }
+
+    /// Like [String::insert_str], but returns an error if an allocation
+    /// fails, leaving the string unchanged.
+    pub fn try_insert_str(
+        &mut self,
+        index: usize,
+        s: &str,
+    ) -> Result<(), TryReserveError> {
+        assert!(self.is_char_boundary(index));
+        self.vec.try_reserve(s.len())?;
+        self.insert_bytes(index, s.as_bytes());
+        Ok(())
+    }
+
+    /// Inserts `bytes` at `index`, which must be a character boundary, after
+    /// room has been reserved for them.
+    #[inline]
+    fn insert_bytes(&mut self, index: usize, bytes: &[u8]) {
+        self.vec.extend_from_slice(bytes);
+        self.vec[index..].rotate_right(bytes.len());
+    }
+
+    /// Removes and returns the character at the byte index `index`.
+    ///
+    /// Panics if `index` isn't on a character boundary or is out of bounds.
+    #[inline]
+    pub fn remove(&mut self, index: usize) -> char {
+        let Some(ch) = self.as_str()[index..].chars().next() else {
+            panic!("cannot remove a char from the end of a string");
+        };
+        self.vec.drain(index..index + ch.len_utf8());
+        ch
+    }
+
+    /// Removes every character for which `f` returns false.
+    pub fn retain(&mut self, mut f: impl FnMut(char) -> bool) {
+        /// Drops everything after the retained characters, even if `f`
+        /// panics, since part of the string may have been overwritten.
+        struct Guard<'a, A: Allocator> {
+            vec: &'a mut Vec<u8, A>,
+            read: usize,
+            write: usize,
+        }
+
+        impl<A: Allocator> Drop for Guard<'_, A> {
+            fn drop(&mut self) {
+                self.vec.truncate(self.write);
+            }
+        }
+
+        let mut guard = Guard {
+            vec: &mut self.vec,
+            read: 0,
+            write: 0,
+        };
+        while guard.read < guard.vec.len() {
+            // SAFETY: read is always on a character boundary.
+            let rest =
+                unsafe { str::from_utf8_unchecked(&guard.vec[guard.read..]) };
+            let Some(ch) = rest.chars().next() else {
+                break;
+            };
+
+            let len = ch.len_utf8();
+            if f(ch) {
+                guard
+                    .vec
+                    .copy_within(guard.read..guard.read + len, guard.write);
+                guard.write += len;
+            }
+            guard.read += len;
+        }
+    }
+
+    /// Removes the characters in the byte range `range`, returning them as an
+    /// iterator. The characters are removed even if the iterator isn't
+    /// consumed.
+    ///
+    /// Panics if either end of `range` isn't on a character boundary, or if
+    /// it's out of bounds.
+    pub fn drain<R: RangeBounds<usize>>(&mut self, range: R) -> Drain<'_, A> {
+        let start = match range.start_bound() {
+            Bound::Included(&x) => x,
+            Bound::Excluded(&x) => x + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&x) => x + 1,
+            Bound::Excluded(&x) => x,
+            Bound::Unbounded => self.len(),
+        };
+        assert!(start <= end);
+        assert!(self.is_char_boundary(start));
+        assert!(self.is_char_boundary(end));
+        Drain {
+            inner: self.vec.drain(start..end),
+        }
+    }
+
+    /// Clears the string, keeping its capacity.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.vec.clear()
+    }
+
     #[inline]
     pub fn truncate(&mut self, new_len: usize) {
         if new_len <= self.len() {
@@ -103,6 +443,417 @@ impl<A: Allocator> String<A> {
     }
 }
 
+impl<A: Allocator + Clone> String<A> {
+    /// Splits the string in two at the byte index `at`, returning everything
+    /// after it in a new string using the same allocator.
+    ///
+    /// Panics if `at` isn't on a character boundary.
+    #[inline]
+    #[must_use]
+    pub fn split_off(&mut self, at: usize) -> String<A> {
+        assert!(self.is_char_boundary(at));
+        String {
+            vec: self.vec.split_off(at),
+        }
+    }
+
+    /// Like [String::split_off], but returns an error if an allocation fails,
+    /// leaving the string unchanged.
+    pub fn try_split_off(
+        &mut self,
+        at: usize,
+    ) -> Result<String<A>, TryReserveError> {
+        assert!(self.is_char_boundary(at));
+        let mut result = String::try_with_capacity_in(
+            self.len() - at,
+            self.allocator().clone(),
+        )?;
+        result.vec.extend_from_slice(&self.vec[at..]);
+        self.vec.truncate(at);
+        Ok(result)
+    }
+
+    /// Returns a copy of the string using the same allocator, or an error if
+    /// an allocation fails.
+    pub fn try_clone(&self) -> Result<String<A>, TryReserveError> {
+        let mut result = String::try_with_capacity_in(
+            self.len(),
+            self.allocator().clone(),
+        )?;
+        result.vec.extend_from_slice(self.as_bytes());
+        Ok(result)
+    }
+}
+
+impl<A: Allocator + Clone> Clone for String<A> {
+    #[inline]
+    fn clone(&self) -> Self {
+        String {
+            vec: self.vec.clone(),
+        }
+    }
+}
+
+impl<A: Allocator> ops::Deref for String<A> {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<A: Allocator> ops::DerefMut for String<A> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut str {
+        self.as_mut_str()
+    }
+}
+
+impl<A: Allocator> AsRef<str> for String<A> {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<A: Allocator> AsRef<[u8]> for String<A> {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl<A: Allocator> Borrow<str> for String<A> {
+    #[inline]
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&str> for String {
+    #[inline]
+    fn from(value: &str) -> Self {
+        let mut result = String::with_capacity(value.len());
+        result.push_str(value);
+        result
+    }
+}
+
+impl<A: Allocator> Extend<char> for String<A> {
+    fn extend<I: IntoIterator<Item = char>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for ch in iter {
+            self.push(ch);
+        }
+    }
+}
+
+impl<'a, A: Allocator> Extend<&'a str> for String<A> {
+    fn extend<I: IntoIterator<Item = &'a str>>(&mut self, iter: I) {
+        for s in iter {
+            self.push_str(s);
+        }
+    }
+}
+
+impl<A: Allocator> fmt::Write for String<A> {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.try_push_str(s).map_err(|_| fmt::Error)
+    }
+
+    #[inline]
+    fn write_char(&mut self, c: char) -> fmt::Result {
+        self.try_push(c).map_err(|_| fmt::Error)
+    }
+}
+
+impl<A: Allocator> fmt::Display for String<A> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl<A: Allocator> fmt::Debug for String<A> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl<A: Allocator> Hash for String<A> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl<A: Allocator, B: Allocator> PartialEq<String<B>> for String<A> {
+    #[inline]
+    fn eq(&self, other: &String<B>) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl<A: Allocator> Eq for String<A> {}
+
+impl<A: Allocator> PartialEq<str> for String<A> {
+    #[inline]
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl<A: Allocator> PartialEq<&str> for String<A> {
+    #[inline]
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl<A: Allocator, B: Allocator> PartialOrd<String<B>> for String<A> {
+    #[inline]
+    fn partial_cmp(&self, other: &String<B>) -> Option<Ordering> {
+        Some(self.as_str().cmp(other.as_str()))
+    }
+}
+
+impl<A: Allocator> Ord for String<A> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+/// The error returned by [String::from_utf8], which holds on to the bytes
+/// which couldn't be converted.
+pub struct FromUtf8Error<A: Allocator = Global> {
+    bytes: Vec<u8, A>,
+    error: Utf8Error,
+}
+
+impl<A: Allocator> FromUtf8Error<A> {
+    #[inline]
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the bytes which were given to [String::from_utf8].
+    #[inline]
+    #[must_use]
+    pub fn into_bytes(self) -> Vec<u8, A> {
+        self.bytes
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn utf8_error(&self) -> Utf8Error {
+        self.error
+    }
+}
+
+impl<A: Allocator> fmt::Debug for FromUtf8Error<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FromUtf8Error")
+            .field("bytes", &self.as_bytes())
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+impl<A: Allocator> fmt::Display for FromUtf8Error<A> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.error, f)
+    }
+}
+
+impl<A: Allocator> Error for FromUtf8Error<A> {}
+
+/// The iterator returned by [String::drain].
+pub struct Drain<'a, A: Allocator = Global> {
+    inner: vec::Drain<'a, u8, A>,
+}
+
+impl<A: Allocator> Drain<'_, A> {
+    /// Returns the characters which haven't been iterated over yet.
+    #[inline]
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        // SAFETY: the range given to String::drain is on character
+        //         boundaries, and only whole characters are removed from it.
+        unsafe { str::from_utf8_unchecked(self.inner.as_slice()) }
+    }
+}
+
+impl<A: Allocator> Iterator for Drain<'_, A> {
+    type Item = char;
+
+    #[inline]
+    fn next(&mut self) -> Option<char> {
+        let ch = self.as_str().chars().next()?;
+        self.inner.nth(ch.len_utf8() - 1);
+        Some(ch)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.inner.len();
+        (len.div_ceil(4), Some(len))
+    }
+}
+
+impl<A: Allocator> DoubleEndedIterator for Drain<'_, A> {
+    #[inline]
+    fn next_back(&mut self) -> Option<char> {
+        let ch = self.as_str().chars().next_back()?;
+        self.inner.nth_back(ch.len_utf8() - 1);
+        Some(ch)
+    }
+}
+
+impl<A: Allocator> FusedIterator for Drain<'_, A> {}
+
 const fn is_utf8_char_boundary(x: u8) -> bool {
     (x as i8) >= -0x40
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc::FixedBufferAllocator;
+    use std::{collections::HashSet, fmt::Write};
+
+    #[test]
+    fn string_push_and_pop() {
+        let mut s = String::new();
+        s.push('a');
+        s.push_str("bç");
+        s.push('€');
+        assert_eq!(s, "abç€");
+        assert_eq!(s.len(), 7);
+        assert_eq!(s.pop(), Some('€'));
+        assert_eq!(s.pop(), Some('ç'));
+        assert_eq!(s.as_str(), "ab");
+        s.clear();
+        assert_eq!(s.pop(), None);
+    }
+
+    #[test]
+    fn string_insert_and_remove() {
+        let mut s = String::from("hllo");
+        s.insert(1, 'e');
+        s.insert_str(5, ", wörld");
+        assert_eq!(s, "hello, wörld");
+        assert_eq!(s.remove(8), 'ö');
+        assert_eq!(s.remove(0), 'h');
+        assert_eq!(s, "ello, wrld");
+    }
+
+    #[test]
+    #[should_panic]
+    fn string_insert_not_char_boundary() {
+        let mut s = String::from("é");
+        s.insert(1, 'x');
+    }
+
+    #[test]
+    fn string_retain() {
+        let mut s = String::from("a1b2ç3€");
+        s.retain(|c| !c.is_ascii_digit());
+        assert_eq!(s, "abç€");
+        s.retain(|c| c.is_ascii());
+        assert_eq!(s, "ab");
+    }
+
+    #[test]
+    fn string_drain() {
+        let mut s = String::from("αβγδ");
+        let drained: Vec<char> = s.drain(2..6).collect();
+        assert_eq!(drained, ['β', 'γ']);
+        assert_eq!(s, "αδ");
+
+        let mut drain = s.drain(..);
+        assert_eq!(drain.next_back(), Some('δ'));
+        assert_eq!(drain.as_str(), "α");
+        drop(drain);
+        assert!(s.is_empty());
+    }
+
+    #[test]
+    fn string_split_off() {
+        let mut s = String::from("hello world");
+        let t = s.split_off(5);
+        assert_eq!(s, "hello");
+        assert_eq!(t, " world");
+        let u = s.try_split_off(0).unwrap();
+        assert_eq!(s, "");
+        assert_eq!(u, "hello");
+    }
+
+    #[test]
+    fn string_from_utf8() {
+        let s = String::from_utf8(b"abc".to_vec()).unwrap();
+        assert_eq!(s, "abc");
+
+        let e = String::from_utf8(vec![b'a', 0xff]).unwrap_err();
+        assert_eq!(e.utf8_error().valid_up_to(), 1);
+        assert_eq!(e.into_bytes(), [b'a', 0xff]);
+
+        let s = String::from_utf8_lossy(b"a\xffb\xf0\x90c");
+        assert_eq!(s, "a\u{FFFD}b\u{FFFD}c");
+    }
+
+    #[test]
+    fn string_traits() {
+        let mut s = String::new();
+        write!(s, "{}-{:?}", 1, "x").unwrap();
+        assert_eq!(format!("{s}"), "1-\"x\"");
+        assert_eq!(format!("{s:?}"), "\"1-\\\"x\\\"\"");
+        assert_eq!(s.clone(), s);
+        let (a, b) = (String::from("a"), String::from("b"));
+        assert!(a < b);
+        assert!(s.starts_with("1-"));
+
+        let mut set = HashSet::new();
+        set.insert(String::from("key"));
+        assert!(set.contains("key"));
+    }
+
+    #[test]
+    fn string_fallible_growth() {
+        let mut buffer = [0u8; 4];
+        let fba = FixedBufferAllocator::from_slice(&mut buffer);
+        let mut s = String::try_with_capacity_in(4, &fba).unwrap();
+        assert!(s.try_push_str("abcd").is_ok());
+        assert!(s.try_push_str("efghijkl").is_err());
+        assert_eq!(s, "abcd");
+        assert!(s.try_insert(0, 'x').is_err());
+        assert!(s.try_clone().is_err());
+        assert!(write!(s, "{}", 12345).is_err());
+        assert_eq!(s, "abcd");
+
+        let lossy = String::try_from_utf8_lossy_in(b"\xff\xff\xff", &fba);
+        assert!(lossy.is_err());
+    }
+
+    #[test]
+    fn string_try_extend() {
+        let mut buffer = [0u8; 8];
+        let fba = FixedBufferAllocator::from_slice(&mut buffer);
+        let mut s = String::new_in(&fba);
+        assert!(s.try_extend("abc".chars()).is_ok());
+        assert!(s.try_extend_str(["de", "f"]).is_ok());
+        assert_eq!(s, "abcdef");
+
+        // What fit before running out of memory is kept.
+        assert!(s.try_extend_str(["g", "hij"]).is_err());
+        assert_eq!(s, "abcdefg");
+        assert!(s.try_extend("hi".chars()).is_err());
+        assert_eq!(s, "abcdefgh");
+    }
+}
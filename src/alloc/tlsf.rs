@@ -0,0 +1,807 @@
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    cell::UnsafeCell,
+    marker::PhantomData,
+    mem,
+    ptr::{self, NonNull},
+    slice,
+};
+
+/// The alignment of every block, and the size of their headers.
+const ALIGN: usize = 2 * mem::size_of::<usize>();
+const ALIGN_SHIFT: u32 = ALIGN.trailing_zeros();
+const HEADER: usize = ALIGN;
+/// Free blocks store two free list pointers in their payload.
+const MIN_PAYLOAD: usize = 2 * mem::size_of::<usize>();
+
+/// Each first level range of sizes is split into `1 << SL_SHIFT` second
+/// level lists.
+const SL_SHIFT: u32 = 5;
+const SL_COUNT: usize = 1 << SL_SHIFT;
+/// Sizes below this all share the first level list 0, whose second level
+/// lists are [ALIGN] bytes apart.
+const FL_SHIFT: u32 = SL_SHIFT + ALIGN_SHIFT;
+const SMALL_BLOCK: usize = 1 << FL_SHIFT;
+const FL_COUNT: usize = (usize::BITS - FL_SHIFT + 1) as usize;
+/// The largest payload which can be allocated, small enough that rounding
+/// sizes up never overflows.
+const MAX_SIZE: usize = 1 << (usize::BITS - 2);
+
+/// Set in [Block::size] if the block is free.
+const FREE: usize = 1 << 0;
+/// Set in [Block::size] if the previous physical block is free.
+const PREV_FREE: usize = 1 << 1;
+
+/// The header at the start of every block, immediately followed by its
+/// payload. Only free blocks use `next_free` and `prev_free`, which overlap
+/// the start of the payload.
+#[repr(C)]
+struct Block {
+    /// The previous block in memory, only valid if it's free.
+    prev_phys: Option<NonNull<Block>>,
+    /// The size of the payload, with [FREE] and [PREV_FREE] in the low bits.
+    size: usize,
+    next_free: Option<NonNull<Block>>,
+    prev_free: Option<NonNull<Block>>,
+}
+
+impl Block {
+    #[inline(always)]
+    fn size(&self) -> usize {
+        self.size & !(FREE | PREV_FREE)
+    }
+
+    #[inline(always)]
+    fn set_size(&mut self, size: usize) {
+        self.size = size | (self.size & (FREE | PREV_FREE));
+    }
+
+    #[inline(always)]
+    fn is_free(&self) -> bool {
+        self.size & FREE != 0
+    }
+
+    #[inline(always)]
+    fn is_prev_free(&self) -> bool {
+        self.size & PREV_FREE != 0
+    }
+
+    #[inline(always)]
+    fn set_flag(&mut self, flag: usize, set: bool) {
+        if set {
+            self.size |= flag;
+        } else {
+            self.size &= !flag;
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `block` must point to a valid block.
+    #[inline(always)]
+    unsafe fn payload(block: NonNull<Block>) -> NonNull<u8> {
+        block.cast::<u8>().add(HEADER)
+    }
+
+    /// # Safety
+    ///
+    /// `ptr` must point to the payload of a valid block.
+    #[inline(always)]
+    unsafe fn from_payload(ptr: NonNull<u8>) -> NonNull<Block> {
+        ptr.sub(HEADER).cast()
+    }
+
+    /// Returns the block following `block` in memory.
+    ///
+    /// # Safety
+    ///
+    /// `block` must point to a valid block other than the sentinel.
+    #[inline(always)]
+    unsafe fn next_phys(block: NonNull<Block>) -> NonNull<Block> {
+        Block::payload(block).add(block.as_ref().size()).cast()
+    }
+}
+
+/// Returns the first and second level indices of the free list blocks with a
+/// payload of `size` bytes belong in.
+#[inline(always)]
+fn mapping(size: usize) -> (usize, usize) {
+    if size < SMALL_BLOCK {
+        return (0, size / ALIGN);
+    }
+    let f = size.ilog2();
+    let sl = (size >> (f - SL_SHIFT)) ^ SL_COUNT;
+    ((f - FL_SHIFT + 1) as usize, sl)
+}
+
+/// Rounds `size` up so that every block in the free list [mapping] returns
+/// for it is at least `size` bytes.
+#[inline(always)]
+fn round_up(size: usize) -> usize {
+    if size < SMALL_BLOCK {
+        return size;
+    }
+    size + (1 << (size.ilog2() - SL_SHIFT)) - 1
+}
+
+/// Returns the payload size needed for `size` bytes, or an error if it's too
+/// large.
+#[inline(always)]
+fn adjust(size: usize) -> Result<usize, AllocError> {
+    if size > MAX_SIZE {
+        return Err(AllocError);
+    }
+    Ok(size.next_multiple_of(ALIGN).max(MIN_PAYLOAD))
+}
+
+/// A snapshot of how the memory of a [Tlsf] allocator is being used,
+/// returned by [Tlsf::stats].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TlsfStats {
+    /// The number of bytes in allocated blocks, which can be a bit more than
+    /// what was requested.
+    pub used_bytes: usize,
+    /// The number of bytes in free blocks.
+    pub free_bytes: usize,
+    /// The number of free blocks.
+    pub free_blocks: usize,
+    /// The size of the largest free block, which is the largest allocation
+    /// which could currently succeed.
+    pub largest_free_block: usize,
+}
+
+impl TlsfStats {
+    /// Returns how fragmented the free memory is, from 0 if it's all in a
+    /// single block, to almost 1 if it's split into many small blocks.
+    #[inline(always)]
+    pub fn fragmentation(&self) -> f64 {
+        if self.free_bytes == 0 {
+            return 0.0;
+        }
+        1.0 - self.largest_free_block as f64 / self.free_bytes as f64
+    }
+}
+
+/// A Two-Level Segregated Fit allocator, which allocates from a fixed buffer
+/// of memory in constant time.
+///
+/// Free blocks are sorted into lists by size, first by the power of two
+/// below their size, then by a linear subdivision of that range. Bitmaps of
+/// which lists aren't empty let [Allocator::allocate] find a large enough
+/// block without searching, and every block records whether its neighbours
+/// are free, so [Allocator::deallocate] can merge them right away. This
+/// makes [Allocator::allocate], [Allocator::deallocate], [Allocator::grow]
+/// and [Allocator::shrink] all take `O(1)` time, which is what real-time
+/// code needs.
+///
+/// Every block has a header of two words, and blocks are aligned to two
+/// words.
+pub struct Tlsf<'a> {
+    state: UnsafeCell<State>,
+    _marker: PhantomData<&'a mut [u8]>,
+}
+
+struct State {
+    fl_bitmap: usize,
+    sl_bitmap: [u32; FL_COUNT],
+    free: [[Option<NonNull<Block>>; SL_COUNT]; FL_COUNT],
+    /// The first block in memory, or [None] if the buffer was too small to
+    /// hold any.
+    first: Option<NonNull<Block>>,
+    used: usize,
+}
+
+unsafe impl Send for Tlsf<'_> {}
+
+impl<'a> Tlsf<'a> {
+    /// Constructs a [Tlsf] allocator managing `len` bytes starting at
+    /// `begin`.
+    ///
+    /// # Safety
+    ///
+    /// Behavior is undefined if any of the following conditions are violated:
+    ///
+    /// - `begin` must not be `null`.
+    /// - `begin` must point to `len` bytes of readable and writable memory.
+    /// - The memory referenced by `begin` must not be accessed through any
+    ///   other pointer for the duration of the lifetime `'a`. Both read and
+    ///   write accesses are forbidden.
+    pub unsafe fn from_raw_parts(begin: *mut u8, len: usize) -> Tlsf<'a> {
+        let slice = slice::from_raw_parts_mut(begin, len);
+        Tlsf::from_slice(slice)
+    }
+
+    /// Creates a [Tlsf] allocator using the given slice as its backing
+    /// memory.
+    pub fn from_slice(mem: &'a mut [u8]) -> Tlsf<'a> {
+        let tlsf = Tlsf {
+            state: UnsafeCell::new(State {
+                fl_bitmap: 0,
+                sl_bitmap: [0; FL_COUNT],
+                free: [[None; SL_COUNT]; FL_COUNT],
+                first: None,
+                used: 0,
+            }),
+            _marker: PhantomData,
+        };
+
+        let offset = mem.as_ptr().align_offset(ALIGN).min(mem.len());
+        let len = (mem.len() - offset) / ALIGN * ALIGN;
+        // The first block needs a header, and the sentinel block at the end
+        // needs room for a whole [Block] so it can be read like any other.
+        let Some(size) = len.checked_sub(mem::size_of::<Block>() + HEADER)
+        else {
+            return tlsf;
+        };
+        let size = size.min(MAX_SIZE);
+        if size < MIN_PAYLOAD {
+            return tlsf;
+        }
+
+        // SAFETY: both headers are within the slice, and aligned.
+        unsafe {
+            let first = NonNull::new_unchecked(mem.as_mut_ptr().add(offset))
+                .cast::<Block>();
+            first.write(Block {
+                prev_phys: None,
+                size: size | FREE,
+                next_free: None,
+                prev_free: None,
+            });
+            // The sentinel is never free, so the last real block can always
+            // look at the block after it.
+            Block::next_phys(first).write(Block {
+                prev_phys: Some(first),
+                size: PREV_FREE,
+                next_free: None,
+                prev_free: None,
+            });
+
+            let state = &mut *tlsf.state.get();
+            state.first = Some(first);
+            tlsf.insert(state, first);
+        }
+        tlsf
+    }
+
+    /// Returns statistics about how the memory is being used. This walks
+    /// every block, so it takes `O(n)` time.
+    pub fn stats(&self) -> TlsfStats {
+        let state = unsafe { &*self.state.get() };
+        let mut stats = TlsfStats {
+            used_bytes: state.used,
+            ..Default::default()
+        };
+        let mut block = state.first;
+        while let Some(b) = block {
+            let header = unsafe { b.as_ref() };
+            if header.size() == 0 {
+                break;
+            }
+            if header.is_free() {
+                stats.free_bytes += header.size();
+                stats.free_blocks += 1;
+                stats.largest_free_block =
+                    stats.largest_free_block.max(header.size());
+            }
+            block = Some(unsafe { Block::next_phys(b) });
+        }
+        stats
+    }
+
+    /// Adds the free `block` to the free list for its size.
+    unsafe fn insert(&self, state: &mut State, mut block: NonNull<Block>) {
+        let (fl, sl) = mapping(block.as_ref().size());
+        let head = state.free[fl][sl];
+        block.as_mut().next_free = head;
+        block.as_mut().prev_free = None;
+        if let Some(mut head) = head {
+            head.as_mut().prev_free = Some(block);
+        }
+        state.free[fl][sl] = Some(block);
+        state.fl_bitmap |= 1 << fl;
+        state.sl_bitmap[fl] |= 1 << sl;
+    }
+
+    /// Removes the free `block` from the free list for its size.
+    unsafe fn remove(&self, state: &mut State, block: NonNull<Block>) {
+        let (fl, sl) = mapping(block.as_ref().size());
+        let Block {
+            next_free,
+            prev_free,
+            ..
+        } = *block.as_ptr();
+        match prev_free {
+            Some(mut p) => p.as_mut().next_free = next_free,
+            None => state.free[fl][sl] = next_free,
+        }
+        if let Some(mut n) = next_free {
+            n.as_mut().prev_free = prev_free;
+        }
+        if state.free[fl][sl].is_none() {
+            state.sl_bitmap[fl] &= !(1 << sl);
+            if state.sl_bitmap[fl] == 0 {
+                state.fl_bitmap &= !(1 << fl);
+            }
+        }
+    }
+
+    /// Removes and returns a free block with a payload of at least `size`
+    /// bytes.
+    unsafe fn locate_free(
+        &self,
+        state: &mut State,
+        size: usize,
+    ) -> Result<NonNull<Block>, AllocError> {
+        let (mut fl, sl) = mapping(round_up(size));
+        if fl >= FL_COUNT {
+            return Err(AllocError);
+        }
+        let mut sl_map = state.sl_bitmap[fl] & (!0 << sl);
+        if sl_map == 0 {
+            let fl_map = state.fl_bitmap & (!0 << (fl + 1));
+            if fl_map == 0 {
+                return Err(AllocError);
+            }
+            fl = fl_map.trailing_zeros() as usize;
+            sl_map = state.sl_bitmap[fl];
+        }
+        let sl = sl_map.trailing_zeros() as usize;
+
+        // SAFETY: the bitmaps say this list isn't empty.
+        let block = state.free[fl][sl].unwrap_unchecked();
+        self.remove(state, block);
+        Ok(block)
+    }
+
+    /// Marks `block` as free, telling the block after it.
+    #[inline(always)]
+    unsafe fn mark_free(&self, mut block: NonNull<Block>) {
+        block.as_mut().set_flag(FREE, true);
+        let mut next = Block::next_phys(block);
+        next.as_mut().prev_phys = Some(block);
+        next.as_mut().set_flag(PREV_FREE, true);
+    }
+
+    /// Marks `block` as used, telling the block after it.
+    #[inline(always)]
+    unsafe fn mark_used(&self, mut block: NonNull<Block>) {
+        block.as_mut().set_flag(FREE, false);
+        Block::next_phys(block).as_mut().set_flag(PREV_FREE, false);
+    }
+
+    /// Splits the end of the used `block` off into a free block, if there's
+    /// enough left over after `size` bytes to make one, merging it with the
+    /// block after it if that's free too.
+    unsafe fn trim(
+        &self,
+        state: &mut State,
+        mut block: NonNull<Block>,
+        size: usize,
+    ) {
+        let total = block.as_ref().size();
+        if total < size + HEADER + MIN_PAYLOAD {
+            return;
+        }
+
+        block.as_mut().set_size(size);
+        let rest = Block::next_phys(block);
+        rest.write(Block {
+            prev_phys: None,
+            size: total - size - HEADER,
+            next_free: None,
+            prev_free: None,
+        });
+        self.mark_free(rest);
+        let rest = self.merge_next(state, rest);
+        self.insert(state, rest);
+    }
+
+    /// Splits the start of the free `block` off into its own free block, so
+    /// that the rest has its payload `gap` bytes later.
+    unsafe fn trim_start(
+        &self,
+        state: &mut State,
+        mut block: NonNull<Block>,
+        gap: usize,
+    ) -> NonNull<Block> {
+        let total = block.as_ref().size();
+        block.as_mut().set_size(gap - HEADER);
+        let rest = Block::next_phys(block);
+        rest.write(Block {
+            prev_phys: Some(block),
+            size: (total - gap) | FREE | PREV_FREE,
+            next_free: None,
+            prev_free: None,
+        });
+        Block::next_phys(rest).as_mut().prev_phys = Some(rest);
+        self.insert(state, block);
+        rest
+    }
+
+    /// Merges the free `block` with the block before it if that's free,
+    /// returning the merged block.
+    unsafe fn merge_prev(
+        &self,
+        state: &mut State,
+        block: NonNull<Block>,
+    ) -> NonNull<Block> {
+        if !block.as_ref().is_prev_free() {
+            return block;
+        }
+        let mut prev = block.as_ref().prev_phys.unwrap_unchecked();
+        self.remove(state, prev);
+        let size = prev.as_ref().size() + HEADER + block.as_ref().size();
+        prev.as_mut().set_size(size);
+        Block::next_phys(prev).as_mut().prev_phys = Some(prev);
+        prev
+    }
+
+    /// Merges the free `block` with the block after it if that's free,
+    /// returning the merged block.
+    unsafe fn merge_next(
+        &self,
+        state: &mut State,
+        mut block: NonNull<Block>,
+    ) -> NonNull<Block> {
+        let next = Block::next_phys(block);
+        if !next.as_ref().is_free() {
+            return block;
+        }
+        self.remove(state, next);
+        let size = block.as_ref().size() + HEADER + next.as_ref().size();
+        block.as_mut().set_size(size);
+        Block::next_phys(block).as_mut().prev_phys = Some(block);
+        block
+    }
+
+    /// Moves an allocation to a new block, for when it can't be resized in
+    /// place.
+    unsafe fn reallocate(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let new_ptr = self.allocate(new_layout)?;
+        ptr::copy_nonoverlapping(
+            ptr.as_ptr(),
+            new_ptr.as_ptr() as *mut u8,
+            old_layout.size().min(new_layout.size()),
+        );
+        self.deallocate(ptr, old_layout);
+        Ok(new_ptr)
+    }
+}
+
+unsafe impl Allocator for Tlsf<'_> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let size = adjust(layout.size())?;
+        // SAFETY: the allocator is !Sync and nothing holds a reference to the
+        //         state across calls.
+        let state = unsafe { &mut *self.state.get() };
+
+        let block = if layout.align() <= ALIGN {
+            unsafe { self.locate_free(state, size)? }
+        } else {
+            // Find a block with room to move the payload forward to an
+            // aligned address, leaving a free block before it.
+            let min_gap = HEADER + MIN_PAYLOAD;
+            let search = size
+                .checked_add(layout.align() + min_gap)
+                .filter(|&x| x <= MAX_SIZE)
+                .ok_or(AllocError)?;
+            let block = unsafe { self.locate_free(state, search)? };
+            let payload = unsafe { Block::payload(block) }.addr().get();
+            let mut gap = payload.next_multiple_of(layout.align()) - payload;
+            if gap != 0 && gap < min_gap {
+                gap = (payload + min_gap).next_multiple_of(layout.align())
+                    - payload;
+            }
+            match gap {
+                0 => block,
+                _ => unsafe { self.trim_start(state, block, gap) },
+            }
+        };
+
+        unsafe {
+            self.mark_used(block);
+            self.trim(state, block, size);
+            state.used += block.as_ref().size();
+            Ok(NonNull::slice_from_raw_parts(
+                Block::payload(block),
+                block.as_ref().size(),
+            ))
+        }
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, _: Layout) {
+        let state = &mut *self.state.get();
+        let block = Block::from_payload(ptr);
+        state.used -= block.as_ref().size();
+        self.mark_free(block);
+        let block = self.merge_prev(state, block);
+        let block = self.merge_next(state, block);
+        self.insert(state, block);
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let size = adjust(new_layout.size())?;
+        let state = &mut *self.state.get();
+        let mut block = Block::from_payload(ptr);
+        if ptr.addr().get() & (new_layout.align() - 1) != 0 {
+            return self.reallocate(ptr, old_layout, new_layout);
+        }
+
+        let old_size = block.as_ref().size();
+        if old_size < size {
+            let next = Block::next_phys(block);
+            if !next.as_ref().is_free()
+                || old_size + HEADER + next.as_ref().size() < size
+            {
+                return self.reallocate(ptr, old_layout, new_layout);
+            }
+            self.remove(state, next);
+            block
+                .as_mut()
+                .set_size(old_size + HEADER + next.as_ref().size());
+            self.mark_used(block);
+            self.trim(state, block, size);
+        }
+
+        let new_size = block.as_ref().size();
+        state.used = state.used - old_size + new_size;
+        Ok(NonNull::slice_from_raw_parts(ptr, new_size))
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let size = adjust(new_layout.size())?;
+        let state = &mut *self.state.get();
+        let block = Block::from_payload(ptr);
+        if ptr.addr().get() & (new_layout.align() - 1) != 0 {
+            return self.reallocate(ptr, old_layout, new_layout);
+        }
+
+        let old_size = block.as_ref().size();
+        self.trim(state, block, size);
+        let new_size = block.as_ref().size();
+        state.used = state.used - old_size + new_size;
+        Ok(NonNull::slice_from_raw_parts(ptr, new_size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::array::Array;
+
+    fn layout(size: usize, align: usize) -> Layout {
+        Layout::from_size_align(size, align).unwrap()
+    }
+
+    /// Walks every block, checking that the headers agree with each other
+    /// and with the free lists.
+    fn check(tlsf: &Tlsf) {
+        let state = unsafe { &*tlsf.state.get() };
+        let mut prev: Option<NonNull<Block>> = None;
+        let mut block = state.first;
+        let (mut free, mut used) = (0, 0);
+        while let Some(b) = block {
+            let header = unsafe { b.as_ref() };
+            let prev_free =
+                prev.is_some_and(|p| unsafe { p.as_ref() }.is_free());
+            assert_eq!(header.is_prev_free(), prev_free);
+            if prev_free {
+                assert_eq!(header.prev_phys, prev);
+                assert!(!header.is_free(), "free blocks weren't merged");
+            }
+            if header.size() == 0 {
+                break;
+            }
+            if header.is_free() {
+                let (fl, sl) = mapping(header.size());
+                let mut listed = state.free[fl][sl];
+                while listed.is_some_and(|x| x != b) {
+                    listed = unsafe { listed.unwrap().as_ref().next_free };
+                }
+                assert!(listed.is_some(), "free block isn't listed");
+                free += 1;
+            } else {
+                used += header.size();
+            }
+            prev = block;
+            block = Some(unsafe { Block::next_phys(b) });
+        }
+        assert_eq!(used, state.used);
+
+        let listed = state
+            .free
+            .iter()
+            .flatten()
+            .map(|&head| {
+                let mut count = 0;
+                let mut block = head;
+                while let Some(b) = block {
+                    count += 1;
+                    block = unsafe { b.as_ref().next_free };
+                }
+                count
+            })
+            .sum::<usize>();
+        assert_eq!(listed, free);
+    }
+
+    #[test]
+    fn tlsf_mapping() {
+        // The smallest size in each list.
+        let mut smallest = std::collections::HashMap::new();
+        let mut last = (0, 0);
+        for size in (MIN_PAYLOAD..1 << 16).step_by(ALIGN) {
+            let class = mapping(size);
+            assert!(class >= last);
+            last = class;
+            smallest.entry(class).or_insert(size);
+        }
+
+        // Anything in the list found for a rounded up size fits.
+        for size in (MIN_PAYLOAD..1 << 15).step_by(ALIGN) {
+            assert!(smallest[&mapping(round_up(size))] >= size);
+        }
+    }
+
+    #[test]
+    fn tlsf_alloc_and_merge() {
+        let mut mem = vec![0u8; 1 << 16];
+        let tlsf = Tlsf::from_slice(&mut mem);
+        let capacity = tlsf.stats().largest_free_block;
+        assert!(capacity > (1 << 16) - 64);
+
+        let blocks = (0..100)
+            .map(|i| tlsf.allocate(layout(i * 7 + 1, 8)).unwrap())
+            .collect::<Vec<_>>();
+        check(&tlsf);
+        for (i, block) in blocks.iter().enumerate() {
+            assert!(block.len() > i * 7);
+            unsafe { block.cast::<u8>().write_bytes(i as u8, block.len()) };
+        }
+
+        for i in (0..100).step_by(2).chain((1..100).step_by(2)) {
+            unsafe { tlsf.deallocate(blocks[i].cast(), layout(i * 7 + 1, 8)) };
+            check(&tlsf);
+        }
+        let stats = tlsf.stats();
+        assert_eq!(stats.free_blocks, 1);
+        assert_eq!(stats.largest_free_block, capacity);
+        assert_eq!(stats.used_bytes, 0);
+    }
+
+    #[test]
+    fn tlsf_alignment() {
+        let mut mem = vec![0u8; 1 << 16];
+        let tlsf = Tlsf::from_slice(&mut mem);
+        for align in [1, 8, 32, 256, 4096] {
+            let a = tlsf.allocate(layout(10, align)).unwrap();
+            assert_eq!(a.cast::<u8>().addr().get() % align, 0);
+            check(&tlsf);
+        }
+    }
+
+    #[test]
+    fn tlsf_grow_and_shrink_in_place() {
+        let mut mem = vec![0u8; 1 << 16];
+        let tlsf = Tlsf::from_slice(&mut mem);
+        let a = tlsf.allocate(layout(100, 8)).unwrap();
+        unsafe { a.cast::<u8>().write_bytes(5, 100) };
+        let b =
+            unsafe { tlsf.grow(a.cast(), layout(100, 8), layout(1000, 8)) }
+                .unwrap();
+        assert_eq!(a.cast::<u8>(), b.cast::<u8>());
+        assert!(b.len() >= 1000);
+        check(&tlsf);
+
+        // Once the next block is used, growing has to move.
+        let c = tlsf.allocate(layout(16, 8)).unwrap();
+        let d =
+            unsafe { tlsf.grow(b.cast(), layout(1000, 8), layout(2000, 8)) }
+                .unwrap();
+        assert_ne!(b.cast::<u8>(), d.cast::<u8>());
+        assert!(unsafe { &d.as_ref()[..100] }.iter().all(|&x| x == 5));
+        check(&tlsf);
+
+        let e =
+            unsafe { tlsf.shrink(d.cast(), layout(2000, 8), layout(10, 8)) }
+                .unwrap();
+        assert_eq!(d.cast::<u8>(), e.cast::<u8>());
+        check(&tlsf);
+        unsafe { tlsf.deallocate(c.cast(), layout(16, 8)) };
+        unsafe { tlsf.deallocate(e.cast(), layout(10, 8)) };
+        check(&tlsf);
+        assert_eq!(tlsf.stats().free_blocks, 1);
+    }
+
+    #[test]
+    fn tlsf_fragmentation() {
+        let mut mem = vec![0u8; 1 << 12];
+        let tlsf = Tlsf::from_slice(&mut mem);
+        assert_eq!(tlsf.stats().fragmentation(), 0.0);
+
+        let mut blocks = Vec::new();
+        while let Ok(x) = tlsf.allocate(layout(64, 8)) {
+            blocks.push(x);
+        }
+        assert!(tlsf.stats().largest_free_block < 64);
+        for x in blocks.iter().step_by(2) {
+            unsafe { tlsf.deallocate(x.cast(), layout(64, 8)) };
+        }
+        // Half the memory is free, but only in small pieces.
+        let stats = tlsf.stats();
+        assert!(stats.free_blocks >= blocks.len() / 2);
+        assert!(stats.largest_free_block < 128);
+        assert!(stats.fragmentation() > 0.9);
+        assert!(tlsf.allocate(layout(128, 8)).is_err());
+    }
+
+    #[test]
+    fn tlsf_tiny_buffer() {
+        let mut mem = [0u8; 16];
+        let tlsf = Tlsf::from_slice(&mut mem);
+        assert!(tlsf.allocate(layout(1, 1)).is_err());
+        assert_eq!(tlsf.stats(), TlsfStats::default());
+    }
+
+    #[test]
+    fn tlsf_random_workload() {
+        let mut mem = vec![0u8; 1 << 18];
+        let tlsf = Tlsf::from_slice(&mut mem);
+        let mut live = Vec::new();
+        let mut rng = 0x2545f4914f6cdd1du64;
+        for i in 0..5000 {
+            rng ^= rng << 13;
+            rng ^= rng >> 7;
+            rng ^= rng << 17;
+            let size = (rng % 3000) as usize + 1;
+            let align = 1 << (rng % 8);
+            if rng % 3 == 0 && !live.is_empty() {
+                let (ptr, layout): (NonNull<[u8]>, Layout) =
+                    live.swap_remove(rng as usize % live.len());
+                unsafe { tlsf.deallocate(ptr.cast(), layout) };
+            } else if let Ok(ptr) = tlsf.allocate(layout(size, align)) {
+                assert_eq!(ptr.cast::<u8>().addr().get() % align, 0);
+                live.push((ptr, layout(size, align)));
+            }
+            if i % 100 == 0 {
+                check(&tlsf);
+            }
+        }
+        for (ptr, layout) in live {
+            unsafe { tlsf.deallocate(ptr.cast(), layout) };
+        }
+        check(&tlsf);
+        assert_eq!(tlsf.stats().free_blocks, 1);
+    }
+
+    #[test]
+    fn tlsf_array() {
+        let mut mem = vec![0u8; 1 << 16];
+        let tlsf = Tlsf::from_slice(&mut mem);
+        let mut a = Array::new(&tlsf);
+        let mut b = Array::new(&tlsf);
+        for i in 0..1000u32 {
+            a.push(i).unwrap();
+            b.push(i * 2).unwrap();
+        }
+        drop(a);
+        check(&tlsf);
+        assert_eq!(b[999], 1998);
+    }
+}
@@ -0,0 +1,321 @@
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    cell::Cell,
+    ptr::NonNull,
+};
+
+/// The number of size classes in [Stats::histogram]. Allocations can't be
+/// larger than `isize::MAX` bytes, so the largest class is `2^63` bytes on
+/// 64-bit targets.
+pub const SIZE_CLASSES: usize = usize::BITS as usize;
+
+/// A snapshot of the measurements taken by a [Tracking] allocator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stats {
+    /// The number of bytes currently allocated.
+    pub live_bytes: usize,
+    /// The largest `live_bytes` has been since the allocator was created or
+    /// [Tracking::reset_peak] was called.
+    pub peak_bytes: usize,
+    /// The number of successful calls to [Allocator::allocate] and
+    /// [Allocator::allocate_zeroed].
+    pub allocations: usize,
+    /// The number of successful calls to [Allocator::grow] and
+    /// [Allocator::grow_zeroed].
+    pub grows: usize,
+    /// The number of successful calls to [Allocator::shrink].
+    pub shrinks: usize,
+    /// The number of calls to [Allocator::deallocate].
+    pub frees: usize,
+    /// The number of allocations, grows and shrinks which the inner allocator
+    /// failed.
+    pub failures: usize,
+    /// `histogram[i]` is the number of allocations whose size rounded up to
+    /// the next power of two is `2^i` bytes. Zero sized allocations are
+    /// counted in `histogram[0]`.
+    pub histogram: [usize; SIZE_CLASSES],
+}
+
+impl Stats {
+    /// Returns the index in [Stats::histogram] counting allocations of
+    /// `size` bytes.
+    #[inline(always)]
+    pub const fn size_class(size: usize) -> usize {
+        match size.checked_next_power_of_two() {
+            Some(x) => x.trailing_zeros() as usize,
+            None => SIZE_CLASSES - 1,
+        }
+    }
+}
+
+impl Default for Stats {
+    #[inline(always)]
+    fn default() -> Self {
+        Stats {
+            live_bytes: 0,
+            peak_bytes: 0,
+            allocations: 0,
+            grows: 0,
+            shrinks: 0,
+            frees: 0,
+            failures: 0,
+            histogram: [0; SIZE_CLASSES],
+        }
+    }
+}
+
+/// An allocator which passes everything through to another [Allocator],
+/// keeping count of how much memory it hands out.
+///
+/// This is meant for measuring how much memory some piece of code really
+/// needs, for example to decide how large an
+/// [Arena](super::Arena)'s first chunk or a
+/// [FixedBufferAllocator](super::FixedBufferAllocator)'s buffer should be.
+///
+/// Sizes are measured using the [Layout]s requested, not the sizes of the
+/// blocks returned by the inner allocator, and the slices returned are
+/// trimmed to the size requested to match.
+pub struct Tracking<A: Allocator> {
+    alloc: A,
+    stats: Cell<Stats>,
+}
+
+impl<A: Allocator> Tracking<A> {
+    /// Returns a [Tracking] allocator which allocates from `alloc`.
+    #[inline(always)]
+    pub fn new(alloc: A) -> Tracking<A> {
+        Tracking {
+            alloc,
+            stats: Cell::new(Stats::default()),
+        }
+    }
+
+    /// Returns everything measured so far.
+    #[inline(always)]
+    pub fn stats(&self) -> Stats {
+        self.stats.get()
+    }
+
+    /// Sets the peak to the number of bytes currently allocated, so that the
+    /// peak of some later section of code can be measured.
+    #[inline(always)]
+    pub fn reset_peak(&self) {
+        self.update(|x| x.peak_bytes = x.live_bytes);
+    }
+
+    /// Returns the allocator being tracked.
+    #[inline(always)]
+    pub fn inner(&self) -> &A {
+        &self.alloc
+    }
+
+    /// Returns the allocator being tracked.
+    ///
+    /// Anything still allocated from the [Tracking] allocator has to be
+    /// freed through the inner allocator.
+    #[inline(always)]
+    pub fn into_inner(self) -> A {
+        self.alloc
+    }
+
+    #[inline(always)]
+    fn update(&self, f: impl FnOnce(&mut Stats)) {
+        let mut stats = self.stats.get();
+        f(&mut stats);
+        stats.peak_bytes = stats.peak_bytes.max(stats.live_bytes);
+        self.stats.set(stats);
+    }
+
+    #[inline(always)]
+    fn record_allocation(
+        &self,
+        result: Result<NonNull<[u8]>, AllocError>,
+        layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        self.update(|x| match result {
+            Ok(_) => {
+                x.live_bytes += layout.size();
+                x.allocations += 1;
+                x.histogram[Stats::size_class(layout.size())] += 1;
+            }
+            Err(_) => x.failures += 1,
+        });
+        result.map(|x| NonNull::slice_from_raw_parts(x.cast(), layout.size()))
+    }
+
+    #[inline(always)]
+    fn record_resize(
+        &self,
+        result: Result<NonNull<[u8]>, AllocError>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        self.update(|x| match result {
+            Ok(_) if new_layout.size() >= old_layout.size() => {
+                x.live_bytes += new_layout.size() - old_layout.size();
+                x.grows += 1;
+            }
+            Ok(_) => {
+                x.live_bytes -= old_layout.size() - new_layout.size();
+                x.shrinks += 1;
+            }
+            Err(_) => x.failures += 1,
+        });
+        result.map(|x| {
+            NonNull::slice_from_raw_parts(x.cast(), new_layout.size())
+        })
+    }
+}
+
+unsafe impl<A: Allocator> Allocator for Tracking<A> {
+    #[inline(always)]
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        self.record_allocation(self.alloc.allocate(layout), layout)
+    }
+
+    #[inline(always)]
+    fn allocate_zeroed(
+        &self,
+        layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        self.record_allocation(self.alloc.allocate_zeroed(layout), layout)
+    }
+
+    #[inline(always)]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.alloc.deallocate(ptr, layout);
+        self.update(|x| {
+            x.live_bytes -= layout.size();
+            x.frees += 1;
+        });
+    }
+
+    #[inline(always)]
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let result = self.alloc.grow(ptr, old_layout, new_layout);
+        self.record_resize(result, old_layout, new_layout)
+    }
+
+    #[inline(always)]
+    unsafe fn grow_zeroed(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let result = self.alloc.grow_zeroed(ptr, old_layout, new_layout);
+        self.record_resize(result, old_layout, new_layout)
+    }
+
+    #[inline(always)]
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let result = self.alloc.shrink(ptr, old_layout, new_layout);
+        self.record_resize(result, old_layout, new_layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        alloc::{FixedBufferAllocator, Malloc, VirtualMemoryAllocator},
+        array::Array,
+    };
+
+    fn layout(size: usize, align: usize) -> Layout {
+        Layout::from_size_align(size, align).unwrap()
+    }
+
+    #[test]
+    fn tracking_counts_bytes() {
+        let tracking = Tracking::new(Malloc);
+        let a = tracking.allocate(layout(100, 8)).unwrap();
+        let b = tracking.allocate_zeroed(layout(28, 4)).unwrap();
+        assert_eq!(tracking.stats().live_bytes, 128);
+        assert_eq!(tracking.stats().allocations, 2);
+
+        let a =
+            unsafe { tracking.grow(a.cast(), layout(100, 8), layout(200, 8)) }
+                .unwrap();
+        let a = unsafe {
+            tracking.shrink(a.cast(), layout(200, 8), layout(50, 8))
+        }
+        .unwrap();
+        assert_eq!(tracking.stats().live_bytes, 78);
+        assert_eq!(tracking.stats().peak_bytes, 228);
+
+        unsafe { tracking.deallocate(a.cast(), layout(50, 8)) };
+        unsafe { tracking.deallocate(b.cast(), layout(28, 4)) };
+        let stats = tracking.stats();
+        assert_eq!(stats.live_bytes, 0);
+        assert_eq!(stats.peak_bytes, 228);
+        assert_eq!((stats.grows, stats.shrinks, stats.frees), (1, 1, 2));
+        assert_eq!(stats.failures, 0);
+    }
+
+    #[test]
+    fn tracking_histogram() {
+        assert_eq!(Stats::size_class(0), 0);
+        assert_eq!(Stats::size_class(1), 0);
+        assert_eq!(Stats::size_class(2), 1);
+        assert_eq!(Stats::size_class(3), 2);
+        assert_eq!(Stats::size_class(4096), 12);
+        assert_eq!(Stats::size_class(4097), 13);
+        assert_eq!(Stats::size_class(usize::MAX), SIZE_CLASSES - 1);
+
+        let mut buffer = [0u8; 256];
+        let fba = FixedBufferAllocator::from_slice(&mut buffer);
+        let tracking = Tracking::new(&fba);
+        for size in [1, 3, 4, 16, 17] {
+            tracking.allocate(layout(size, 1)).unwrap();
+        }
+        let histogram = tracking.stats().histogram;
+        assert_eq!(histogram[..6], [1, 0, 2, 0, 1, 1]);
+    }
+
+    #[test]
+    fn tracking_failures_and_peak_reset() {
+        let mut buffer = [0u8; 64];
+        let fba = FixedBufferAllocator::from_slice(&mut buffer);
+        let tracking = Tracking::new(&fba);
+        let a = tracking.allocate(layout(48, 1)).unwrap();
+        assert!(tracking.allocate(layout(32, 1)).is_err());
+        assert!(unsafe {
+            tracking.grow(a.cast(), layout(48, 1), layout(128, 1))
+        }
+        .is_err());
+        assert_eq!(tracking.stats().failures, 2);
+        assert_eq!(tracking.stats().live_bytes, 48);
+
+        unsafe { tracking.deallocate(a.cast(), layout(48, 1)) };
+        assert_eq!(tracking.stats().peak_bytes, 48);
+        tracking.reset_peak();
+        assert_eq!(tracking.stats().peak_bytes, 0);
+    }
+
+    #[test]
+    fn tracking_array_growth() {
+        let vm = VirtualMemoryAllocator::new(1 << 20).unwrap();
+        let tracking = Tracking::new(vm);
+        let mut array = Array::new(&tracking);
+        for i in 0..1000u32 {
+            array.push(i).unwrap();
+        }
+        let stats = tracking.stats();
+        assert_eq!(stats.live_bytes, array.capacity() * 4);
+        assert!(stats.allocations + stats.grows > 1);
+
+        drop(array);
+        assert_eq!(tracking.stats().live_bytes, 0);
+    }
+}
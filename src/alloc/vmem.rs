@@ -1,33 +1,204 @@
 use core::{
     alloc::{self, AllocError, Allocator},
-    ptr::NonNull,
+    cell::Cell,
+    mem::ManuallyDrop,
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
-use super::FixedBufferAllocator;
+use super::{AtomicFixedBufferAllocator, FixedBufferAllocator, Owns};
+
+/// The default number of bytes committed at a time by a
+/// [VirtualMemoryAllocator].
+const DEFAULT_COMMIT_GRANULARITY: usize = 64 * 1024;
+
+/// The size of a transparent huge page. Memory hinted to use them is aligned
+/// to and committed in multiples of this, since the kernel can only use a
+/// huge page for a range which is entirely mapped the same way.
+const TRANSPARENT_HUGE_PAGE_SIZE: usize = 2 << 20;
 
 /// An allocator which reserves a range of virtual memory with the size given,
 /// only committing it as needed.
+///
+/// The whole range is reserved as inaccessible memory up front, and is made
+/// readable and writable in chunks of the commit granularity as allocations
+/// reach into it. [VirtualMemoryAllocator::decommit_to] and
+/// [VirtualMemoryAllocator::reset] hand memory back to the OS, which is useful
+/// for long-lived programs which only occasionally need a lot of memory.
 pub struct VirtualMemoryAllocator {
-    addr: *const u8,
+    addr: *mut u8,
     size: usize,
+    /// The number of bytes from `addr` which are readable and writable.
+    /// Everything past this is mapped with no access permissions.
+    committed: Cell<usize>,
+    /// Always a multiple of the page size.
+    granularity: usize,
+    /// The size of the pages backing the reservation, which is larger than
+    /// the system's page size if explicit huge pages are used.
+    page_size: usize,
     fba: FixedBufferAllocator<'static>,
 }
 
+unsafe impl Send for VirtualMemoryAllocator {}
+
 impl VirtualMemoryAllocator {
     /// Reserves `size` bytes of virtual memory and returns a
     /// [FixedBufferAllocator] using that memory.
     pub fn new(
         size: usize,
     ) -> Result<VirtualMemoryAllocator, alloc::AllocError> {
-        unsafe { internal::virtual_memory_alloc(size) }.map(|addr| {
-            VirtualMemoryAllocator {
-                addr,
-                size,
-                fba: unsafe {
-                    FixedBufferAllocator::from_raw_parts(addr, size)
-                },
-            }
-        })
+        VirtualMemoryAllocator::with_commit_granularity(
+            size,
+            DEFAULT_COMMIT_GRANULARITY,
+        )
+    }
+
+    /// Reserves `size` bytes of virtual memory, committing `granularity`
+    /// bytes at a time as allocations need them.
+    ///
+    /// `granularity` is rounded up to a multiple of the page size.
+    #[inline(always)]
+    pub fn with_commit_granularity(
+        size: usize,
+        granularity: usize,
+    ) -> Result<VirtualMemoryAllocator, alloc::AllocError> {
+        VirtualMemoryAllocator::builder(size)
+            .commit_granularity(granularity)
+            .build()
+    }
+
+    /// Returns a [VirtualMemoryBuilder] for reserving `size` bytes of virtual
+    /// memory with options like huge pages and NUMA binding.
+    #[inline(always)]
+    pub const fn builder(size: usize) -> VirtualMemoryBuilder {
+        VirtualMemoryBuilder {
+            size,
+            granularity: DEFAULT_COMMIT_GRANULARITY,
+            transparent_huge_pages: false,
+            huge_pages: None,
+            populate: false,
+            numa_node: None,
+        }
+    }
+
+    /// Returns the size of the pages backing the memory. This is the size of
+    /// a huge page if [VirtualMemoryBuilder::huge_pages] was able to get
+    /// them, and the system's page size otherwise.
+    #[inline(always)]
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// Returns the number of bytes from the start of the reservation which
+    /// have been allocated. This can later be passed to
+    /// [VirtualMemoryAllocator::decommit_to] to free everything allocated
+    /// after this point.
+    #[inline(always)]
+    pub fn used(&self) -> usize {
+        self.size - self.fba.remaining()
+    }
+
+    /// Returns the number of bytes which are currently committed.
+    #[inline(always)]
+    pub fn committed(&self) -> usize {
+        self.committed.get()
+    }
+
+    /// Frees everything allocated after the first `mark` bytes, and returns
+    /// the committed memory past `mark` to the OS.
+    ///
+    /// `mark` is typically a value previously returned by
+    /// [VirtualMemoryAllocator::used]. If `mark` is past the currently used
+    /// memory, only the unused committed memory is returned.
+    pub fn decommit_to(&mut self, mark: usize) {
+        let mark = mark.min(self.used());
+        self.rewind_to(mark);
+
+        // SAFETY: nothing past `mark` is in use anymore.
+        let committed = unsafe {
+            decommit_past(
+                self.addr,
+                self.size,
+                self.granularity,
+                mark,
+                self.committed.get(),
+            )
+        };
+        self.committed.set(committed);
+    }
+
+    /// Frees everything allocated after the first `mark` bytes, but keeps
+    /// all the memory committed so it can be reused without asking the OS.
+    pub(crate) fn rewind_to(&mut self, mark: usize) {
+        let mark = mark.min(self.used());
+        // A FixedBufferAllocator has no notion of where its buffer started,
+        // so one starting at `mark` is the same as rewinding to `mark`.
+        //
+        // SAFETY: `mark <= size`, and since we have a mutable reference,
+        //         nothing allocated from the old fba can still be in use.
+        self.fba = unsafe {
+            FixedBufferAllocator::from_raw_parts(
+                self.addr.add(mark),
+                self.size - mark,
+            )
+        };
+    }
+
+    /// Frees everything allocated, returning all committed memory to the OS.
+    #[inline(always)]
+    pub fn reset(&mut self) {
+        self.decommit_to(0)
+    }
+
+    /// Makes sure the first `end` bytes of the reservation are committed.
+    fn commit(&self, end: usize) -> Result<(), AllocError> {
+        let committed = self.committed.get();
+        if end <= committed {
+            return Ok(());
+        }
+
+        // SAFETY: end <= size, since it comes from an allocation within the
+        //         reservation.
+        let new_committed = unsafe {
+            commit_to(self.addr, self.size, self.granularity, committed, end)?
+        };
+        self.committed.set(new_committed);
+        Ok(())
+    }
+
+    /// Commits the memory used by `result`, giving it back to the
+    /// [FixedBufferAllocator] if that fails.
+    #[inline(always)]
+    fn commit_allocation(
+        &self,
+        result: NonNull<[u8]>,
+        layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let end = result.as_ptr().addr() + result.len() - self.addr.addr();
+        if let Err(e) = self.commit(end) {
+            unsafe { self.fba.deallocate(result.cast(), layout) };
+            return Err(e);
+        }
+        Ok(result)
+    }
+
+    /// Moves an allocation to a new one, when it can't be resized in place.
+    /// The [FixedBufferAllocator] would do this on its own, but it doesn't
+    /// know that it has to commit the new memory before copying to it.
+    unsafe fn relocate(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: alloc::Layout,
+        new_layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let new_ptr = self.allocate(new_layout)?;
+        ptr::copy_nonoverlapping(
+            ptr.as_ptr(),
+            new_ptr.as_ptr() as *mut u8,
+            old_layout.size().min(new_layout.size()),
+        );
+        self.deallocate(ptr, old_layout);
+        Ok(new_ptr)
     }
 }
 
@@ -38,25 +209,574 @@ impl Drop for VirtualMemoryAllocator {
     }
 }
 
+impl Owns for VirtualMemoryAllocator {
+    #[inline(always)]
+    fn owns(&self, ptr: NonNull<u8>, layout: alloc::Layout) -> bool {
+        let addr = ptr.as_ptr().addr();
+        addr >= self.addr.addr()
+            && addr
+                .checked_add(layout.size())
+                .is_some_and(|end| end <= self.addr.addr() + self.size)
+    }
+}
+
 unsafe impl Allocator for VirtualMemoryAllocator {
     #[inline(always)]
     fn allocate(
         &self,
         layout: alloc::Layout,
     ) -> Result<NonNull<[u8]>, AllocError> {
-        self.fba.allocate(layout)
+        let result = self.fba.allocate(layout)?;
+        self.commit_allocation(result, layout)
+    }
+
+    #[inline(always)]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: alloc::Layout) {
+        self.fba.deallocate(ptr, layout)
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: alloc::Layout,
+        new_layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if old_layout.align() != new_layout.align() {
+            return self.relocate(ptr, old_layout, new_layout);
+        }
+
+        // Only the last allocation can grow in place, anything else has to
+        // move.
+        let Ok(result) = self.fba.grow(ptr, old_layout, new_layout) else {
+            return self.relocate(ptr, old_layout, new_layout);
+        };
+        let end = result.as_ptr().addr() + result.len() - self.addr.addr();
+        if let Err(e) = self.commit(end) {
+            // Growing only moved the cursor, so shrinking it back leaves the
+            // allocation exactly as it was.
+            let _ = self.fba.shrink(result.cast(), new_layout, old_layout);
+            return Err(e);
+        }
+        Ok(result)
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: alloc::Layout,
+        new_layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if old_layout.align() != new_layout.align() {
+            return self.relocate(ptr, old_layout, new_layout);
+        }
+
+        self.fba.shrink(ptr, old_layout, new_layout)
+    }
+}
+
+/// Commits the memory of a reservation from `committed` up to `end` rounded
+/// up to the granularity, and returns the new number of committed bytes.
+///
+/// # Safety
+///
+/// `addr` must point to a reservation of `size` bytes, of which the first
+/// `committed` are committed, and `committed < end <= size`.
+unsafe fn commit_to(
+    addr: *mut u8,
+    size: usize,
+    granularity: usize,
+    committed: usize,
+    end: usize,
+) -> Result<usize, AllocError> {
+    let new_committed = end.next_multiple_of(granularity).min(size);
+    internal::virtual_memory_commit(
+        addr.add(committed),
+        new_committed - committed,
+    )?;
+    Ok(new_committed)
+}
+
+/// Decommits the memory of a reservation past `mark` rounded up to the
+/// granularity, and returns the new number of committed bytes.
+///
+/// # Safety
+///
+/// `addr` must point to a reservation of `size` bytes, of which the first
+/// `committed` are committed, and nothing past `mark` can be in use.
+unsafe fn decommit_past(
+    addr: *mut u8,
+    size: usize,
+    granularity: usize,
+    mark: usize,
+    committed: usize,
+) -> usize {
+    let keep = mark.next_multiple_of(granularity).min(size);
+    if keep >= committed {
+        return committed;
+    }
+
+    if internal::virtual_memory_decommit(addr.add(keep), committed - keep) {
+        keep
+    } else {
+        committed
+    }
+}
+
+/// A [VirtualMemoryAllocator] which can be shared between threads, returned
+/// by [VirtualMemoryBuilder::build_atomic].
+///
+/// Memory is allocated from the reservation with an
+/// [AtomicFixedBufferAllocator], so threads allocate from it at once without
+/// locking. Like the [VirtualMemoryAllocator], only the last allocation can
+/// be freed or resized in place.
+pub struct AtomicVirtualMemoryAllocator {
+    addr: *mut u8,
+    size: usize,
+    /// The number of bytes from `addr` which are readable and writable. It
+    /// only grows while the allocator is shared, so memory below it is always
+    /// committed.
+    committed: AtomicUsize,
+    /// Always a multiple of the page size.
+    granularity: usize,
+    page_size: usize,
+    fba: AtomicFixedBufferAllocator<'static>,
+}
+
+unsafe impl Send for AtomicVirtualMemoryAllocator {}
+unsafe impl Sync for AtomicVirtualMemoryAllocator {}
+
+impl AtomicVirtualMemoryAllocator {
+    /// Reserves `size` bytes of virtual memory and returns an
+    /// [AtomicVirtualMemoryAllocator] using that memory.
+    #[inline(always)]
+    pub fn new(
+        size: usize,
+    ) -> Result<AtomicVirtualMemoryAllocator, AllocError> {
+        VirtualMemoryAllocator::builder(size).build_atomic()
+    }
+
+    /// Returns the size of the pages backing the memory.
+    #[inline(always)]
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// Returns the number of bytes from the start of the reservation which
+    /// have been allocated.
+    #[inline(always)]
+    pub fn used(&self) -> usize {
+        self.size - self.fba.remaining()
+    }
+
+    /// Returns the number of bytes which are currently committed.
+    #[inline(always)]
+    pub fn committed(&self) -> usize {
+        self.committed.load(Ordering::Acquire)
+    }
+
+    /// Frees everything allocated after the first `mark` bytes, and returns
+    /// the committed memory past `mark` to the OS.
+    pub fn decommit_to(&mut self, mark: usize) {
+        let mark = mark.min(self.used());
+        // SAFETY: `mark <= size`, and since we have a mutable reference,
+        //         nothing allocated past `mark` can still be in use.
+        self.fba = unsafe {
+            AtomicFixedBufferAllocator::from_raw_parts(
+                self.addr.add(mark),
+                self.size - mark,
+            )
+        };
+        let committed = self.committed.get_mut();
+        *committed = unsafe {
+            decommit_past(
+                self.addr,
+                self.size,
+                self.granularity,
+                mark,
+                *committed,
+            )
+        };
+    }
+
+    /// Frees everything allocated, returning all committed memory to the OS.
+    #[inline(always)]
+    pub fn reset(&mut self) {
+        self.decommit_to(0)
+    }
+
+    /// Makes sure the first `end` bytes of the reservation are committed.
+    ///
+    /// Threads racing to commit may commit some of the same pages, which is
+    /// harmless since committing memory twice does nothing. The count is only
+    /// raised once the memory is committed, so any memory below it can be
+    /// used right away.
+    fn commit(&self, end: usize) -> Result<(), AllocError> {
+        let committed = self.committed.load(Ordering::Acquire);
+        if end <= committed {
+            return Ok(());
+        }
+
+        // SAFETY: end <= size, since it comes from an allocation within the
+        //         reservation.
+        let new_committed = unsafe {
+            commit_to(self.addr, self.size, self.granularity, committed, end)?
+        };
+        self.committed.fetch_max(new_committed, Ordering::AcqRel);
+        Ok(())
+    }
+
+    /// Moves an allocation to a new one, when it can't be resized in place.
+    unsafe fn relocate(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: alloc::Layout,
+        new_layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let new_ptr = self.allocate(new_layout)?;
+        ptr::copy_nonoverlapping(
+            ptr.as_ptr(),
+            new_ptr.as_ptr() as *mut u8,
+            old_layout.size().min(new_layout.size()),
+        );
+        self.deallocate(ptr, old_layout);
+        Ok(new_ptr)
+    }
+}
+
+impl Drop for AtomicVirtualMemoryAllocator {
+    #[inline(always)]
+    fn drop(&mut self) {
+        unsafe { internal::virtual_memory_free(self.addr, self.size) }
+    }
+}
+
+impl Owns for AtomicVirtualMemoryAllocator {
+    /// Checks against the whole reservation rather than `fba`, which only
+    /// covers the memory past the mark after [decommit_to](Self::decommit_to).
+    #[inline(always)]
+    fn owns(&self, ptr: NonNull<u8>, layout: alloc::Layout) -> bool {
+        let addr = ptr.as_ptr().addr();
+        addr >= self.addr.addr()
+            && addr
+                .checked_add(layout.size())
+                .is_some_and(|end| end <= self.addr.addr() + self.size)
+    }
+}
+
+unsafe impl Allocator for AtomicVirtualMemoryAllocator {
+    fn allocate(
+        &self,
+        layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let result = self.fba.allocate(layout)?;
+        let end = result.as_ptr().addr() + result.len() - self.addr.addr();
+        if let Err(e) = self.commit(end) {
+            unsafe { self.fba.deallocate(result.cast(), layout) };
+            return Err(e);
+        }
+        Ok(result)
     }
 
     #[inline(always)]
     unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: alloc::Layout) {
         self.fba.deallocate(ptr, layout)
     }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: alloc::Layout,
+        new_layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if old_layout.align() != new_layout.align() {
+            return self.relocate(ptr, old_layout, new_layout);
+        }
+
+        // Only the last allocation can grow in place, anything else has to
+        // move.
+        let Ok(result) = self.fba.grow(ptr, old_layout, new_layout) else {
+            return self.relocate(ptr, old_layout, new_layout);
+        };
+        let end = result.as_ptr().addr() + result.len() - self.addr.addr();
+        if let Err(e) = self.commit(end) {
+            // This only fails to give the memory back if another thread
+            // allocated after it, in which case it's lost until a reset.
+            let _ = self.fba.shrink(result.cast(), new_layout, old_layout);
+            return Err(e);
+        }
+        Ok(result)
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: alloc::Layout,
+        new_layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if old_layout.align() != new_layout.align() {
+            return self.relocate(ptr, old_layout, new_layout);
+        }
+
+        self.fba.shrink(ptr, old_layout, new_layout)
+    }
+}
+
+/// The size of the explicit huge pages a [VirtualMemoryAllocator] can be
+/// backed by. See [VirtualMemoryBuilder::huge_pages].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HugePageSize {
+    /// 2 MiB pages.
+    Size2MiB,
+    /// 1 GiB pages.
+    Size1GiB,
+}
+
+impl HugePageSize {
+    /// Returns the size of the page in bytes.
+    #[inline(always)]
+    pub const fn bytes(self) -> usize {
+        match self {
+            HugePageSize::Size2MiB => 2 << 20,
+            HugePageSize::Size1GiB => 1 << 30,
+        }
+    }
+}
+
+/// Options for reserving the memory of a [VirtualMemoryAllocator], returned
+/// by [VirtualMemoryAllocator::builder].
+///
+/// Programs which reserve many gigabytes spend a lot of time on TLB misses
+/// with the system's normal pages. Huge pages cover far more memory with
+/// each TLB entry, and prefaulting and NUMA binding avoid paying for page
+/// faults and remote memory accesses while the program is running.
+///
+/// ```
+/// use stdx::alloc::{HugePageSize, VirtualMemoryAllocator};
+///
+/// let vm = VirtualMemoryAllocator::builder(1 << 30)
+///     .huge_pages(HugePageSize::Size2MiB)
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct VirtualMemoryBuilder {
+    size: usize,
+    granularity: usize,
+    transparent_huge_pages: bool,
+    huge_pages: Option<HugePageSize>,
+    populate: bool,
+    numa_node: Option<usize>,
+}
+
+impl VirtualMemoryBuilder {
+    /// Sets the number of bytes committed at a time as allocations need them.
+    /// It's rounded up to a multiple of the page size.
+    #[inline(always)]
+    pub const fn commit_granularity(
+        mut self,
+        granularity: usize,
+    ) -> VirtualMemoryBuilder {
+        self.granularity = granularity;
+        self
+    }
+
+    /// Hints that the memory should be backed by transparent huge pages with
+    /// `madvise(MADV_HUGEPAGE)`, which is needed when the system only uses
+    /// them where asked to.
+    ///
+    /// The reservation is aligned to, and committed in multiples of, the
+    /// size of a huge page, so the kernel is able to use them everywhere.
+    /// This does nothing on systems without transparent huge pages.
+    #[inline(always)]
+    pub const fn transparent_huge_pages(mut self) -> VirtualMemoryBuilder {
+        self.transparent_huge_pages = true;
+        self
+    }
+
+    /// Backs the memory with explicit huge pages of the size given, using
+    /// `MAP_HUGETLB`. The size of the reservation is rounded up to a multiple
+    /// of the huge page size.
+    ///
+    /// Explicit huge pages come from a pool the system's administrator has
+    /// to set aside, so there may not be enough of them. In that case, 1 GiB
+    /// pages fall back to 2 MiB pages, and 2 MiB pages fall back to the
+    /// system's normal pages with a transparent huge page hint.
+    /// [VirtualMemoryAllocator::page_size] tells which pages were used.
+    #[inline(always)]
+    pub const fn huge_pages(
+        mut self,
+        size: HugePageSize,
+    ) -> VirtualMemoryBuilder {
+        self.huge_pages = Some(size);
+        self
+    }
+
+    /// Commits and faults in all of the memory up front, so nothing has to be
+    /// faulted in as it's allocated.
+    ///
+    /// This uses `MAP_POPULATE` where it can, but memory which is bound to a
+    /// NUMA node or uses transparent huge pages has to be faulted in after
+    /// it's mapped, with `madvise(MADV_POPULATE_WRITE)`.
+    #[inline(always)]
+    pub const fn populate(mut self) -> VirtualMemoryBuilder {
+        self.populate = true;
+        self
+    }
+
+    /// Binds the memory to the NUMA node given with `mbind`, so it's always
+    /// allocated from that node's memory. Building fails if the node doesn't
+    /// exist, or the system doesn't support NUMA.
+    #[inline(always)]
+    pub const fn numa_node(mut self, node: usize) -> VirtualMemoryBuilder {
+        self.numa_node = Some(node);
+        self
+    }
+
+    /// Reserves the memory and returns a [VirtualMemoryAllocator] using it.
+    pub fn build(self) -> Result<VirtualMemoryAllocator, AllocError> {
+        let mut page_size = internal::page_size();
+        let mut size = self.size;
+        let mut addr = None;
+        // NUMA binding has to happen before pages are faulted in, so they're
+        // faulted in afterwards rather than when they're mapped.
+        let populate_huge = self.populate && self.numa_node.is_none();
+        let huge_pages: &[HugePageSize] = match self.huge_pages {
+            Some(HugePageSize::Size1GiB) => {
+                &[HugePageSize::Size1GiB, HugePageSize::Size2MiB]
+            }
+            Some(HugePageSize::Size2MiB) => &[HugePageSize::Size2MiB],
+            None => &[],
+        };
+        for &huge in huge_pages {
+            let Some(huge_size) = size.checked_next_multiple_of(huge.bytes())
+            else {
+                continue;
+            };
+            let result = unsafe {
+                internal::virtual_memory_map(
+                    huge_size,
+                    Some(huge.bytes()),
+                    populate_huge,
+                )
+            };
+            if let Ok(a) = result {
+                addr = Some((a, populate_huge));
+                size = huge_size;
+                page_size = huge.bytes();
+                break;
+            }
+        }
+
+        // Falling back from explicit huge pages still tries to get huge
+        // pages, just transparently.
+        let transparent = self.transparent_huge_pages
+            || (self.huge_pages.is_some() && addr.is_none());
+        let populate = populate_huge && !transparent;
+        let (addr, populated) = match addr {
+            Some(addr) => addr,
+            None if transparent => {
+                (reserve_aligned(size, TRANSPARENT_HUGE_PAGE_SIZE)?, false)
+            }
+            None => unsafe {
+                (
+                    internal::virtual_memory_map(size, None, populate)?,
+                    populate,
+                )
+            },
+        };
+
+        let granularity = if transparent {
+            self.granularity.max(TRANSPARENT_HUGE_PAGE_SIZE)
+        } else {
+            self.granularity
+        };
+        let granularity =
+            match granularity.max(1).checked_next_multiple_of(page_size) {
+                Some(g) => g,
+                None => {
+                    unsafe { internal::virtual_memory_free(addr, size) };
+                    return Err(AllocError);
+                }
+            };
+
+        // Anything which fails from here on frees the reservation when this
+        // is dropped.
+        let vm = VirtualMemoryAllocator {
+            addr,
+            size,
+            committed: Cell::new(if populated { size } else { 0 }),
+            granularity,
+            page_size,
+            fba: unsafe { FixedBufferAllocator::from_raw_parts(addr, size) },
+        };
+
+        if transparent {
+            // This is only a hint, so it's fine if it isn't supported.
+            unsafe { internal::virtual_memory_advise_huge(addr, size) };
+        }
+        if let Some(node) = self.numa_node {
+            unsafe { internal::virtual_memory_bind(addr, size, node)? };
+        }
+        if self.populate && !populated {
+            vm.commit(size)?;
+            unsafe {
+                internal::virtual_memory_populate(addr, size, page_size)
+            };
+        }
+        Ok(vm)
+    }
+}
+
+impl VirtualMemoryBuilder {
+    /// Reserves the memory and returns an [AtomicVirtualMemoryAllocator]
+    /// using it, which can be shared between threads.
+    pub fn build_atomic(
+        self,
+    ) -> Result<AtomicVirtualMemoryAllocator, AllocError> {
+        // The reservation now belongs to the atomic allocator, which frees
+        // it when it's dropped.
+        let vm = ManuallyDrop::new(self.build()?);
+        Ok(AtomicVirtualMemoryAllocator {
+            addr: vm.addr,
+            size: vm.size,
+            committed: AtomicUsize::new(vm.committed.get()),
+            granularity: vm.granularity,
+            page_size: vm.page_size,
+            fba: unsafe {
+                AtomicFixedBufferAllocator::from_raw_parts(vm.addr, vm.size)
+            },
+        })
+    }
+}
+
+/// Reserves `size` bytes of virtual memory starting at an address which is a
+/// multiple of `align`.
+fn reserve_aligned(size: usize, align: usize) -> Result<*mut u8, AllocError> {
+    let len = size.checked_add(align).ok_or(AllocError)?;
+    let base = unsafe { internal::virtual_memory_reserve(len)? };
+    let start = base.addr().next_multiple_of(align);
+    let end = start + size.next_multiple_of(internal::page_size());
+    unsafe {
+        if start > base.addr() {
+            internal::virtual_memory_free(base, start - base.addr());
+        }
+        if end < base.addr() + len {
+            internal::virtual_memory_free(
+                base.with_addr(end),
+                base.addr() + len - end,
+            );
+        }
+    }
+    Ok(base.with_addr(start))
 }
 
 #[cfg(unix)]
-mod internal {
+pub(super) mod internal {
+    #[cfg(target_os = "linux")]
+    use core::ffi::{c_long, c_uint, c_ulong};
     use core::{alloc::AllocError, ffi::c_int, ptr};
 
+    const PROT_NONE: c_int = 0;
     const PROT_READ: c_int = 1 << 0;
     const PROT_WRITE: c_int = 1 << 1;
 
@@ -72,8 +792,42 @@ mod internal {
     #[cfg(target_os = "linux")]
     const MAP_ANON: c_int = 1 << 5;
 
+    #[cfg(target_os = "linux")]
+    const MAP_POPULATE: c_int = 0x8000;
+    #[cfg(target_os = "linux")]
+    const MAP_HUGETLB: c_int = 0x40000;
+    /// The huge page size is encoded as its base 2 logarithm shifted by this
+    /// much in the flags given to mmap.
+    #[cfg(target_os = "linux")]
+    const MAP_HUGE_SHIFT: u32 = 26;
+
     const MAP_FAILED: usize = usize::MAX;
 
+    const MADV_DONTNEED: c_int = 4;
+    #[cfg(target_os = "linux")]
+    const MADV_HUGEPAGE: c_int = 14;
+    #[cfg(target_os = "linux")]
+    const MADV_POPULATE_WRITE: c_int = 23;
+
+    #[cfg(target_os = "linux")]
+    const MPOL_BIND: c_int = 2;
+    /// Moves pages which were already faulted in to the nodes being bound
+    /// to.
+    #[cfg(target_os = "linux")]
+    const MPOL_MF_MOVE: c_uint = 1 << 1;
+    /// The number of NUMA nodes which can be passed to mbind. This is the
+    /// largest number the kernel supports by default.
+    #[cfg(target_os = "linux")]
+    const MAX_NUMA_NODES: usize = 1024;
+
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    const SYS_MBIND: c_long = 237;
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "aarch64", target_arch = "riscv64"),
+    ))]
+    const SYS_MBIND: c_long = 235;
+
     #[link(name = "c")]
     extern "C" {
         fn mmap(
@@ -85,54 +839,484 @@ mod internal {
             offset: isize,
         ) -> *mut u8;
         fn munmap(addr: *const u8, len: usize) -> c_int;
+        fn mprotect(addr: *mut u8, len: usize, prot: c_int) -> c_int;
+        fn madvise(addr: *mut u8, len: usize, advice: c_int) -> c_int;
+        fn getpagesize() -> c_int;
+        #[cfg(target_os = "linux")]
+        fn syscall(number: c_long, ...) -> c_long;
     }
 
     #[inline(always)]
-    pub unsafe fn virtual_memory_alloc(
+    pub fn page_size() -> usize {
+        unsafe { getpagesize() as usize }
+    }
+
+    /// Reserves `size` bytes of address space which can't be accessed until
+    /// it's committed with [virtual_memory_commit].
+    #[inline(always)]
+    pub unsafe fn virtual_memory_reserve(
         size: usize,
     ) -> Result<*mut u8, AllocError> {
-        let ret = mmap(
-            ptr::null_mut(),
-            size,
-            PROT_READ | PROT_WRITE,
-            MAP_PRIVATE | MAP_ANON,
-            -1,
-            0,
-        );
+        virtual_memory_map(size, None, false)
+    }
+
+    /// Reserves `size` bytes of address space like [virtual_memory_reserve].
+    ///
+    /// If `huge_page_size` is given, the memory is backed by explicit huge
+    /// pages of that size, and `size` must be a multiple of it. If `populate`
+    /// is true, the memory is committed and faulted in right away.
+    pub unsafe fn virtual_memory_map(
+        size: usize,
+        huge_page_size: Option<usize>,
+        populate: bool,
+    ) -> Result<*mut u8, AllocError> {
+        let mut flags = MAP_PRIVATE | MAP_ANON;
+        if let Some(huge) = huge_page_size {
+            #[cfg(target_os = "linux")]
+            {
+                flags |= MAP_HUGETLB
+                    | ((huge.trailing_zeros() << MAP_HUGE_SHIFT) as c_int);
+            }
+            #[cfg(not(target_os = "linux"))]
+            {
+                let _ = huge;
+                return Err(AllocError);
+            }
+        }
+        let prot = if populate {
+            #[cfg(target_os = "linux")]
+            {
+                flags |= MAP_POPULATE;
+            }
+            PROT_READ | PROT_WRITE
+        } else {
+            PROT_NONE
+        };
+
+        let ret = mmap(ptr::null_mut(), size, prot, flags, -1, 0);
         if ret.addr() == MAP_FAILED {
+            return Err(AllocError);
+        }
+        #[cfg(not(target_os = "linux"))]
+        if populate {
+            virtual_memory_populate(ret, size, page_size());
+        }
+        Ok(ret)
+    }
+
+    /// Hints that the `len` bytes starting at the page aligned `addr` should
+    /// be backed by transparent huge pages. Returns false if the system
+    /// doesn't support them.
+    #[inline(always)]
+    pub unsafe fn virtual_memory_advise_huge(
+        addr: *mut u8,
+        len: usize,
+    ) -> bool {
+        #[cfg(target_os = "linux")]
+        return madvise(addr, len, MADV_HUGEPAGE) == 0;
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = (addr, len);
+            false
+        }
+    }
+
+    /// Binds the `len` bytes starting at the page aligned `addr` to the NUMA
+    /// node given, moving any pages which were already faulted in.
+    pub unsafe fn virtual_memory_bind(
+        addr: *mut u8,
+        len: usize,
+        node: usize,
+    ) -> Result<(), AllocError> {
+        #[cfg(all(
+            target_os = "linux",
+            any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "riscv64",
+            ),
+        ))]
+        {
+            const BITS: usize = c_ulong::BITS as usize;
+            if node >= MAX_NUMA_NODES {
+                return Err(AllocError);
+            }
+            let mut mask = [0 as c_ulong; MAX_NUMA_NODES / BITS];
+            mask[node / BITS] |= 1 << (node % BITS);
+            // The kernel ignores the last bit of the mask, so the number of
+            // nodes passed is one more than the mask holds. syscall reads
+            // every argument as a full word, so the mode and flags have to be
+            // widened or their upper bits are garbage.
+            let ret = syscall(
+                SYS_MBIND,
+                addr,
+                len,
+                MPOL_BIND as c_ulong,
+                mask.as_ptr(),
+                (MAX_NUMA_NODES + 1) as c_ulong,
+                MPOL_MF_MOVE as c_ulong,
+            );
+            if ret != 0 {
+                return Err(AllocError);
+            }
+            Ok(())
+        }
+        #[cfg(not(all(
+            target_os = "linux",
+            any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "riscv64",
+            ),
+        )))]
+        {
+            let _ = (addr, len, node);
             Err(AllocError)
-        } else {
-            Ok(ret)
         }
     }
 
+    /// Faults in the `len` bytes of committed memory starting at the page
+    /// aligned `addr`, which must not have been written to yet.
+    pub unsafe fn virtual_memory_populate(
+        addr: *mut u8,
+        len: usize,
+        page_size: usize,
+    ) {
+        #[cfg(target_os = "linux")]
+        if madvise(addr, len, MADV_POPULATE_WRITE) == 0 {
+            return;
+        }
+        // Older kernels don't have MADV_POPULATE_WRITE, so touch every page
+        // instead. Fresh pages are always zeroed, so writing a zero doesn't
+        // change anything.
+        for offset in (0..len).step_by(page_size) {
+            ptr::write_volatile(addr.add(offset), 0);
+        }
+    }
+
+    /// Makes `len` bytes starting at the page aligned `addr` readable and
+    /// writable.
+    #[inline(always)]
+    pub unsafe fn virtual_memory_commit(
+        addr: *mut u8,
+        len: usize,
+    ) -> Result<(), AllocError> {
+        if mprotect(addr, len, PROT_READ | PROT_WRITE) != 0 {
+            return Err(AllocError);
+        }
+        Ok(())
+    }
+
+    /// Returns the physical memory backing `len` bytes starting at the page
+    /// aligned `addr` to the OS and makes it inaccessible again. Returns
+    /// false if the memory is still committed.
+    #[inline(always)]
+    pub unsafe fn virtual_memory_decommit(addr: *mut u8, len: usize) -> bool {
+        if madvise(addr, len, MADV_DONTNEED) != 0 {
+            return false;
+        }
+        mprotect(addr, len, PROT_NONE) == 0
+    }
+
     #[inline(always)]
     pub unsafe fn virtual_memory_free(addr: *const u8, size: usize) {
         munmap(addr, size);
     }
 }
 
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
-
-//     fn layout(size: usize, align: usize) -> alloc::Layout {
-//         alloc::Layout::from_size_align(size, align).unwrap()
-//     }
-
-//     /// The purpose of this test is not to evaluate if the system's
-//     /// virtual_malloc/free work, rather, its just to ensure we can use the
-//     /// functions successfully.
-//     #[test]
-//     fn virutal_memory_works() {
-//         let vm = VirtualMemoryAllocator::new(1);
-//         assert!(vm.is_ok());
-//         let vm = vm.unwrap();
-//         let result = vm.allocate(layout(1, 1));
-//         assert!(result.is_ok());
-//         let alloc = result.unwrap();
-//         unsafe { alloc.cast::<u8>().as_ptr().write(0) };
-//         assert_eq!(alloc.len(), 1);
-//         unsafe { vm.deallocate(alloc.cast(), layout(1, 1)) };
-//     }
-// }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::array::Array;
+
+    fn layout(size: usize, align: usize) -> alloc::Layout {
+        alloc::Layout::from_size_align(size, align).unwrap()
+    }
+
+    /// The purpose of this test is not to evaluate if the system's
+    /// virtual_malloc/free work, rather, its just to ensure we can use the
+    /// functions successfully.
+    #[test]
+    fn virutal_memory_works() {
+        let vm = VirtualMemoryAllocator::new(1);
+        assert!(vm.is_ok());
+        let vm = vm.unwrap();
+        let result = vm.allocate(layout(1, 1));
+        assert!(result.is_ok());
+        let alloc = result.unwrap();
+        unsafe { alloc.cast::<u8>().as_ptr().write(0) };
+        assert_eq!(alloc.len(), 1);
+        unsafe { vm.deallocate(alloc.cast(), layout(1, 1)) };
+    }
+
+    #[test]
+    fn virtual_memory_commits_on_demand() {
+        let page = internal::page_size();
+        let vm =
+            VirtualMemoryAllocator::with_commit_granularity(page * 16, page)
+                .unwrap();
+        assert_eq!(vm.committed(), 0);
+
+        let a = vm.allocate(layout(1, 1)).unwrap();
+        unsafe { a.cast::<u8>().as_ptr().write(1) };
+        assert_eq!(vm.committed(), page);
+
+        let b = vm.allocate(layout(page * 2, 1)).unwrap();
+        unsafe { b.cast::<u8>().as_ptr().add(page * 2 - 1).write(1) };
+        assert_eq!(vm.committed(), page * 3);
+    }
+
+    #[test]
+    fn virtual_memory_grow_commits() {
+        let page = internal::page_size();
+        let vm =
+            VirtualMemoryAllocator::with_commit_granularity(page * 16, page)
+                .unwrap();
+
+        let a = vm.allocate(layout(8, 8)).unwrap();
+        let a =
+            unsafe { vm.grow(a.cast(), layout(8, 8), layout(page * 4, 8)) }
+                .unwrap();
+        unsafe { a.cast::<u8>().as_ptr().add(page * 4 - 1).write(1) };
+        assert_eq!(vm.committed(), page * 4);
+    }
+
+    #[test]
+    fn virtual_memory_grow_moves_earlier_allocations() {
+        let page = internal::page_size();
+        let vm =
+            VirtualMemoryAllocator::with_commit_granularity(page * 16, page)
+                .unwrap();
+
+        let a = vm.allocate(layout(8, 8)).unwrap();
+        let b = vm.allocate(layout(8, 8)).unwrap();
+        unsafe { a.cast::<u64>().as_ptr().write(1) };
+        unsafe { b.cast::<u64>().as_ptr().write(2) };
+        let a =
+            unsafe { vm.grow(a.cast(), layout(8, 8), layout(page * 2, 8)) }
+                .unwrap();
+        unsafe { a.cast::<u8>().as_ptr().add(page * 2 - 1).write(1) };
+        assert_eq!(unsafe { a.cast::<u64>().as_ptr().read() }, 1);
+        assert_eq!(unsafe { b.cast::<u64>().as_ptr().read() }, 2);
+
+        // The same with two arrays taking turns to grow.
+        let mut x = Array::new(&vm);
+        let mut y = Array::new(&vm);
+        for i in 0..1000 {
+            x.push(i).unwrap();
+            y.push(-i).unwrap();
+        }
+        assert!(x.iter().copied().eq(0..1000));
+        assert!(y.iter().copied().eq((0..1000).map(|i| -i)));
+    }
+
+    #[test]
+    fn virtual_memory_decommit_to_returns_memory() {
+        let page = internal::page_size();
+        let mut vm =
+            VirtualMemoryAllocator::with_commit_granularity(page * 16, page)
+                .unwrap();
+
+        let a = vm.allocate(layout(page, 1)).unwrap();
+        unsafe { a.cast::<u8>().as_ptr().write(1) };
+        let mark = vm.used();
+        let b = vm.allocate(layout(page * 4, 1)).unwrap();
+        unsafe { b.cast::<u8>().as_ptr().write(1) };
+        assert_eq!(vm.committed(), page * 5);
+
+        vm.decommit_to(mark);
+        assert_eq!(vm.used(), mark);
+        assert_eq!(vm.committed(), page);
+
+        // Decommitted pages come back zeroed once they're committed again.
+        let c = vm.allocate(layout(page * 4, 1)).unwrap();
+        assert_eq!(c.cast::<u8>(), b.cast::<u8>());
+        assert_eq!(unsafe { c.cast::<u8>().as_ptr().read() }, 0);
+    }
+
+    #[test]
+    fn virtual_memory_reset() {
+        let page = internal::page_size();
+        let mut vm =
+            VirtualMemoryAllocator::with_commit_granularity(page * 16, page)
+                .unwrap();
+
+        let a = vm.allocate(layout(page * 3, 1)).unwrap();
+        unsafe { a.cast::<u8>().as_ptr().write(1) };
+        vm.reset();
+        assert_eq!(vm.used(), 0);
+        assert_eq!(vm.committed(), 0);
+
+        let b = vm.allocate(layout(1, 1)).unwrap();
+        assert_eq!(a.cast::<u8>(), b.cast::<u8>());
+        assert_eq!(unsafe { b.cast::<u8>().as_ptr().read() }, 0);
+    }
+
+    #[test]
+    fn virtual_memory_builder_defaults() {
+        let vm = VirtualMemoryAllocator::builder(1 << 20).build().unwrap();
+        assert_eq!(vm.page_size(), internal::page_size());
+        assert_eq!(vm.committed(), 0);
+        let a = vm.allocate(layout(100, 8)).unwrap();
+        unsafe { a.cast::<u8>().as_ptr().write(1) };
+        assert_eq!(vm.committed(), DEFAULT_COMMIT_GRANULARITY);
+    }
+
+    #[test]
+    fn virtual_memory_builder_populate() {
+        let size = 16 * internal::page_size();
+        let vm = VirtualMemoryAllocator::builder(size)
+            .populate()
+            .build()
+            .unwrap();
+        assert_eq!(vm.committed(), size);
+        let a = vm.allocate(layout(size, 1)).unwrap();
+        assert!(unsafe { a.as_ref() }.iter().all(|&x| x == 0));
+    }
+
+    #[test]
+    fn virtual_memory_builder_transparent_huge_pages() {
+        let vm = VirtualMemoryAllocator::builder(8 << 20)
+            .transparent_huge_pages()
+            .populate()
+            .build()
+            .unwrap();
+        assert_eq!(vm.addr.addr() % TRANSPARENT_HUGE_PAGE_SIZE, 0);
+        assert_eq!(vm.committed(), 8 << 20);
+
+        let vm = VirtualMemoryAllocator::builder(8 << 20)
+            .transparent_huge_pages()
+            .build()
+            .unwrap();
+        let a = vm.allocate(layout(1, 1)).unwrap();
+        unsafe { a.cast::<u8>().as_ptr().write(1) };
+        assert_eq!(vm.committed(), TRANSPARENT_HUGE_PAGE_SIZE);
+    }
+
+    #[test]
+    fn virtual_memory_builder_huge_pages_fall_back() {
+        // Whether there are any huge pages depends on the system, but either
+        // way the allocator has to work.
+        for size in [HugePageSize::Size2MiB, HugePageSize::Size1GiB] {
+            let vm = VirtualMemoryAllocator::builder(3 << 20)
+                .huge_pages(size)
+                .build()
+                .unwrap();
+            let page = vm.page_size();
+            assert!(
+                page == internal::page_size()
+                    || page == HugePageSize::Size2MiB.bytes()
+                    || page == HugePageSize::Size1GiB.bytes()
+            );
+            assert_eq!(vm.addr.addr() % page, 0);
+            let a = vm.allocate(layout(3 << 20, 8)).unwrap();
+            unsafe { a.cast::<u8>().write_bytes(1, a.len()) };
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn virtual_memory_builder_numa_node() {
+        assert!(VirtualMemoryAllocator::builder(1 << 20)
+            .numa_node(usize::MAX)
+            .build()
+            .is_err());
+
+        // Only systems with NUMA support have this directory.
+        if std::path::Path::new("/sys/devices/system/node/node0").exists() {
+            let vm = VirtualMemoryAllocator::builder(1 << 20)
+                .numa_node(0)
+                .populate()
+                .build()
+                .unwrap();
+            let a = vm.allocate(layout(1 << 20, 8)).unwrap();
+            unsafe { a.cast::<u8>().write_bytes(1, a.len()) };
+        }
+    }
+
+    #[test]
+    fn atomic_virtual_memory_stress() {
+        const THREADS: usize = 8;
+        let page = internal::page_size();
+        let mut vm = VirtualMemoryAllocator::builder(THREADS * 512 * page)
+            .commit_granularity(page)
+            .build_atomic()
+            .unwrap();
+
+        // Every thread keeps reaching into uncommitted memory, so they race
+        // to commit it too.
+        std::thread::scope(|s| {
+            for t in 0..THREADS {
+                let vm = &vm;
+                s.spawn(move || {
+                    let tag = t as u8 + 1;
+                    let kept = (0..256)
+                        .map(|i| {
+                            let l = layout(page + i % 64, 8);
+                            let a = vm.allocate(l).unwrap();
+                            unsafe {
+                                a.cast::<u8>().write_bytes(tag, l.size())
+                            };
+                            (a, l)
+                        })
+                        .collect::<Vec<_>>();
+                    for (a, l) in kept {
+                        let bytes = unsafe { &a.as_ref()[..l.size()] };
+                        assert!(bytes.iter().all(|&x| x == tag));
+                    }
+                });
+            }
+        });
+        assert!(vm.committed() >= vm.used());
+
+        vm.reset();
+        assert_eq!(vm.used(), 0);
+        assert_eq!(vm.committed(), 0);
+        let a = vm.allocate(layout(8, 8)).unwrap();
+        assert_eq!(unsafe { a.cast::<u8>().as_ptr().read() }, 0);
+    }
+
+    #[test]
+    fn atomic_virtual_memory_grow_moves_earlier_allocations() {
+        let page = internal::page_size();
+        let vm = AtomicVirtualMemoryAllocator::new(page * 16).unwrap();
+        let mut x = Array::new(&vm);
+        let mut y = Array::new(&vm);
+        for i in 0..1000 {
+            x.push(i).unwrap();
+            y.push(-i).unwrap();
+        }
+        assert!(x.iter().copied().eq(0..1000));
+        assert!(y.iter().copied().eq((0..1000).map(|i| -i)));
+    }
+
+    #[test]
+    fn atomic_virtual_memory_owns_memory_below_mark() {
+        let page = internal::page_size();
+        let mut vm = AtomicVirtualMemoryAllocator::new(page * 16).unwrap();
+        let a = vm.allocate(layout(8, 8)).unwrap();
+        let mark = vm.used();
+        let b = vm.allocate(layout(8, 8)).unwrap();
+        assert!(vm.owns(a.cast(), layout(8, 8)));
+        assert!(vm.owns(b.cast(), layout(8, 8)));
+
+        vm.decommit_to(mark);
+        assert!(vm.owns(a.cast(), layout(8, 8)));
+        let x = 0u64;
+        assert!(!vm.owns(NonNull::from(&x).cast(), layout(8, 8)));
+    }
+
+    #[test]
+    fn atomic_virtual_memory_resizes_last_allocation() {
+        let page = internal::page_size();
+        let vm = AtomicVirtualMemoryAllocator::new(page * 16).unwrap();
+        let a = vm.allocate(layout(8, 8)).unwrap();
+        let a =
+            unsafe { vm.grow(a.cast(), layout(8, 8), layout(page * 4, 8)) }
+                .unwrap();
+        unsafe { a.cast::<u8>().as_ptr().add(page * 4 - 1).write(1) };
+        assert_eq!(vm.used(), page * 4);
+        unsafe { vm.deallocate(a.cast(), layout(page * 4, 8)) };
+        assert_eq!(vm.used(), 0);
+    }
+}
@@ -0,0 +1,266 @@
+use core::{
+    cell::{Cell, RefCell},
+    future::Future,
+    marker::PhantomData,
+    pin::pin,
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Context, Poll, Waker},
+};
+use std::{
+    io, process,
+    sync::Arc,
+    task::Wake,
+    thread::{self, Thread},
+};
+
+use super::uring::{Ring, Sqe, IORING_OP_ASYNC_CANCEL};
+
+/// The number of submission queue entries used by [EventLoop::new].
+const DEFAULT_ENTRIES: u32 = 256;
+
+/// The `user_data` of cancellation requests, whose completions are ignored.
+const CANCEL_USER_DATA: u64 = u64::MAX;
+
+enum OpState {
+    Free,
+    /// Submitted but not completed, holding the waker of the last poll.
+    Pending(Option<Waker>),
+    /// Completed with the result given, but not yet seen by its future.
+    Done(i32),
+}
+
+/// A single-threaded event loop which runs IO through `io_uring`.
+///
+/// Operations like [EventLoop::read] return futures which are submitted to
+/// the kernel the first time they're polled, and are driven to completion by
+/// [EventLoop::block_on].
+///
+/// Any buffers given to the event loop come from allocators which live for at
+/// least `'a`. If an operation's future is dropped before it completes, the
+/// operation is cancelled and the drop waits until the kernel is done with
+/// its memory. If it's leaked instead, the event loop waits for it when
+/// dropped, which is why the allocators have to outlive it.
+pub struct EventLoop<'a> {
+    ring: RefCell<Ring>,
+    ops: RefCell<Vec<OpState>>,
+    free: RefCell<Vec<usize>>,
+    /// The number of submitted operations whose completions haven't been
+    /// reaped yet, not counting cancellation requests.
+    in_flight: Cell<usize>,
+    // 'a is invariant, so an EventLoop<'static> can't be used with buffers
+    // from shorter lived allocators.
+    _buffers: PhantomData<Cell<&'a ()>>,
+}
+
+impl<'a> EventLoop<'a> {
+    /// Sets up a new event loop with a reasonable number of submission queue
+    /// entries.
+    #[inline(always)]
+    pub fn new() -> io::Result<EventLoop<'a>> {
+        EventLoop::with_entries(DEFAULT_ENTRIES)
+    }
+
+    /// Sets up a new event loop which can queue at least `entries`
+    /// operations between submissions. Any more than that are still
+    /// accepted, but cause an extra `io_uring_enter` call.
+    pub fn with_entries(entries: u32) -> io::Result<EventLoop<'a>> {
+        Ok(EventLoop {
+            ring: RefCell::new(Ring::new(entries)?),
+            ops: RefCell::new(Vec::new()),
+            free: RefCell::new(Vec::new()),
+            in_flight: Cell::new(0),
+            _buffers: PhantomData,
+        })
+    }
+
+    /// Returns the number of operations which have been submitted, but
+    /// haven't completed yet.
+    #[inline(always)]
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.get()
+    }
+
+    /// Runs `future` to completion, submitting the operations it's waiting on
+    /// and blocking until they complete.
+    ///
+    /// Returns an error if `io_uring_enter` failed.
+    pub fn block_on<F: Future>(&self, future: F) -> io::Result<F::Output> {
+        let mut future = pin!(future);
+        let state = Arc::new(ThreadWaker {
+            thread: thread::current(),
+            woken: AtomicBool::new(true),
+        });
+        let waker = Waker::from(state.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        loop {
+            if state.woken.swap(false, Ordering::Acquire) {
+                if let Poll::Ready(x) = future.as_mut().poll(&mut cx) {
+                    return Ok(x);
+                }
+                continue;
+            }
+
+            // Nothing from the ring can wake the future, so it must be
+            // waiting on something else, like another thread.
+            if self.in_flight.get() == 0 {
+                thread::park();
+                continue;
+            }
+
+            self.ring.borrow_mut().enter(1)?;
+            self.reap();
+        }
+    }
+
+    /// Submits `sqe` and returns the id used to refer to it.
+    ///
+    /// # Safety
+    ///
+    /// This function is safe to use if all memory referenced by `sqe` stays
+    /// valid until the operation completes, or until it's cancelled with
+    /// [EventLoop::cancel].
+    pub(super) unsafe fn submit(&self, mut sqe: Sqe) -> io::Result<usize> {
+        let mut ops = self.ops.borrow_mut();
+        let id = match self.free.borrow_mut().pop() {
+            Some(id) => id,
+            None => {
+                ops.push(OpState::Free);
+                ops.len() - 1
+            }
+        };
+
+        sqe.user_data = id as u64;
+        if let Err(e) = self.ring.borrow_mut().push(sqe) {
+            self.free.borrow_mut().push(id);
+            return Err(e);
+        }
+        ops[id] = OpState::Pending(None);
+        self.in_flight.set(self.in_flight.get() + 1);
+        Ok(id)
+    }
+
+    /// Returns the result of the operation `id` if it's complete, freeing
+    /// its id. Otherwise, `cx` is woken once it completes.
+    pub(super) fn poll_op(&self, id: usize, cx: &Context<'_>) -> Poll<i32> {
+        let mut ops = self.ops.borrow_mut();
+        match &mut ops[id] {
+            OpState::Done(res) => {
+                let res = *res;
+                ops[id] = OpState::Free;
+                self.free.borrow_mut().push(id);
+                Poll::Ready(res)
+            }
+            OpState::Pending(waker) => {
+                match waker {
+                    Some(x) if x.will_wake(cx.waker()) => {}
+                    _ => *waker = Some(cx.waker().clone()),
+                }
+                Poll::Pending
+            }
+            OpState::Free => unreachable!("polled a freed operation"),
+        }
+    }
+
+    /// Cancels the operation `id`, blocking until the kernel is done with
+    /// it, and frees its id.
+    ///
+    /// Aborts the process if waiting fails, since the kernel could otherwise
+    /// still be using memory which is about to be freed.
+    pub(super) fn cancel(&self, id: usize) {
+        if matches!(self.ops.borrow()[id], OpState::Pending(_)) {
+            // SAFETY: cancellation requests don't reference any memory.
+            let pushed =
+                unsafe { self.ring.borrow_mut().push(cancel_sqe(id)) };
+            if pushed.is_err() {
+                process::abort();
+            }
+        }
+
+        while matches!(self.ops.borrow()[id], OpState::Pending(_)) {
+            if self.ring.borrow_mut().enter(1).is_err() {
+                process::abort();
+            }
+            self.reap();
+        }
+
+        self.ops.borrow_mut()[id] = OpState::Free;
+        self.free.borrow_mut().push(id);
+    }
+
+    /// Records the result of every completed operation, waking the futures
+    /// waiting on them.
+    fn reap(&self) {
+        let mut ops = self.ops.borrow_mut();
+        self.ring.borrow_mut().reap(|cqe| {
+            if cqe.user_data == CANCEL_USER_DATA {
+                return;
+            }
+
+            let state = &mut ops[cqe.user_data as usize];
+            if let OpState::Pending(Some(waker)) = state {
+                waker.wake_by_ref();
+            }
+            *state = OpState::Done(cqe.res);
+            self.in_flight.set(self.in_flight.get() - 1);
+        });
+    }
+}
+
+impl Drop for EventLoop<'_> {
+    /// Cancels any operations which are still running because their futures
+    /// were leaked, and waits for them to finish.
+    fn drop(&mut self) {
+        let pending = self
+            .ops
+            .get_mut()
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| matches!(x, OpState::Pending(_)))
+            .map(|(id, _)| cancel_sqe(id));
+        let ring = self.ring.get_mut();
+        for sqe in pending {
+            // SAFETY: cancellation requests don't reference any memory.
+            if unsafe { ring.push(sqe) }.is_err() {
+                process::abort();
+            }
+        }
+
+        while self.in_flight.get() > 0 {
+            if self.ring.get_mut().enter(1).is_err() {
+                process::abort();
+            }
+            self.reap();
+        }
+    }
+}
+
+/// Returns a request to cancel the operation `id`.
+#[inline(always)]
+fn cancel_sqe(id: usize) -> Sqe {
+    Sqe {
+        opcode: IORING_OP_ASYNC_CANCEL,
+        addr: id as u64,
+        user_data: CANCEL_USER_DATA,
+        ..Default::default()
+    }
+}
+
+/// Wakes the thread running [EventLoop::block_on].
+struct ThreadWaker {
+    thread: Thread,
+    woken: AtomicBool,
+}
+
+impl Wake for ThreadWaker {
+    #[inline(always)]
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    #[inline(always)]
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::Release);
+        self.thread.unpark();
+    }
+}
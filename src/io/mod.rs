@@ -0,0 +1,40 @@
+//! Module io contains a single-threaded event loop which does all of its IO
+//! through `io_uring`.
+//!
+//! Every operation is a future which borrows the [EventLoop] it was created
+//! from, and is only submitted to the kernel once it's first polled, so many
+//! operations can be batched into a single `io_uring_enter` call. Run them
+//! with [EventLoop::block_on]:
+//!
+//! ```no_run
+//! # #![feature(allocator_api)]
+//! use stdx::{alloc::Malloc, array::Array, io::EventLoop};
+//!
+//! let event_loop = EventLoop::new().unwrap();
+//! let buf = Array::with_capacity(4096, Malloc).unwrap();
+//! let (result, buf) = event_loop.block_on(event_loop.read(0, buf)).unwrap();
+//! println!("read {} bytes: {:?}", result.unwrap(), &buf[..]);
+//! ```
+//!
+//! ## Buffers
+//!
+//! Reads and writes take ownership of an [Array](crate::array::Array) of
+//! bytes and give it back once they complete, since the kernel may be using
+//! the buffer's memory until then. The arrays can come from any allocator
+//! which outlives the event loop, so using a
+//! [FixedBufferAllocator](crate::alloc::FixedBufferAllocator) or an
+//! [Arena](crate::alloc::Arena) for per-connection buffers works as you'd
+//! expect.
+//!
+//! ## Cancellation
+//!
+//! Dropping an operation's future before it completes cancels it, blocking
+//! until the kernel has stopped using its memory. Leaking one is also safe,
+//! since the event loop waits for every outstanding operation when dropped.
+
+mod event_loop;
+mod op;
+mod uring;
+
+pub use event_loop::*;
+pub use op::*;
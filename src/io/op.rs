@@ -0,0 +1,654 @@
+use core::{
+    ffi::c_int,
+    future::Future,
+    marker::PhantomPinned,
+    mem,
+    pin::Pin,
+    ptr,
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+use std::{
+    io,
+    net::SocketAddr,
+    os::fd::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd, RawFd},
+};
+
+use stdx_core::array::Array;
+
+use super::{
+    uring::{
+        Sqe, Timespec, IORING_OP_ACCEPT, IORING_OP_CLOSE, IORING_OP_CONNECT,
+        IORING_OP_READ, IORING_OP_TIMEOUT, IORING_OP_WRITE,
+    },
+    EventLoop,
+};
+
+const AF_INET: u16 = 2;
+const AF_INET6: u16 = 10;
+const SOCK_STREAM: c_int = 1;
+const SOCK_CLOEXEC: c_int = 0o2000000;
+
+const ETIME: i32 = 62;
+
+/// The offset used to read or write at the file's current position.
+const CURRENT_POSITION: u64 = u64::MAX;
+
+#[link(name = "c")]
+extern "C" {
+    fn socket(domain: c_int, ty: c_int, protocol: c_int) -> c_int;
+}
+
+impl<'a> EventLoop<'a> {
+    /// Reads from `fd` into the spare capacity of `buf`, at the file's
+    /// current position if it has one.
+    ///
+    /// The future returns the number of bytes read, along with `buf`, whose
+    /// length has grown by that much. Reserve capacity in `buf` beforehand to
+    /// choose how much is read.
+    #[inline(always)]
+    pub fn read(&self, fd: RawFd, buf: Array<'a, u8>) -> Read<'_, 'a> {
+        self.read_at(fd, buf, CURRENT_POSITION)
+    }
+
+    /// Like [EventLoop::read], but reads from `offset` bytes into the file.
+    #[inline(always)]
+    pub fn read_at(
+        &self,
+        fd: RawFd,
+        buf: Array<'a, u8>,
+        offset: u64,
+    ) -> Read<'_, 'a> {
+        Read {
+            submission: Submission::new(self),
+            fd,
+            offset,
+            buf: Some(buf),
+        }
+    }
+
+    /// Writes the contents of `buf` to `fd`, at the file's current position if
+    /// it has one.
+    ///
+    /// The future returns the number of bytes written, which may be less than
+    /// `buf.len()`, along with `buf`.
+    #[inline(always)]
+    pub fn write(&self, fd: RawFd, buf: Array<'a, u8>) -> Write<'_, 'a> {
+        self.write_at(fd, buf, CURRENT_POSITION)
+    }
+
+    /// Like [EventLoop::write], but writes at `offset` bytes into the file.
+    #[inline(always)]
+    pub fn write_at(
+        &self,
+        fd: RawFd,
+        buf: Array<'a, u8>,
+        offset: u64,
+    ) -> Write<'_, 'a> {
+        Write {
+            submission: Submission::new(self),
+            fd,
+            offset,
+            buf: Some(buf),
+        }
+    }
+
+    /// Accepts a connection on the listening socket `fd`.
+    #[inline(always)]
+    pub fn accept(&self, fd: RawFd) -> Accept<'_, 'a> {
+        Accept {
+            submission: Submission::new(self),
+            fd,
+        }
+    }
+
+    /// Opens a TCP connection to `addr`.
+    #[inline(always)]
+    pub fn connect(&self, addr: SocketAddr) -> Connect<'_, 'a> {
+        Connect {
+            submission: Submission::new(self),
+            addr,
+            raw_addr: RawSocketAddr::from(addr),
+            socket: None,
+            _pinned: PhantomPinned,
+        }
+    }
+
+    /// Waits until `duration` has passed.
+    #[inline(always)]
+    pub fn timeout(&self, duration: Duration) -> Timeout<'_, 'a> {
+        Timeout {
+            submission: Submission::new(self),
+            timespec: Timespec {
+                tv_sec: duration.as_secs().min(i64::MAX as u64) as i64,
+                tv_nsec: duration.subsec_nanos() as i64,
+            },
+            _pinned: PhantomPinned,
+        }
+    }
+
+    /// Closes `fd`.
+    ///
+    /// If the future is dropped before it's polled, `fd` is closed
+    /// immediately instead.
+    #[inline(always)]
+    pub fn close(&self, fd: OwnedFd) -> Close<'_, 'a> {
+        Close {
+            submission: Submission::new(self),
+            fd: Some(fd),
+        }
+    }
+}
+
+/// The part of every operation's future which submits it and cancels it if
+/// it's dropped before completing.
+///
+/// This has to be the first field of a future, so that it's dropped before
+/// any memory the operation uses.
+struct Submission<'l, 'a> {
+    event_loop: &'l EventLoop<'a>,
+    state: SubmissionState,
+}
+
+enum SubmissionState {
+    Unsubmitted,
+    Submitted(usize),
+    Complete,
+}
+
+impl<'l, 'a> Submission<'l, 'a> {
+    #[inline(always)]
+    fn new(event_loop: &'l EventLoop<'a>) -> Submission<'l, 'a> {
+        Submission {
+            event_loop,
+            state: SubmissionState::Unsubmitted,
+        }
+    }
+
+    /// Submits the operation returned by `prepare` the first time it's
+    /// called, then returns the operation's result once it's complete.
+    ///
+    /// # Safety
+    ///
+    /// This function is safe to use if all memory referenced by the
+    /// operation stays valid until this [Submission] is dropped.
+    unsafe fn poll(
+        &mut self,
+        cx: &Context<'_>,
+        prepare: impl FnOnce() -> Sqe,
+    ) -> Poll<io::Result<u32>> {
+        let id = match self.state {
+            SubmissionState::Unsubmitted => {
+                let id = self.event_loop.submit(prepare())?;
+                self.state = SubmissionState::Submitted(id);
+                id
+            }
+            SubmissionState::Submitted(id) => id,
+            SubmissionState::Complete => {
+                panic!("an io operation was polled after completing")
+            }
+        };
+
+        let res = ready!(self.event_loop.poll_op(id, cx));
+        self.state = SubmissionState::Complete;
+        if res < 0 {
+            return Poll::Ready(Err(io::Error::from_raw_os_error(-res)));
+        }
+        Poll::Ready(Ok(res as u32))
+    }
+}
+
+impl Drop for Submission<'_, '_> {
+    #[inline(always)]
+    fn drop(&mut self) {
+        if let SubmissionState::Submitted(id) = self.state {
+            self.event_loop.cancel(id);
+        }
+    }
+}
+
+/// The future returned by [EventLoop::read] and [EventLoop::read_at].
+pub struct Read<'l, 'a> {
+    submission: Submission<'l, 'a>,
+    fd: RawFd,
+    offset: u64,
+    buf: Option<Array<'a, u8>>,
+}
+
+impl<'a> Future for Read<'_, 'a> {
+    type Output = (io::Result<usize>, Array<'a, u8>);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let Some(buf) = &mut this.buf else {
+            panic!("an io operation was polled after completing");
+        };
+
+        let len = buf.len();
+        let spare = (buf.capacity() - len).min(u32::MAX as usize);
+        // SAFETY: len <= capacity.
+        let addr = unsafe { buf.as_mut_ptr().add(len) };
+        // SAFETY: the buffer's memory is owned by this future, and isn't
+        //         freed until it's returned after the read completes.
+        let result = ready!(unsafe {
+            this.submission.poll(cx, || Sqe {
+                opcode: IORING_OP_READ,
+                fd: this.fd,
+                off: this.offset,
+                addr: addr as u64,
+                len: spare as u32,
+                ..Default::default()
+            })
+        });
+
+        let mut buf = this.buf.take().unwrap();
+        let result = result.map(|n| {
+            // SAFETY: the kernel initialized the n bytes after len.
+            unsafe { buf.set_len(len + n as usize) };
+            n as usize
+        });
+        Poll::Ready((result, buf))
+    }
+}
+
+/// The future returned by [EventLoop::write] and [EventLoop::write_at].
+pub struct Write<'l, 'a> {
+    submission: Submission<'l, 'a>,
+    fd: RawFd,
+    offset: u64,
+    buf: Option<Array<'a, u8>>,
+}
+
+impl<'a> Future for Write<'_, 'a> {
+    type Output = (io::Result<usize>, Array<'a, u8>);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let Some(buf) = &this.buf else {
+            panic!("an io operation was polled after completing");
+        };
+
+        let len = buf.len().min(u32::MAX as usize);
+        let addr = buf.as_ptr();
+        // SAFETY: the buffer's memory is owned by this future, and isn't
+        //         freed until it's returned after the write completes.
+        let result = ready!(unsafe {
+            this.submission.poll(cx, || Sqe {
+                opcode: IORING_OP_WRITE,
+                fd: this.fd,
+                off: this.offset,
+                addr: addr as u64,
+                len: len as u32,
+                ..Default::default()
+            })
+        });
+
+        let buf = this.buf.take().unwrap();
+        Poll::Ready((result.map(|n| n as usize), buf))
+    }
+}
+
+/// The future returned by [EventLoop::accept].
+pub struct Accept<'l, 'a> {
+    submission: Submission<'l, 'a>,
+    fd: RawFd,
+}
+
+impl Future for Accept<'_, '_> {
+    type Output = io::Result<OwnedFd>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        // SAFETY: accept is given no memory to write the peer's address to.
+        let fd = ready!(unsafe {
+            this.submission.poll(cx, || Sqe {
+                opcode: IORING_OP_ACCEPT,
+                fd: this.fd,
+                op_flags: SOCK_CLOEXEC as u32,
+                ..Default::default()
+            })
+        })?;
+        // SAFETY: the kernel gave us a new file descriptor.
+        Poll::Ready(Ok(unsafe { OwnedFd::from_raw_fd(fd as RawFd) }))
+    }
+}
+
+/// The future returned by [EventLoop::connect].
+pub struct Connect<'l, 'a> {
+    submission: Submission<'l, 'a>,
+    addr: SocketAddr,
+    raw_addr: RawSocketAddr,
+    socket: Option<OwnedFd>,
+    _pinned: PhantomPinned,
+}
+
+impl Future for Connect<'_, '_> {
+    type Output = io::Result<OwnedFd>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: nothing is moved out of this.
+        let this = unsafe { self.get_unchecked_mut() };
+        if this.socket.is_none() {
+            let domain = match this.addr {
+                SocketAddr::V4(_) => AF_INET,
+                SocketAddr::V6(_) => AF_INET6,
+            };
+            let fd = unsafe {
+                socket(domain as c_int, SOCK_STREAM | SOCK_CLOEXEC, 0)
+            };
+            if fd < 0 {
+                return Poll::Ready(Err(io::Error::last_os_error()));
+            }
+            // SAFETY: socket gave us a new file descriptor.
+            this.socket = Some(unsafe { OwnedFd::from_raw_fd(fd) });
+        }
+
+        let fd = this.socket.as_ref().map_or(-1, AsRawFd::as_raw_fd);
+        let (addr, len) = this.raw_addr.as_ptr();
+        // SAFETY: this future is pinned, so the address stays where it is
+        //         until the future is dropped.
+        ready!(unsafe {
+            this.submission.poll(cx, || Sqe {
+                opcode: IORING_OP_CONNECT,
+                fd,
+                addr: addr as u64,
+                off: len as u64,
+                ..Default::default()
+            })
+        })?;
+        Poll::Ready(Ok(this.socket.take().unwrap()))
+    }
+}
+
+/// The future returned by [EventLoop::timeout].
+pub struct Timeout<'l, 'a> {
+    submission: Submission<'l, 'a>,
+    timespec: Timespec,
+    _pinned: PhantomPinned,
+}
+
+impl Future for Timeout<'_, '_> {
+    type Output = io::Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: nothing is moved out of this.
+        let this = unsafe { self.get_unchecked_mut() };
+        let timespec = ptr::addr_of!(this.timespec);
+        // SAFETY: this future is pinned, so the timespec stays where it is
+        //         until the future is dropped.
+        let result = ready!(unsafe {
+            this.submission.poll(cx, || Sqe {
+                opcode: IORING_OP_TIMEOUT,
+                addr: timespec as u64,
+                len: 1,
+                ..Default::default()
+            })
+        });
+        match result {
+            Err(e) if e.raw_os_error() == Some(ETIME) => Poll::Ready(Ok(())),
+            x => Poll::Ready(x.map(|_| ())),
+        }
+    }
+}
+
+/// The future returned by [EventLoop::close].
+pub struct Close<'l, 'a> {
+    submission: Submission<'l, 'a>,
+    fd: Option<OwnedFd>,
+}
+
+impl Future for Close<'_, '_> {
+    type Output = io::Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        // The fd is only given up once it's submitted, since after that the
+        // kernel closes it even if the future is dropped.
+        let fd = &mut this.fd;
+        // SAFETY: close doesn't reference any memory.
+        let result = ready!(unsafe {
+            this.submission.poll(cx, || Sqe {
+                opcode: IORING_OP_CLOSE,
+                fd: fd.take().map_or(-1, IntoRawFd::into_raw_fd),
+                ..Default::default()
+            })
+        });
+        Poll::Ready(result.map(|_| ()))
+    }
+}
+
+/// `struct sockaddr_in`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SockaddrIn {
+    family: u16,
+    port: [u8; 2],
+    addr: [u8; 4],
+    zero: [u8; 8],
+}
+
+/// `struct sockaddr_in6`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SockaddrIn6 {
+    family: u16,
+    port: [u8; 2],
+    flowinfo: u32,
+    addr: [u8; 16],
+    scope_id: u32,
+}
+
+/// A [SocketAddr] in the format the kernel expects.
+enum RawSocketAddr {
+    V4(SockaddrIn),
+    V6(SockaddrIn6),
+}
+
+impl RawSocketAddr {
+    /// Returns a pointer to the address and its length.
+    #[inline(always)]
+    fn as_ptr(&self) -> (*const u8, usize) {
+        match self {
+            RawSocketAddr::V4(x) => {
+                (ptr::from_ref(x).cast(), mem::size_of::<SockaddrIn>())
+            }
+            RawSocketAddr::V6(x) => {
+                (ptr::from_ref(x).cast(), mem::size_of::<SockaddrIn6>())
+            }
+        }
+    }
+}
+
+impl From<SocketAddr> for RawSocketAddr {
+    fn from(value: SocketAddr) -> Self {
+        match value {
+            SocketAddr::V4(x) => RawSocketAddr::V4(SockaddrIn {
+                family: AF_INET,
+                port: x.port().to_be_bytes(),
+                addr: x.ip().octets(),
+                zero: [0; 8],
+            }),
+            SocketAddr::V6(x) => RawSocketAddr::V6(SockaddrIn6 {
+                family: AF_INET6,
+                port: x.port().to_be_bytes(),
+                flowinfo: x.flowinfo().to_be(),
+                addr: x.ip().octets(),
+                scope_id: x.scope_id(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc::{FixedBufferAllocator, Malloc};
+    use std::{
+        future::poll_fn,
+        io::{pipe, Read as _, Write as _},
+        net::TcpListener,
+        time::Instant,
+    };
+
+    fn array<'a>(contents: &[u8], capacity: usize) -> Array<'a, u8> {
+        let mut result = Array::with_capacity(capacity, Malloc).unwrap();
+        result.try_extend(contents.iter().copied()).unwrap();
+        result
+    }
+
+    #[test]
+    fn event_loop_read_and_write() {
+        let event_loop = EventLoop::new().unwrap();
+        let (mut reader, mut writer) = pipe().unwrap();
+
+        let write = event_loop.write(writer.as_raw_fd(), array(b"hello", 5));
+        let (result, buf) = event_loop.block_on(write).unwrap();
+        assert_eq!(result.unwrap(), 5);
+        assert_eq!(&buf[..], b"hello");
+        let mut contents = [0u8; 5];
+        reader.read_exact(&mut contents).unwrap();
+        assert_eq!(&contents, b"hello");
+
+        // Reads append to what's already in the buffer.
+        writer.write_all(b" world").unwrap();
+        let read = event_loop.read(reader.as_raw_fd(), array(b"hello", 64));
+        let (result, buf) = event_loop.block_on(read).unwrap();
+        assert_eq!(result.unwrap(), 6);
+        assert_eq!(&buf[..], b"hello world");
+    }
+
+    #[test]
+    fn event_loop_fixed_buffer_allocator() {
+        let mut memory = [0u8; 64];
+        let fba = FixedBufferAllocator::from_slice(&mut memory);
+        let event_loop = EventLoop::new().unwrap();
+        let (reader, mut writer) = pipe().unwrap();
+
+        writer.write_all(b"abc").unwrap();
+        let buf = Array::with_capacity(16, &fba).unwrap();
+        let read = event_loop.read(reader.as_raw_fd(), buf);
+        let (result, buf) = event_loop.block_on(read).unwrap();
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(&buf[..], b"abc");
+    }
+
+    #[test]
+    fn event_loop_read_error() {
+        let event_loop = EventLoop::new().unwrap();
+        let read = event_loop.read(-1, array(b"", 8));
+        let (result, buf) = event_loop.block_on(read).unwrap();
+        assert_eq!(result.unwrap_err().raw_os_error(), Some(9));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn event_loop_many_operations() {
+        let event_loop = EventLoop::with_entries(2).unwrap();
+        let (mut reader, writer) = pipe().unwrap();
+        let fd = writer.as_raw_fd();
+
+        let result = event_loop.block_on(async {
+            let mut total = 0;
+            for i in 0..10u8 {
+                let (result, _) = event_loop.write(fd, array(&[i], 1)).await;
+                total += result.unwrap();
+            }
+            total
+        });
+        assert_eq!(result.unwrap(), 10);
+        let mut contents = [0u8; 10];
+        reader.read_exact(&mut contents).unwrap();
+        assert_eq!(contents, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn event_loop_timeout() {
+        let event_loop = EventLoop::new().unwrap();
+        let start = Instant::now();
+        let timeout = event_loop.timeout(Duration::from_millis(20));
+        event_loop.block_on(timeout).unwrap().unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn event_loop_accept_and_connect() {
+        let event_loop = EventLoop::new().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let connect = event_loop.connect(addr);
+        let client = event_loop.block_on(connect).unwrap().unwrap();
+        let accept = event_loop.accept(listener.as_raw_fd());
+        let server = event_loop.block_on(accept).unwrap().unwrap();
+
+        let write = event_loop.write(client.as_raw_fd(), array(b"ping", 4));
+        assert_eq!(event_loop.block_on(write).unwrap().0.unwrap(), 4);
+        let read = event_loop.read(server.as_raw_fd(), array(b"", 16));
+        let (result, buf) = event_loop.block_on(read).unwrap();
+        assert_eq!(result.unwrap(), 4);
+        assert_eq!(&buf[..], b"ping");
+
+        event_loop
+            .block_on(event_loop.close(client))
+            .unwrap()
+            .unwrap();
+        let read = event_loop.read(server.as_raw_fd(), array(b"", 16));
+        assert_eq!(event_loop.block_on(read).unwrap().0.unwrap(), 0);
+    }
+
+    #[test]
+    fn event_loop_connect_refused() {
+        let event_loop = EventLoop::new().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let result = event_loop.block_on(event_loop.connect(addr)).unwrap();
+        assert_eq!(
+            result.unwrap_err().kind(),
+            io::ErrorKind::ConnectionRefused
+        );
+    }
+
+    #[test]
+    fn event_loop_drop_cancels() {
+        let event_loop = EventLoop::new().unwrap();
+        let (reader, _writer) = pipe().unwrap();
+
+        // Nothing is ever written, so the read is only submitted by polling
+        // it once, then cancelled when it's dropped.
+        let mut read =
+            Box::pin(event_loop.read(reader.as_raw_fd(), array(b"", 8)));
+        let polled = event_loop.block_on(poll_fn(|cx| {
+            Poll::Ready(read.as_mut().poll(cx).is_pending())
+        }));
+        assert!(polled.unwrap());
+        drop(read);
+        assert_eq!(event_loop.in_flight(), 0);
+
+        let mut timeout =
+            Box::pin(event_loop.timeout(Duration::from_secs(60)));
+        let start = Instant::now();
+        let polled = event_loop.block_on(poll_fn(|cx| {
+            Poll::Ready(timeout.as_mut().poll(cx).is_pending())
+        }));
+        assert!(polled.unwrap());
+        drop(timeout);
+        assert!(start.elapsed() < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn event_loop_drop_waits_for_leaked_operations() {
+        let event_loop = EventLoop::new().unwrap();
+        let (reader, _writer) = pipe().unwrap();
+        let mut read =
+            Box::pin(event_loop.read(reader.as_raw_fd(), array(b"", 8)));
+        let polled = event_loop.block_on(poll_fn(|cx| {
+            Poll::Ready(read.as_mut().poll(cx).is_pending())
+        }));
+        assert!(polled.unwrap());
+        mem::forget(read);
+        assert_eq!(event_loop.in_flight(), 1);
+        drop(event_loop);
+    }
+}
@@ -0,0 +1,333 @@
+//! Raw bindings to the `io_uring` interface, made with the syscalls directly
+//! rather than through `liburing`.
+
+use core::{
+    ffi::{c_int, c_long, c_uint, c_void},
+    mem, ptr,
+    sync::atomic::{AtomicU32, Ordering},
+};
+use std::io;
+
+const SYS_IO_URING_SETUP: c_long = 425;
+const SYS_IO_URING_ENTER: c_long = 426;
+
+const IORING_OFF_SQ_RING: isize = 0;
+const IORING_OFF_CQ_RING: isize = 0x8000000;
+const IORING_OFF_SQES: isize = 0x10000000;
+
+const IORING_ENTER_GETEVENTS: c_uint = 1 << 0;
+
+pub const IORING_OP_TIMEOUT: u8 = 11;
+pub const IORING_OP_ACCEPT: u8 = 13;
+pub const IORING_OP_ASYNC_CANCEL: u8 = 14;
+pub const IORING_OP_CONNECT: u8 = 16;
+pub const IORING_OP_CLOSE: u8 = 19;
+pub const IORING_OP_READ: u8 = 22;
+pub const IORING_OP_WRITE: u8 = 23;
+
+const PROT_READ: c_int = 1 << 0;
+const PROT_WRITE: c_int = 1 << 1;
+const MAP_SHARED: c_int = 1 << 0;
+const MAP_POPULATE: c_int = 0x8000;
+const MAP_FAILED: usize = usize::MAX;
+
+const EINTR: i32 = 4;
+
+#[link(name = "c")]
+extern "C" {
+    fn syscall(number: c_long, ...) -> c_long;
+    fn mmap(
+        addr: *mut u8,
+        size: usize,
+        prot: c_int,
+        flags: c_int,
+        fildes: c_int,
+        offset: isize,
+    ) -> *mut u8;
+    fn munmap(addr: *const u8, len: usize) -> c_int;
+    fn close(fd: c_int) -> c_int;
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct SqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct CqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: SqringOffsets,
+    cq_off: CqringOffsets,
+}
+
+/// A submission queue entry, `struct io_uring_sqe`.
+#[repr(C)]
+#[derive(Default)]
+pub struct Sqe {
+    pub opcode: u8,
+    pub flags: u8,
+    pub ioprio: u16,
+    pub fd: i32,
+    pub off: u64,
+    pub addr: u64,
+    pub len: u32,
+    pub op_flags: u32,
+    pub user_data: u64,
+    pub buf_index: u16,
+    pub personality: u16,
+    pub splice_fd_in: i32,
+    pub addr3: u64,
+    pub pad: u64,
+}
+
+/// A completion queue entry, `struct io_uring_cqe`.
+#[repr(C)]
+pub struct Cqe {
+    pub user_data: u64,
+    pub res: i32,
+    pub flags: u32,
+}
+
+/// `struct __kernel_timespec`, used by [IORING_OP_TIMEOUT].
+#[repr(C)]
+#[derive(Default)]
+pub struct Timespec {
+    pub tv_sec: i64,
+    pub tv_nsec: i64,
+}
+
+/// A memory mapping shared with the kernel.
+struct Mmap {
+    addr: *mut u8,
+    len: usize,
+}
+
+impl Mmap {
+    fn new(fd: c_int, len: usize, offset: isize) -> io::Result<Mmap> {
+        let addr = unsafe {
+            mmap(
+                ptr::null_mut(),
+                len,
+                PROT_READ | PROT_WRITE,
+                MAP_SHARED | MAP_POPULATE,
+                fd,
+                offset,
+            )
+        };
+        if addr.addr() == MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mmap { addr, len })
+    }
+
+    /// # Safety
+    ///
+    /// This function is safe to use if `offset` is the offset of a `T` given
+    /// by the kernel.
+    #[inline(always)]
+    unsafe fn at<T>(&self, offset: u32) -> *mut T {
+        self.addr.add(offset as usize).cast()
+    }
+}
+
+impl Drop for Mmap {
+    #[inline(always)]
+    fn drop(&mut self) {
+        unsafe { munmap(self.addr, self.len) };
+    }
+}
+
+/// An `io_uring` instance, made up of a submission queue and a completion
+/// queue shared with the kernel.
+///
+/// Nothing here keeps track of the memory used by submitted operations, which
+/// is up to the caller.
+pub struct Ring {
+    fd: c_int,
+    // These are only kept around to be unmapped on drop.
+    _sq_ring: Mmap,
+    _cq_ring: Mmap,
+    _sqes: Mmap,
+
+    sq_head: *const AtomicU32,
+    sq_tail: *const AtomicU32,
+    sq_mask: u32,
+    sq_entries: u32,
+    sq_array: *mut u32,
+    sqes: *mut Sqe,
+    /// The number of entries written to the submission queue which haven't
+    /// been passed to `io_uring_enter` yet.
+    unsubmitted: u32,
+
+    cq_head: *const AtomicU32,
+    cq_tail: *const AtomicU32,
+    cq_mask: u32,
+    cqes: *const Cqe,
+}
+
+impl Ring {
+    /// Sets up a ring with room for at least `entries` submissions.
+    pub fn new(entries: u32) -> io::Result<Ring> {
+        let mut params = Params::default();
+        let fd = unsafe {
+            syscall(
+                SYS_IO_URING_SETUP,
+                entries as c_uint,
+                ptr::addr_of_mut!(params),
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = fd as c_int;
+
+        let sq_len = params.sq_off.array as usize
+            + params.sq_entries as usize * mem::size_of::<u32>();
+        let cq_len = params.cq_off.cqes as usize
+            + params.cq_entries as usize * mem::size_of::<Cqe>();
+        let sqes_len = params.sq_entries as usize * mem::size_of::<Sqe>();
+        let maps = Mmap::new(fd, sq_len, IORING_OFF_SQ_RING).and_then(|sq| {
+            let cq = Mmap::new(fd, cq_len, IORING_OFF_CQ_RING)?;
+            let sqes = Mmap::new(fd, sqes_len, IORING_OFF_SQES)?;
+            Ok((sq, cq, sqes))
+        });
+        let (sq, cq, sqes) = match maps {
+            Ok(x) => x,
+            Err(e) => {
+                unsafe { close(fd) };
+                return Err(e);
+            }
+        };
+
+        // SAFETY: the offsets all come from the kernel.
+        unsafe {
+            let sq_off = &params.sq_off;
+            let cq_off = &params.cq_off;
+            Ok(Ring {
+                fd,
+                sq_head: sq.at(sq_off.head),
+                sq_tail: sq.at(sq_off.tail),
+                sq_mask: *sq.at::<u32>(sq_off.ring_mask),
+                sq_entries: *sq.at::<u32>(sq_off.ring_entries),
+                sq_array: sq.at(sq_off.array),
+                sqes: sqes.addr.cast(),
+                unsubmitted: 0,
+                cq_head: cq.at(cq_off.head),
+                cq_tail: cq.at(cq_off.tail),
+                cq_mask: *cq.at::<u32>(cq_off.ring_mask),
+                cqes: cq.at(cq_off.cqes),
+                _sq_ring: sq,
+                _cq_ring: cq,
+                _sqes: sqes,
+            })
+        }
+    }
+
+    /// Adds `sqe` to the submission queue, submitting everything queued so
+    /// far first if it's full.
+    ///
+    /// # Safety
+    ///
+    /// This function is safe to use if all memory referenced by `sqe` stays
+    /// valid until its completion is reaped.
+    pub unsafe fn push(&mut self, sqe: Sqe) -> io::Result<()> {
+        let head = (*self.sq_head).load(Ordering::Acquire);
+        let tail = (*self.sq_tail).load(Ordering::Relaxed);
+        if tail.wrapping_sub(head) == self.sq_entries {
+            self.enter(0)?;
+        }
+
+        let index = tail & self.sq_mask;
+        ptr::write(self.sqes.add(index as usize), sqe);
+        ptr::write(self.sq_array.add(index as usize), index);
+        (*self.sq_tail).store(tail.wrapping_add(1), Ordering::Release);
+        self.unsubmitted += 1;
+        Ok(())
+    }
+
+    /// Submits everything in the submission queue, then waits until there
+    /// are at least `min_complete` completions.
+    pub fn enter(&mut self, min_complete: u32) -> io::Result<()> {
+        let flags = if min_complete > 0 {
+            IORING_ENTER_GETEVENTS
+        } else {
+            0
+        };
+
+        loop {
+            let ret = unsafe {
+                syscall(
+                    SYS_IO_URING_ENTER,
+                    self.fd,
+                    self.unsubmitted as c_uint,
+                    min_complete as c_uint,
+                    flags,
+                    ptr::null::<c_void>(),
+                    0usize,
+                )
+            };
+            if ret >= 0 {
+                self.unsubmitted -= ret as u32;
+                return Ok(());
+            }
+
+            let e = io::Error::last_os_error();
+            if e.raw_os_error() != Some(EINTR) {
+                return Err(e);
+            }
+        }
+    }
+
+    /// Calls `f` with each completion in the completion queue, removing them.
+    pub fn reap(&mut self, mut f: impl FnMut(&Cqe)) {
+        // SAFETY: the head and tail come from the kernel, and everything
+        //         between them is an initialized completion.
+        unsafe {
+            let mut head = (*self.cq_head).load(Ordering::Relaxed);
+            let tail = (*self.cq_tail).load(Ordering::Acquire);
+            while head != tail {
+                f(&*self.cqes.add((head & self.cq_mask) as usize));
+                head = head.wrapping_add(1);
+            }
+            (*self.cq_head).store(head, Ordering::Release);
+        }
+    }
+}
+
+impl Drop for Ring {
+    #[inline(always)]
+    fn drop(&mut self) {
+        unsafe { close(self.fd) };
+    }
+}
@@ -4,20 +4,22 @@
 #![feature(allocator_api, cfg_version, alloc_layout_extra, try_with_capacity)]
 
 pub use stdx_core::*;
+pub use stdx_nopanic::nopanic;
 
 /// A url parser and utilities.
 pub mod url;
 
+/// A single-threaded event loop using `io_uring`.
+#[cfg(target_os = "linux")]
+pub mod io;
+
 /// A useful set of allocators which can provide better performance than
 /// general purpose allocators depending on your usage pattern.
 pub mod alloc;
 
+/// A dynamic array as well as building blocks for creating data structures
+/// containing them.
 pub mod array {
+    pub use stdx_core::array::*;
     pub use stdx_soa::Soa;
 }
-
-#[derive(array::Soa)]
-struct Foo {
-    bar: i32,
-    baz: String,
-}
@@ -0,0 +1,11 @@
+use std::hint::black_box;
+use stdx_nopanic::nopanic;
+
+#[nopanic]
+fn get(x: &[u8], i: usize) -> Option<u8> {
+    x.get(i).copied()
+}
+
+fn main() {
+    println!("{:?}", get(black_box(b"abc"), black_box(1)));
+}
@@ -0,0 +1,11 @@
+use std::hint::black_box;
+use stdx_nopanic::nopanic;
+
+#[nopanic(always)]
+fn index(x: &[u8], i: usize) -> u8 {
+    x[i]
+}
+
+fn main() {
+    println!("{}", index(black_box(b"abc"), black_box(1)));
+}
@@ -0,0 +1,22 @@
+use std::{collections::HashMap, thread};
+
+use stdx::alloc::{GlobalAdapter, VirtualMemoryAllocator};
+
+#[global_allocator]
+static GLOBAL: GlobalAdapter<VirtualMemoryAllocator> =
+    GlobalAdapter::virtual_memory();
+
+#[test]
+fn global_alloc_std_collections() {
+    let mut map = HashMap::new();
+    for i in 0..10000 {
+        map.insert(i.to_string(), vec![i; i % 16]);
+    }
+    assert_eq!(map["9999"].len(), 9999 % 16);
+
+    let threads = (0..4)
+        .map(|i| thread::spawn(move || (0..1000).map(|j| i * j).sum::<u64>()))
+        .collect::<Vec<_>>();
+    let sums = threads.into_iter().map(|t| t.join().unwrap());
+    assert_eq!(sums.sum::<u64>(), 6 * 499500);
+}
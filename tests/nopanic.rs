@@ -0,0 +1,146 @@
+use std::process::{Command, Output};
+
+use stdx::nopanic;
+
+#[nopanic]
+fn get(x: &[u8], i: usize) -> Option<u8> {
+    x.get(i).copied()
+}
+
+#[nopanic]
+fn first_or(x: &[u8], default: u8) -> u8 {
+    if x.is_empty() {
+        return default;
+    }
+    x.first().copied().unwrap_or(default)
+}
+
+#[nopanic]
+fn parse_sum(a: &str, b: &str) -> Result<u32, std::num::ParseIntError> {
+    let a: u16 = a.parse()?;
+    let b: u16 = b.parse()?;
+    Ok(a as u32 + b as u32)
+}
+
+#[nopanic]
+fn evens(x: &[u8]) -> impl Iterator<Item = &u8> + '_ {
+    x.iter().filter(|x| *x % 2 == 0)
+}
+
+#[nopanic]
+fn apply<F: Fn(u8) -> u8 + Copy>(f: F, mut x: u8) -> u8 {
+    x = f(x);
+    f(x)
+}
+
+#[nopanic]
+fn pick<T>(a: T, b: T, first: bool) -> T
+where
+    T: Copy,
+{
+    if first {
+        a
+    } else {
+        b
+    }
+}
+
+#[nopanic]
+fn increment(x: &mut u64) {
+    *x = x.wrapping_add(1);
+}
+
+struct Counter {
+    name: String,
+    count: usize,
+}
+
+impl Counter {
+    #[nopanic]
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[nopanic]
+    fn bump(&mut self) -> usize {
+        self.count = self.count.saturating_add(1);
+        self.count
+    }
+
+    #[nopanic]
+    fn into_parts(self) -> (String, usize) {
+        (self.name, self.count)
+    }
+}
+
+#[nopanic]
+#[allow(clippy::unused_unit)]
+fn nothing() -> () {}
+
+#[test]
+fn nopanic_preserves_behaviour() {
+    assert_eq!(get(b"abc", 1), Some(b'b'));
+    assert_eq!(get(b"abc", 3), None);
+    assert_eq!(first_or(b"", 7), 7);
+    assert_eq!(first_or(b"xy", 7), b'x');
+    assert_eq!(parse_sum("1", "2"), Ok(3));
+    assert!(parse_sum("1", "x").is_err());
+    assert_eq!(evens(&[1, 2, 3, 4]).copied().collect::<Vec<_>>(), [2, 4]);
+    assert_eq!(apply(|x| x.wrapping_mul(2), 3), 12);
+    assert_eq!(pick('a', 'b', false), 'b');
+
+    let mut x = u64::MAX;
+    increment(&mut x);
+    assert_eq!(x, 0);
+    nothing();
+}
+
+#[test]
+fn nopanic_methods() {
+    let mut counter = Counter {
+        name: "hits".to_string(),
+        count: 0,
+    };
+    assert_eq!(counter.name(), "hits");
+    assert_eq!(counter.bump(), 1);
+    assert_eq!(counter.bump(), 2);
+    assert_eq!(counter.into_parts(), ("hits".to_string(), 2));
+}
+
+#[test]
+fn nopanic_ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/nopanic_*.rs");
+}
+
+/// Builds a binary from tests/fixtures/nopanic in release mode, where the
+/// optimizer decides whether the guard #[nopanic] adds can be removed.
+fn build_fixture(bin: &str) -> Output {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/nopanic");
+    Command::new(env!("CARGO"))
+        .args(["build", "--release", "--quiet", "--bin", bin])
+        .current_dir(dir)
+        .env("CARGO_TARGET_DIR", env!("CARGO_TARGET_TMPDIR"))
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn nopanic_fails_to_link_if_function_can_panic() {
+    let output = build_fixture("panics");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success(), "{stderr}");
+    assert!(
+        stderr.contains(
+            "error: function `index` annotated with #[nopanic] may panic"
+        ),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn nopanic_links_in_release_if_function_cannot_panic() {
+    let output = build_fixture("no_panic");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+}
@@ -0,0 +1,176 @@
+#![feature(allocator_api)]
+
+use std::rc::Rc;
+
+use stdx::alloc::FixedBufferAllocator;
+use stdx::array::Soa;
+
+#[derive(Soa, Debug, PartialEq)]
+struct Particle {
+    position: [f32; 3],
+    mass: f64,
+    id: u8,
+}
+
+#[derive(Soa)]
+pub struct Named {
+    pub name: String,
+    tags: Vec<&'static str>,
+}
+
+#[derive(Soa)]
+struct Counted {
+    counter: Rc<()>,
+    value: u16,
+}
+
+#[derive(Soa)]
+struct Empty {
+    unit: (),
+    nothing: [u64; 0],
+}
+
+fn particle(i: u8) -> Particle {
+    Particle {
+        position: [i as f32, 0.0, -(i as f32)],
+        mass: i as f64 * 0.5,
+        id: i,
+    }
+}
+
+#[test]
+fn soa_push_and_field_slices() {
+    let mut soa = SoaParticle::new();
+    assert!(soa.is_empty());
+    for i in 0..100 {
+        soa.push(particle(i));
+    }
+
+    assert_eq!(soa.len(), 100);
+    assert!(soa.capacity() >= 100);
+    assert_eq!(soa.ids(), (0..100).collect::<Vec<u8>>());
+    assert_eq!(soa.masss().len(), 100);
+    for (i, position) in soa.positions().iter().enumerate() {
+        assert_eq!(*position, [i as f32, 0.0, -(i as f32)]);
+    }
+}
+
+#[test]
+fn soa_get_and_get_mut() {
+    let mut soa = SoaParticle::default();
+    soa.push(particle(1));
+    soa.push(particle(2));
+
+    let p = soa.get(1).unwrap();
+    assert_eq!(*p.id, 2);
+    assert_eq!(*p.mass, 1.0);
+    assert!(soa.get(2).is_none());
+
+    let p = soa.get_mut(0).unwrap();
+    *p.mass = 10.0;
+    p.position[1] = 3.0;
+    assert_eq!(soa.masss()[0], 10.0);
+    assert_eq!(soa.positions()[0], [1.0, 3.0, -1.0]);
+
+    soa.ids_mut()[1] = 42;
+    assert_eq!(*soa.get(1).unwrap().id, 42);
+}
+
+#[test]
+fn soa_pop_and_swap_remove() {
+    let mut soa = SoaParticle::new();
+    for i in 0..4 {
+        soa.push(particle(i));
+    }
+
+    assert_eq!(soa.pop(), Some(particle(3)));
+    assert_eq!(soa.swap_remove(0), Some(particle(0)));
+    assert_eq!(soa.ids(), [2, 1]);
+    assert_eq!(soa.swap_remove(2), None);
+    assert_eq!(soa.swap_remove(1), Some(particle(1)));
+    assert_eq!(soa.pop(), Some(particle(2)));
+    assert_eq!(soa.pop(), None);
+}
+
+#[test]
+fn soa_non_copy_fields() {
+    let mut soa = SoaNamed::new();
+    for i in 0..10 {
+        soa.push(Named {
+            name: format!("item {i}"),
+            tags: vec!["a"; i],
+        });
+    }
+
+    assert_eq!(soa.names()[7], "item 7");
+    assert_eq!(soa.tagss()[3].len(), 3);
+    let removed = soa.swap_remove(2).unwrap();
+    assert_eq!(removed.name, "item 2");
+    assert_eq!(soa.names()[2], "item 9");
+}
+
+#[test]
+fn soa_drops_elements() {
+    let rc = Rc::new(());
+    let mut soa = SoaCounted::new();
+    for i in 0..20 {
+        soa.push(Counted {
+            counter: rc.clone(),
+            value: i,
+        });
+    }
+    assert_eq!(Rc::strong_count(&rc), 21);
+
+    soa.truncate(15);
+    assert_eq!(Rc::strong_count(&rc), 16);
+    assert_eq!(soa.values(), (0..15).collect::<Vec<u16>>());
+
+    drop(soa.pop());
+    assert_eq!(Rc::strong_count(&rc), 15);
+
+    soa.clear();
+    assert_eq!(Rc::strong_count(&rc), 1);
+    assert!(soa.is_empty());
+
+    soa.push(Counted {
+        counter: rc.clone(),
+        value: 0,
+    });
+    drop(soa);
+    assert_eq!(Rc::strong_count(&rc), 1);
+}
+
+#[test]
+fn soa_zero_sized_fields() {
+    let mut soa = SoaEmpty::new();
+    assert_eq!(soa.capacity(), usize::MAX);
+    for _ in 0..1000 {
+        soa.push(Empty {
+            unit: (),
+            nothing: [],
+        });
+    }
+    assert_eq!(soa.units().len(), 1000);
+    assert_eq!(soa.len(), 1000);
+}
+
+#[test]
+fn soa_custom_allocator() {
+    let mut buffer = [0u8; 1024];
+    let fba = FixedBufferAllocator::from_slice(&mut buffer);
+    let mut soa = SoaParticle::new_in(&fba);
+
+    assert!(soa.try_reserve(8).is_ok());
+    for i in 0..8 {
+        assert!(soa.try_push(particle(i)).is_ok());
+    }
+    assert_eq!(soa.ids(), [0, 1, 2, 3, 4, 5, 6, 7]);
+    assert!(soa.try_reserve(1000).is_err());
+    assert_eq!(soa.len(), 8);
+}
+
+#[test]
+fn soa_ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/soa_*.rs");
+}
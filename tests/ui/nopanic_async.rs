@@ -0,0 +1,6 @@
+use stdx::nopanic;
+
+#[nopanic]
+async fn foo() {}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use stdx::nopanic;
+
+#[nopanic(sometimes)]
+fn foo() {}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use stdx::nopanic;
+
+trait Foo {
+    #[nopanic]
+    fn foo(&self);
+}
+
+fn main() {}
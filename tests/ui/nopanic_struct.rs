@@ -0,0 +1,6 @@
+use stdx::nopanic;
+
+#[nopanic]
+struct Foo;
+
+fn main() {}
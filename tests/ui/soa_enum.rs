@@ -0,0 +1,9 @@
+use stdx::array::Soa;
+
+#[derive(Soa)]
+enum Foo {
+    A,
+    B,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use stdx::array::Soa;
+
+#[derive(Soa)]
+struct Foo<T> {
+    x: T,
+    y: u32,
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use stdx::array::Soa;
+
+#[derive(Soa)]
+struct Foo(u32, String);
+
+fn main() {}